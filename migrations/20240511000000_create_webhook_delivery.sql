@@ -0,0 +1,19 @@
+BEGIN;
+
+CREATE TABLE webhook_delivery (
+    id BIGSERIAL PRIMARY KEY,
+    subscriber TEXT NOT NULL,
+    stream TEXT NOT NULL,
+    entry_id TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE (subscriber, stream, entry_id)
+);
+
+CREATE INDEX webhook_delivery_idx_pending ON webhook_delivery(status) WHERE status = 'pending';
+
+COMMIT;
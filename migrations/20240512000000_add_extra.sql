@@ -0,0 +1,13 @@
+BEGIN;
+
+ALTER TABLE nft_mint ADD COLUMN extra JSONB;
+ALTER TABLE nft_transfer ADD COLUMN extra JSONB;
+ALTER TABLE nft_burn ADD COLUMN extra JSONB;
+ALTER TABLE potlock_donation ADD COLUMN extra JSONB;
+ALTER TABLE potlock_pot_project_donation ADD COLUMN extra JSONB;
+ALTER TABLE potlock_pot_donation ADD COLUMN extra JSONB;
+ALTER TABLE trade_pool ADD COLUMN extra JSONB;
+ALTER TABLE trade_swap ADD COLUMN extra JSONB;
+ALTER TABLE trade_pool_change ADD COLUMN extra JSONB;
+
+COMMIT;
@@ -0,0 +1,59 @@
+BEGIN;
+
+CREATE TABLE ft_transfer (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    old_owner_id TEXT NOT NULL,
+    new_owner_id TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    memo TEXT,
+    extra JSONB
+);
+
+SELECT create_hypertable('ft_transfer', 'timestamp');
+
+CREATE INDEX ft_transfer_idx_contract_id ON ft_transfer(contract_id);
+CREATE INDEX ft_transfer_idx_old_owner_id ON ft_transfer(old_owner_id);
+CREATE INDEX ft_transfer_idx_new_owner_id ON ft_transfer(new_owner_id);
+
+CREATE TABLE ft_mint (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    memo TEXT,
+    extra JSONB
+);
+
+SELECT create_hypertable('ft_mint', 'timestamp');
+
+CREATE INDEX ft_mint_idx_contract_id ON ft_mint(contract_id);
+CREATE INDEX ft_mint_idx_owner_id ON ft_mint(owner_id);
+
+CREATE TABLE ft_burn (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    memo TEXT,
+    extra JSONB
+);
+
+SELECT create_hypertable('ft_burn', 'timestamp');
+
+CREATE INDEX ft_burn_idx_contract_id ON ft_burn(contract_id);
+CREATE INDEX ft_burn_idx_owner_id ON ft_burn(owner_id);
+
+COMMIT;
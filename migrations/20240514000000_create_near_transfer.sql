@@ -0,0 +1,20 @@
+BEGIN;
+
+CREATE TABLE near_transfer (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    sender_id TEXT NOT NULL,
+    receiver_id TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('near_transfer', 'timestamp');
+
+CREATE INDEX near_transfer_idx_sender_id ON near_transfer(sender_id);
+CREATE INDEX near_transfer_idx_receiver_id ON near_transfer(receiver_id);
+
+COMMIT;
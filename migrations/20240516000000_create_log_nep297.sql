@@ -0,0 +1,23 @@
+BEGIN;
+
+CREATE TABLE log_nep297 (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    standard TEXT NOT NULL,
+    version TEXT NOT NULL,
+    event TEXT NOT NULL,
+    data JSONB,
+    extra JSONB
+);
+
+SELECT create_hypertable('log_nep297', 'timestamp');
+
+CREATE INDEX log_nep297_idx_contract_id ON log_nep297(contract_id);
+CREATE INDEX log_nep297_idx_standard_event ON log_nep297(standard, event);
+CREATE INDEX log_nep297_idx_data ON log_nep297 USING GIN (data jsonb_path_ops);
+
+COMMIT;
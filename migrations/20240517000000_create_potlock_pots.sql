@@ -0,0 +1,67 @@
+BEGIN;
+
+CREATE TABLE potlock_pot_config (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    pot_id TEXT NOT NULL,
+    created BOOLEAN NOT NULL,
+    owner_id TEXT NOT NULL,
+    admins TEXT[] NOT NULL,
+    chef_id TEXT,
+    name TEXT NOT NULL,
+    description TEXT,
+    application_start TIMESTAMPTZ NOT NULL,
+    application_end TIMESTAMPTZ NOT NULL,
+    public_round_start TIMESTAMPTZ NOT NULL,
+    public_round_end TIMESTAMPTZ NOT NULL,
+    config JSONB NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('potlock_pot_config', 'timestamp');
+
+CREATE INDEX potlock_pot_config_idx_pot_id ON potlock_pot_config(pot_id);
+CREATE INDEX potlock_pot_config_idx_owner_id ON potlock_pot_config(owner_id);
+
+CREATE TABLE potlock_project_status (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    project_id TEXT NOT NULL,
+    pot_id TEXT,
+    status TEXT NOT NULL,
+    reviewer_id TEXT,
+    review_notes TEXT,
+    extra JSONB
+);
+
+SELECT create_hypertable('potlock_project_status', 'timestamp');
+
+CREATE INDEX potlock_project_status_idx_project_id ON potlock_project_status(project_id);
+CREATE INDEX potlock_project_status_idx_pot_id ON potlock_project_status(pot_id);
+
+CREATE TABLE potlock_pot_payout (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    payout_id TEXT NOT NULL,
+    pot_id TEXT NOT NULL,
+    project_id TEXT NOT NULL,
+    amount NUMERIC NOT NULL,
+    paid_at TIMESTAMPTZ NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('potlock_pot_payout', 'timestamp');
+
+CREATE INDEX potlock_pot_payout_idx_pot_id ON potlock_pot_payout(pot_id);
+CREATE INDEX potlock_pot_payout_idx_project_id ON potlock_pot_payout(project_id);
+
+COMMIT;
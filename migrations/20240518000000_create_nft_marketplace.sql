@@ -0,0 +1,125 @@
+BEGIN;
+
+CREATE TABLE nft_approve (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    account_id TEXT NOT NULL,
+    approval_id BIGINT NOT NULL,
+    msg TEXT,
+    extra JSONB
+);
+
+SELECT create_hypertable('nft_approve', 'timestamp');
+
+CREATE INDEX nft_approve_idx_contract_id ON nft_approve(contract_id);
+CREATE INDEX nft_approve_idx_owner_id ON nft_approve(owner_id);
+CREATE INDEX nft_approve_idx_account_id ON nft_approve(account_id);
+
+CREATE TABLE nft_revoke (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    account_id TEXT,
+    extra JSONB
+);
+
+SELECT create_hypertable('nft_revoke', 'timestamp');
+
+CREATE INDEX nft_revoke_idx_contract_id ON nft_revoke(contract_id);
+CREATE INDEX nft_revoke_idx_owner_id ON nft_revoke(owner_id);
+
+CREATE TABLE nft_list (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    nft_contract_id TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    seller_id TEXT NOT NULL,
+    price NUMERIC NOT NULL,
+    ft_id TEXT NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('nft_list', 'timestamp');
+
+CREATE INDEX nft_list_idx_contract_id ON nft_list(contract_id);
+CREATE INDEX nft_list_idx_nft_contract_id ON nft_list(nft_contract_id);
+CREATE INDEX nft_list_idx_seller_id ON nft_list(seller_id);
+
+CREATE TABLE nft_unlist (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    nft_contract_id TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    seller_id TEXT NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('nft_unlist', 'timestamp');
+
+CREATE INDEX nft_unlist_idx_contract_id ON nft_unlist(contract_id);
+CREATE INDEX nft_unlist_idx_nft_contract_id ON nft_unlist(nft_contract_id);
+CREATE INDEX nft_unlist_idx_seller_id ON nft_unlist(seller_id);
+
+CREATE TABLE nft_sale (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    contract_id TEXT NOT NULL,
+    nft_contract_id TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    seller_id TEXT NOT NULL,
+    buyer_id TEXT NOT NULL,
+    price NUMERIC NOT NULL,
+    ft_id TEXT NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('nft_sale', 'timestamp');
+
+CREATE INDEX nft_sale_idx_contract_id ON nft_sale(contract_id);
+CREATE INDEX nft_sale_idx_nft_contract_id ON nft_sale(nft_contract_id);
+CREATE INDEX nft_sale_idx_seller_id ON nft_sale(seller_id);
+CREATE INDEX nft_sale_idx_buyer_id ON nft_sale(buyer_id);
+
+-- Current state of every listing, derived from nft_list, nft_unlist and nft_sale. Unlisted
+-- and sold listings are kept with active = FALSE, so that events of earlier blocks that
+-- arrive later don't bring them back.
+CREATE TABLE nft_listing (
+    marketplace_id TEXT NOT NULL,
+    nft_contract_id TEXT NOT NULL,
+    token_id TEXT NOT NULL,
+    seller_id TEXT NOT NULL,
+    price NUMERIC,
+    ft_id TEXT,
+    timestamp TIMESTAMPTZ NOT NULL,
+    block_height BIGINT NOT NULL,
+    active BOOLEAN NOT NULL,
+    PRIMARY KEY (marketplace_id, nft_contract_id, token_id)
+);
+
+CREATE INDEX nft_listing_idx_active_nft_contract_id ON nft_listing(nft_contract_id, token_id) WHERE active;
+CREATE INDEX nft_listing_idx_active_seller_id ON nft_listing(seller_id) WHERE active;
+CREATE INDEX nft_listing_idx_block_height ON nft_listing(block_height);
+
+COMMIT;
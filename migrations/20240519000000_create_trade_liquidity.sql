@@ -0,0 +1,43 @@
+BEGIN;
+
+CREATE TABLE trade_liquidity_add (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    trader TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    pool TEXT NOT NULL,
+    tokens TEXT[] NOT NULL,
+    amounts NUMERIC[] NOT NULL,
+    shares NUMERIC NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('trade_liquidity_add', 'timestamp');
+
+CREATE INDEX trade_liquidity_add_idx_trader ON trade_liquidity_add(trader);
+CREATE INDEX trade_liquidity_add_idx_pool ON trade_liquidity_add(pool);
+CREATE INDEX trade_liquidity_add_idx_tokens ON trade_liquidity_add USING GIN (tokens);
+
+CREATE TABLE trade_liquidity_remove (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    trader TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    pool TEXT NOT NULL,
+    tokens TEXT[] NOT NULL,
+    amounts NUMERIC[] NOT NULL,
+    shares NUMERIC NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('trade_liquidity_remove', 'timestamp');
+
+CREATE INDEX trade_liquidity_remove_idx_trader ON trade_liquidity_remove(trader);
+CREATE INDEX trade_liquidity_remove_idx_pool ON trade_liquidity_remove(pool);
+CREATE INDEX trade_liquidity_remove_idx_tokens ON trade_liquidity_remove USING GIN (tokens);
+
+COMMIT;
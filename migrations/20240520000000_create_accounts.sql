@@ -0,0 +1,52 @@
+BEGIN;
+
+CREATE TABLE account_create (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    account_id TEXT NOT NULL,
+    creator_id TEXT NOT NULL,
+    initial_balance NUMERIC NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('account_create', 'timestamp');
+
+CREATE INDEX account_create_idx_account_id ON account_create(account_id);
+CREATE INDEX account_create_idx_creator_id ON account_create(creator_id);
+
+CREATE TABLE account_delete (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    account_id TEXT NOT NULL,
+    beneficiary_id TEXT NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('account_delete', 'timestamp');
+
+CREATE INDEX account_delete_idx_account_id ON account_delete(account_id);
+CREATE INDEX account_delete_idx_beneficiary_id ON account_delete(beneficiary_id);
+
+CREATE TABLE contract_deploy (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    account_id TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('contract_deploy', 'timestamp');
+
+CREATE INDEX contract_deploy_idx_account_id ON contract_deploy(account_id);
+CREATE INDEX contract_deploy_idx_code_hash ON contract_deploy(code_hash);
+
+COMMIT;
@@ -0,0 +1,25 @@
+BEGIN;
+
+CREATE TABLE transactions (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    transaction_id TEXT NOT NULL,
+    block_height BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    signer_id TEXT NOT NULL,
+    receiver_id TEXT NOT NULL,
+    gas_burnt BIGINT NOT NULL,
+    tokens_burnt NUMERIC NOT NULL,
+    status TEXT NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('transactions', 'timestamp');
+
+-- A transaction is stored once. Unique indexes of hypertables have to include the
+-- partition column.
+CREATE UNIQUE INDEX transactions_idx_transaction_id ON transactions(transaction_id, timestamp);
+CREATE INDEX transactions_idx_signer_id ON transactions(signer_id);
+CREATE INDEX transactions_idx_receiver_id ON transactions(receiver_id);
+
+COMMIT;
@@ -0,0 +1,74 @@
+BEGIN;
+
+CREATE TABLE token_price (
+    id SERIAL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    block_height BIGINT NOT NULL,
+    token_id TEXT NOT NULL,
+    price_usd NUMERIC NOT NULL,
+    decimals BIGINT NOT NULL,
+    extra JSONB
+);
+
+SELECT create_hypertable('token_price', 'timestamp');
+
+CREATE INDEX token_price_idx_token_id ON token_price(token_id, timestamp DESC);
+
+-- The price point of a token closest to `at`, before or after it. The price feed is
+-- sampled, so the point just after a row is often closer than the one before it, and
+-- rows before the token's first price point still get a value. Ties go to the earlier
+-- point.
+CREATE FUNCTION nearest_token_price(token TEXT, at TIMESTAMPTZ)
+RETURNS TABLE (price_usd NUMERIC, decimals BIGINT) AS $$
+    SELECT price_usd, decimals FROM (
+        (
+            SELECT price_usd, decimals, timestamp FROM token_price
+            WHERE token_id = token AND timestamp <= at
+            ORDER BY timestamp DESC LIMIT 1
+        )
+        UNION ALL
+        (
+            SELECT price_usd, decimals, timestamp FROM token_price
+            WHERE token_id = token AND timestamp > at
+            ORDER BY timestamp LIMIT 1
+        )
+    ) AS nearest
+    ORDER BY abs(extract(epoch FROM nearest.timestamp - at)), nearest.timestamp
+    LIMIT 1
+$$ LANGUAGE sql STABLE;
+
+-- Each row valued at the nearest price of its token. Amounts in NEAR are valued at the
+-- price of wrap.near, and are always in yoctoNEAR (24 decimals).
+CREATE VIEW trade_pool_usd AS
+SELECT trade_pool.*,
+    trade_pool.amount_in / power(10::NUMERIC, price_in.decimals) * price_in.price_usd AS amount_in_usd,
+    trade_pool.amount_out / power(10::NUMERIC, price_out.decimals) * price_out.price_usd AS amount_out_usd
+FROM trade_pool
+LEFT JOIN LATERAL nearest_token_price(trade_pool.token_in, trade_pool.timestamp) price_in ON TRUE
+LEFT JOIN LATERAL nearest_token_price(trade_pool.token_out, trade_pool.timestamp) price_out ON TRUE;
+
+CREATE VIEW potlock_donation_usd AS
+SELECT potlock_donation.*,
+    potlock_donation.total_amount / power(10::NUMERIC, price.decimals) * price.price_usd AS total_amount_usd,
+    potlock_donation.protocol_fee / power(10::NUMERIC, price.decimals) * price.price_usd AS protocol_fee_usd,
+    potlock_donation.referrer_fee / power(10::NUMERIC, price.decimals) * price.price_usd AS referrer_fee_usd
+FROM potlock_donation
+LEFT JOIN LATERAL (
+    SELECT price_usd, CASE potlock_donation.ft_id WHEN 'near' THEN 24 ELSE decimals END AS decimals
+    FROM nearest_token_price(
+        CASE potlock_donation.ft_id WHEN 'near' THEN 'wrap.near' ELSE potlock_donation.ft_id END,
+        potlock_donation.timestamp
+    )
+) price ON TRUE;
+
+CREATE VIEW nft_transfer_usd AS
+SELECT nft_transfer.*,
+    ARRAY(
+        SELECT token_price_near / power(10::NUMERIC, 24) * price.price_usd
+        FROM unnest(nft_transfer.token_prices_near) WITH ORDINALITY AS prices(token_price_near, i)
+        ORDER BY i
+    ) AS token_prices_usd
+FROM nft_transfer
+LEFT JOIN LATERAL nearest_token_price('wrap.near', nft_transfer.timestamp) price ON TRUE;
+
+COMMIT;
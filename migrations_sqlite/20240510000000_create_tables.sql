@@ -0,0 +1,172 @@
+CREATE TABLE nft_transfer (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    contract_id TEXT NOT NULL,
+    old_owner_id TEXT NOT NULL,
+    new_owner_id TEXT NOT NULL,
+    token_ids TEXT NOT NULL,
+    token_prices_near TEXT NOT NULL,
+    memo TEXT
+);
+
+CREATE INDEX nft_transfer_idx_timestamp ON nft_transfer(timestamp);
+CREATE INDEX nft_transfer_idx_old_owner_id ON nft_transfer(old_owner_id);
+CREATE INDEX nft_transfer_idx_new_owner_id ON nft_transfer(new_owner_id);
+CREATE INDEX nft_transfer_idx_contract_id ON nft_transfer(contract_id);
+
+CREATE TABLE nft_mint (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    token_ids TEXT NOT NULL,
+    token_prices_near TEXT NULL,
+    memo TEXT
+);
+
+CREATE INDEX nft_mint_idx_timestamp ON nft_mint(timestamp);
+CREATE INDEX nft_mint_idx_owner_id ON nft_mint(owner_id);
+CREATE INDEX nft_mint_idx_contract_id ON nft_mint(contract_id);
+
+CREATE TABLE nft_burn (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    token_ids TEXT NOT NULL,
+    token_prices_near TEXT NULL,
+    memo TEXT
+);
+
+CREATE INDEX nft_burn_idx_timestamp ON nft_burn(timestamp);
+CREATE INDEX nft_burn_idx_owner_id ON nft_burn(owner_id);
+CREATE INDEX nft_burn_idx_contract_id ON nft_burn(contract_id);
+
+CREATE TABLE potlock_donation (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    donation_id INTEGER NOT NULL,
+    donor_id TEXT NOT NULL,
+    total_amount TEXT NOT NULL,
+    ft_id TEXT NOT NULL DEFAULT 'near',
+    message TEXT,
+    donated_at TEXT NOT NULL,
+    project_id TEXT NOT NULL,
+    protocol_fee TEXT NOT NULL,
+    referrer_id TEXT,
+    referrer_fee TEXT
+);
+
+CREATE INDEX potlock_donation_idx_timestamp ON potlock_donation(timestamp);
+CREATE INDEX potlock_donation_idx_donor_id ON potlock_donation(donor_id);
+CREATE INDEX potlock_donation_idx_project_id ON potlock_donation(project_id);
+CREATE INDEX potlock_donation_idx_referrer_id ON potlock_donation(referrer_id);
+
+CREATE TABLE potlock_pot_project_donation (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    donation_id INTEGER NOT NULL,
+    pot_id TEXT NOT NULL,
+    donor_id TEXT NOT NULL,
+    total_amount TEXT NOT NULL,
+    net_amount TEXT NOT NULL,
+    message TEXT,
+    donated_at TEXT NOT NULL,
+    project_id TEXT NOT NULL,
+    referrer_id TEXT,
+    referrer_fee TEXT,
+    protocol_fee TEXT NOT NULL,
+    chef_id TEXT,
+    chef_fee TEXT
+);
+
+CREATE INDEX potlock_pot_project_donation_idx_timestamp ON potlock_pot_project_donation(timestamp);
+CREATE INDEX potlock_pot_project_donation_idx_pot_id ON potlock_pot_project_donation(pot_id);
+CREATE INDEX potlock_pot_project_donation_idx_donor_id ON potlock_pot_project_donation(donor_id);
+CREATE INDEX potlock_pot_project_donation_idx_project_id ON potlock_pot_project_donation(project_id);
+CREATE INDEX potlock_pot_project_donation_idx_referrer_id ON potlock_pot_project_donation(referrer_id);
+
+CREATE TABLE potlock_pot_donation (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    donation_id INTEGER NOT NULL,
+    pot_id TEXT NOT NULL,
+    donor_id TEXT NOT NULL,
+    total_amount TEXT NOT NULL,
+    net_amount TEXT NOT NULL,
+    message TEXT,
+    donated_at TEXT NOT NULL,
+    referrer_id TEXT,
+    referrer_fee TEXT,
+    protocol_fee TEXT NOT NULL,
+    chef_id TEXT,
+    chef_fee TEXT
+);
+
+CREATE INDEX potlock_pot_donation_idx_timestamp ON potlock_pot_donation(timestamp);
+CREATE INDEX potlock_pot_donation_idx_pot_id ON potlock_pot_donation(pot_id);
+CREATE INDEX potlock_pot_donation_idx_donor_id ON potlock_pot_donation(donor_id);
+CREATE INDEX potlock_pot_donation_idx_referrer_id ON potlock_pot_donation(referrer_id);
+
+CREATE TABLE trade_pool (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    trader TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    pool TEXT NOT NULL,
+    token_in TEXT NOT NULL,
+    token_out TEXT NOT NULL,
+    amount_in TEXT NOT NULL,
+    amount_out TEXT NOT NULL
+);
+
+CREATE INDEX trade_pool_idx_timestamp ON trade_pool(timestamp);
+CREATE INDEX trade_pool_idx_trader ON trade_pool(trader);
+CREATE INDEX trade_pool_idx_pool ON trade_pool(pool);
+CREATE INDEX trade_pool_idx_token_in ON trade_pool(token_in);
+CREATE INDEX trade_pool_idx_token_out ON trade_pool(token_out);
+
+CREATE TABLE trade_swap (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    trader TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    balance_changes TEXT NOT NULL
+);
+
+CREATE INDEX trade_swap_idx_timestamp ON trade_swap(timestamp);
+CREATE INDEX trade_swap_idx_trader ON trade_swap(trader);
+
+CREATE TABLE trade_pool_change (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    receipt_id TEXT NOT NULL,
+    pool_id TEXT NOT NULL,
+    pool TEXT NOT NULL
+);
+
+CREATE INDEX trade_pool_change_idx_timestamp ON trade_pool_change(timestamp);
+CREATE INDEX trade_pool_change_idx_pool_id ON trade_pool_change(pool_id);
@@ -0,0 +1,9 @@
+ALTER TABLE nft_mint ADD COLUMN extra TEXT;
+ALTER TABLE nft_transfer ADD COLUMN extra TEXT;
+ALTER TABLE nft_burn ADD COLUMN extra TEXT;
+ALTER TABLE potlock_donation ADD COLUMN extra TEXT;
+ALTER TABLE potlock_pot_project_donation ADD COLUMN extra TEXT;
+ALTER TABLE potlock_pot_donation ADD COLUMN extra TEXT;
+ALTER TABLE trade_pool ADD COLUMN extra TEXT;
+ALTER TABLE trade_swap ADD COLUMN extra TEXT;
+ALTER TABLE trade_pool_change ADD COLUMN extra TEXT;
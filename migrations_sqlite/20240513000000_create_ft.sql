@@ -0,0 +1,52 @@
+CREATE TABLE ft_transfer (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    contract_id TEXT NOT NULL,
+    old_owner_id TEXT NOT NULL,
+    new_owner_id TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    memo TEXT,
+    extra TEXT
+);
+
+CREATE INDEX ft_transfer_idx_timestamp ON ft_transfer(timestamp);
+CREATE INDEX ft_transfer_idx_contract_id ON ft_transfer(contract_id);
+CREATE INDEX ft_transfer_idx_old_owner_id ON ft_transfer(old_owner_id);
+CREATE INDEX ft_transfer_idx_new_owner_id ON ft_transfer(new_owner_id);
+
+CREATE TABLE ft_mint (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    memo TEXT,
+    extra TEXT
+);
+
+CREATE INDEX ft_mint_idx_timestamp ON ft_mint(timestamp);
+CREATE INDEX ft_mint_idx_contract_id ON ft_mint(contract_id);
+CREATE INDEX ft_mint_idx_owner_id ON ft_mint(owner_id);
+
+CREATE TABLE ft_burn (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    contract_id TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    memo TEXT,
+    extra TEXT
+);
+
+CREATE INDEX ft_burn_idx_timestamp ON ft_burn(timestamp);
+CREATE INDEX ft_burn_idx_contract_id ON ft_burn(contract_id);
+CREATE INDEX ft_burn_idx_owner_id ON ft_burn(owner_id);
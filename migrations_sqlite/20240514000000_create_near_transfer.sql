@@ -0,0 +1,15 @@
+CREATE TABLE near_transfer (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    sender_id TEXT NOT NULL,
+    receiver_id TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX near_transfer_idx_timestamp ON near_transfer(timestamp);
+CREATE INDEX near_transfer_idx_sender_id ON near_transfer(sender_id);
+CREATE INDEX near_transfer_idx_receiver_id ON near_transfer(receiver_id);
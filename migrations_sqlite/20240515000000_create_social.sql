@@ -0,0 +1,70 @@
+CREATE TABLE social_post (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    account_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    text TEXT,
+    value TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX social_post_idx_timestamp ON social_post(timestamp);
+CREATE INDEX social_post_idx_account_id ON social_post(account_id);
+
+CREATE TABLE social_comment (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    account_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    text TEXT,
+    item_author_id TEXT NOT NULL,
+    item_block_height INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX social_comment_idx_timestamp ON social_comment(timestamp);
+CREATE INDEX social_comment_idx_account_id ON social_comment(account_id);
+CREATE INDEX social_comment_idx_item_author_id ON social_comment(item_author_id);
+
+CREATE TABLE social_like (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    account_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    item_author_id TEXT NOT NULL,
+    item_block_height INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX social_like_idx_timestamp ON social_like(timestamp);
+CREATE INDEX social_like_idx_account_id ON social_like(account_id);
+CREATE INDEX social_like_idx_item_author_id ON social_like(item_author_id);
+
+CREATE TABLE social_follow (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    account_id TEXT NOT NULL,
+    path TEXT NOT NULL,
+    target_id TEXT NOT NULL,
+    follow INTEGER NOT NULL,
+    value TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX social_follow_idx_timestamp ON social_follow(timestamp);
+CREATE INDEX social_follow_idx_account_id ON social_follow(account_id);
+CREATE INDEX social_follow_idx_target_id ON social_follow(target_id);
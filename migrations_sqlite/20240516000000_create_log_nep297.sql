@@ -0,0 +1,17 @@
+CREATE TABLE log_nep297 (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    contract_id TEXT NOT NULL,
+    standard TEXT NOT NULL,
+    version TEXT NOT NULL,
+    event TEXT NOT NULL,
+    data TEXT,
+    extra TEXT
+);
+
+CREATE INDEX log_nep297_idx_timestamp ON log_nep297(timestamp);
+CREATE INDEX log_nep297_idx_contract_id ON log_nep297(contract_id);
+CREATE INDEX log_nep297_idx_standard_event ON log_nep297(standard, event);
@@ -0,0 +1,60 @@
+CREATE TABLE potlock_pot_config (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    pot_id TEXT NOT NULL,
+    created INTEGER NOT NULL,
+    owner_id TEXT NOT NULL,
+    admins TEXT NOT NULL,
+    chef_id TEXT,
+    name TEXT NOT NULL,
+    description TEXT,
+    application_start TEXT NOT NULL,
+    application_end TEXT NOT NULL,
+    public_round_start TEXT NOT NULL,
+    public_round_end TEXT NOT NULL,
+    config TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX potlock_pot_config_idx_timestamp ON potlock_pot_config(timestamp);
+CREATE INDEX potlock_pot_config_idx_pot_id ON potlock_pot_config(pot_id);
+CREATE INDEX potlock_pot_config_idx_owner_id ON potlock_pot_config(owner_id);
+
+CREATE TABLE potlock_project_status (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    project_id TEXT NOT NULL,
+    pot_id TEXT,
+    status TEXT NOT NULL,
+    reviewer_id TEXT,
+    review_notes TEXT,
+    extra TEXT
+);
+
+CREATE INDEX potlock_project_status_idx_timestamp ON potlock_project_status(timestamp);
+CREATE INDEX potlock_project_status_idx_project_id ON potlock_project_status(project_id);
+CREATE INDEX potlock_project_status_idx_pot_id ON potlock_project_status(pot_id);
+
+CREATE TABLE potlock_pot_payout (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    payout_id TEXT NOT NULL,
+    pot_id TEXT NOT NULL,
+    project_id TEXT NOT NULL,
+    amount TEXT NOT NULL,
+    paid_at TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX potlock_pot_payout_idx_timestamp ON potlock_pot_payout(timestamp);
+CREATE INDEX potlock_pot_payout_idx_pot_id ON potlock_pot_payout(pot_id);
+CREATE INDEX potlock_pot_payout_idx_project_id ON potlock_pot_payout(project_id);
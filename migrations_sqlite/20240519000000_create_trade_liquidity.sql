@@ -0,0 +1,35 @@
+CREATE TABLE trade_liquidity_add (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    trader TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    pool TEXT NOT NULL,
+    tokens TEXT NOT NULL,
+    amounts TEXT NOT NULL,
+    shares TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX trade_liquidity_add_idx_timestamp ON trade_liquidity_add(timestamp);
+CREATE INDEX trade_liquidity_add_idx_trader ON trade_liquidity_add(trader);
+CREATE INDEX trade_liquidity_add_idx_pool ON trade_liquidity_add(pool);
+
+CREATE TABLE trade_liquidity_remove (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    trader TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    pool TEXT NOT NULL,
+    tokens TEXT NOT NULL,
+    amounts TEXT NOT NULL,
+    shares TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX trade_liquidity_remove_idx_timestamp ON trade_liquidity_remove(timestamp);
+CREATE INDEX trade_liquidity_remove_idx_trader ON trade_liquidity_remove(trader);
+CREATE INDEX trade_liquidity_remove_idx_pool ON trade_liquidity_remove(pool);
@@ -0,0 +1,45 @@
+CREATE TABLE account_create (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    account_id TEXT NOT NULL,
+    creator_id TEXT NOT NULL,
+    initial_balance TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX account_create_idx_timestamp ON account_create(timestamp);
+CREATE INDEX account_create_idx_account_id ON account_create(account_id);
+CREATE INDEX account_create_idx_creator_id ON account_create(creator_id);
+
+CREATE TABLE account_delete (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    account_id TEXT NOT NULL,
+    beneficiary_id TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX account_delete_idx_timestamp ON account_delete(timestamp);
+CREATE INDEX account_delete_idx_account_id ON account_delete(account_id);
+CREATE INDEX account_delete_idx_beneficiary_id ON account_delete(beneficiary_id);
+
+CREATE TABLE contract_deploy (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    receipt_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    account_id TEXT NOT NULL,
+    code_hash TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX contract_deploy_idx_timestamp ON contract_deploy(timestamp);
+CREATE INDEX contract_deploy_idx_account_id ON contract_deploy(account_id);
+CREATE INDEX contract_deploy_idx_code_hash ON contract_deploy(code_hash);
@@ -0,0 +1,18 @@
+CREATE TABLE transactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    transaction_id TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    block_hash TEXT NOT NULL,
+    signer_id TEXT NOT NULL,
+    receiver_id TEXT NOT NULL,
+    gas_burnt INTEGER NOT NULL,
+    tokens_burnt TEXT NOT NULL,
+    status TEXT NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX transactions_idx_timestamp ON transactions(timestamp);
+CREATE UNIQUE INDEX transactions_idx_transaction_id ON transactions(transaction_id, timestamp);
+CREATE INDEX transactions_idx_signer_id ON transactions(signer_id);
+CREATE INDEX transactions_idx_receiver_id ON transactions(receiver_id);
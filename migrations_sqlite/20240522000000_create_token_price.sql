@@ -0,0 +1,12 @@
+CREATE TABLE token_price (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TEXT NOT NULL,
+    block_height INTEGER NOT NULL,
+    token_id TEXT NOT NULL,
+    price_usd TEXT NOT NULL,
+    decimals INTEGER NOT NULL,
+    extra TEXT
+);
+
+CREATE INDEX token_price_idx_timestamp ON token_price(timestamp);
+CREATE INDEX token_price_idx_token_id ON token_price(token_id, timestamp);
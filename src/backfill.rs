@@ -0,0 +1,1894 @@
+//! Bulk load of archived events into Postgres, for history that's no longer in the streams.
+//! Reads the recorded JSONL files in a directory (decoded with the same event types as
+//! the handlers and skipped if they don't pass `FILTERS_FILE`) and, with the `parquet`
+//! feature, the `{directory}/{stream}/**.parquet` files of the Parquet archive, whose
+//! columns are already the columns of the tables and whose rows were filtered when written.
+//!
+//! Rows are loaded with `COPY` into a temporary table, where reverts recorded in the
+//! archive remove them, and inserted from there unless an identical row is already
+//! stored, so overlapping with live ingestion or earlier runs is safe. Rows are compared
+//! on every column, since the natural keys of events aren't unique (a receipt can swap in
+//! the same pool twice), and identical rows are inserted as many times as they're
+//! missing. Rows conflicting with a unique index (`transactions`) are skipped. Listings
+//! of backfilled marketplace events are recomputed in `nft_listing`.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use chrono::{DateTime, Utc};
+use events_api_redis_to_db::{
+    events::{
+        decode_entry, timestamp, AccountCreateEvent, AccountDeleteEvent, ContractDeployEvent,
+        Decoded, FtBurnEvent, FtMintEvent, FtTransferEvent, LogNep297Event, NearTransferEvent,
+        NftApproveEvent, NftBurnEvent, NftListEvent, NftMintEvent, NftRevokeEvent, NftSaleEvent,
+        NftTransferEvent, NftUnlistEvent, PotlockDonationEvent, PotlockPotConfigEvent,
+        PotlockPotDonationEvent, PotlockPotPayoutEvent, PotlockPotProjectDonationEvent,
+        PotlockProjectStatusEvent, SocialCommentEvent, SocialFollowEvent, SocialLikeEvent,
+        SocialPostEvent, TokenPriceEvent, TradeBalanceChangeSwapEvent, TradeLiquidityAddEvent,
+        TradeLiquidityRemoveEvent, TradePoolChangeEvent, TradeRawPoolSwapEvent, TransactionEvent,
+    },
+    redis_reader::parse_revert,
+};
+use redis::Value;
+use sqlx::PgConnection;
+
+use crate::{
+    filters::Filters,
+    jsonl::{jsonl_files, read_entries},
+    passes_filters,
+};
+
+const BATCH_SIZE: usize = 10_000;
+
+pub struct BackfillOptions {
+    pub directory: PathBuf,
+    pub from_block: u64,
+    pub to_block: u64,
+    /// Tables to load, all of them if empty.
+    pub streams: Vec<String>,
+}
+
+impl BackfillOptions {
+    /// Parses `<directory> <from_block> <to_block> [stream...]`, the block range is inclusive.
+    pub fn from_args(args: &[String]) -> anyhow::Result<Self> {
+        let [directory, from_block, to_block, streams @ ..] = args else {
+            anyhow::bail!("Usage: backfill <directory> <from_block> <to_block> [stream...]");
+        };
+        let options = Self {
+            directory: PathBuf::from(directory),
+            from_block: from_block.parse()?,
+            to_block: to_block.parse()?,
+            streams: streams.to_vec(),
+        };
+        for stream in &options.streams {
+            table(stream)?;
+        }
+        Ok(options)
+    }
+
+    fn includes(&self, stream: &str) -> bool {
+        self.streams.is_empty() || self.streams.iter().any(|s| s == stream)
+    }
+
+    fn in_range(&self, block_height: u64) -> bool {
+        (self.from_block..=self.to_block).contains(&block_height)
+    }
+}
+
+struct Table {
+    name: &'static str,
+    /// Columns of rows decoded from events, in order. Parquet files have their own.
+    columns: &'static [&'static str],
+}
+
+const TABLES: &[Table] = &[
+    Table {
+        name: "nft_mint",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "owner_id",
+            "token_ids",
+            "memo",
+            "extra",
+        ],
+    },
+    Table {
+        name: "nft_transfer",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "old_owner_id",
+            "new_owner_id",
+            "token_ids",
+            "memo",
+            "token_prices_near",
+            "extra",
+        ],
+    },
+    Table {
+        name: "nft_burn",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "owner_id",
+            "token_ids",
+            "memo",
+            "extra",
+        ],
+    },
+    Table {
+        name: "potlock_donation",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "donation_id",
+            "donor_id",
+            "total_amount",
+            "ft_id",
+            "message",
+            "donated_at",
+            "project_id",
+            "protocol_fee",
+            "referrer_id",
+            "referrer_fee",
+            "extra",
+        ],
+    },
+    Table {
+        name: "potlock_pot_project_donation",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "donation_id",
+            "pot_id",
+            "donor_id",
+            "total_amount",
+            "net_amount",
+            "message",
+            "donated_at",
+            "project_id",
+            "referrer_id",
+            "referrer_fee",
+            "protocol_fee",
+            "chef_id",
+            "chef_fee",
+            "extra",
+        ],
+    },
+    Table {
+        name: "potlock_pot_donation",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "donation_id",
+            "pot_id",
+            "donor_id",
+            "total_amount",
+            "net_amount",
+            "message",
+            "donated_at",
+            "referrer_id",
+            "referrer_fee",
+            "protocol_fee",
+            "chef_id",
+            "chef_fee",
+            "extra",
+        ],
+    },
+    Table {
+        name: "trade_pool",
+        columns: &[
+            "timestamp",
+            "trader",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "pool",
+            "token_in",
+            "token_out",
+            "amount_in",
+            "amount_out",
+            "extra",
+        ],
+    },
+    Table {
+        name: "trade_swap",
+        columns: &[
+            "timestamp",
+            "trader",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "balance_changes",
+            "extra",
+        ],
+    },
+    Table {
+        name: "trade_pool_change",
+        columns: &[
+            "timestamp",
+            "receipt_id",
+            "block_height",
+            "pool_id",
+            "pool",
+            "extra",
+        ],
+    },
+    Table {
+        name: "ft_transfer",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "old_owner_id",
+            "new_owner_id",
+            "amount",
+            "memo",
+            "extra",
+        ],
+    },
+    Table {
+        name: "ft_mint",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "owner_id",
+            "amount",
+            "memo",
+            "extra",
+        ],
+    },
+    Table {
+        name: "ft_burn",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "owner_id",
+            "amount",
+            "memo",
+            "extra",
+        ],
+    },
+    Table {
+        name: "near_transfer",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "sender_id",
+            "receiver_id",
+            "amount",
+            "extra",
+        ],
+    },
+    Table {
+        name: "social_post",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "account_id",
+            "path",
+            "text",
+            "value",
+            "extra",
+        ],
+    },
+    Table {
+        name: "social_comment",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "account_id",
+            "path",
+            "text",
+            "item_author_id",
+            "item_block_height",
+            "value",
+            "extra",
+        ],
+    },
+    Table {
+        name: "social_like",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "account_id",
+            "path",
+            "item_author_id",
+            "item_block_height",
+            "value",
+            "extra",
+        ],
+    },
+    Table {
+        name: "social_follow",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "account_id",
+            "path",
+            "target_id",
+            "follow",
+            "value",
+            "extra",
+        ],
+    },
+    Table {
+        name: "log_nep297",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "standard",
+            "version",
+            "event",
+            "data",
+            "extra",
+        ],
+    },
+    Table {
+        name: "potlock_pot_config",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "pot_id",
+            "created",
+            "owner_id",
+            "admins",
+            "chef_id",
+            "name",
+            "description",
+            "application_start",
+            "application_end",
+            "public_round_start",
+            "public_round_end",
+            "config",
+            "extra",
+        ],
+    },
+    Table {
+        name: "potlock_project_status",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "project_id",
+            "pot_id",
+            "status",
+            "reviewer_id",
+            "review_notes",
+            "extra",
+        ],
+    },
+    Table {
+        name: "potlock_pot_payout",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "payout_id",
+            "pot_id",
+            "project_id",
+            "amount",
+            "paid_at",
+            "extra",
+        ],
+    },
+    Table {
+        name: "nft_approve",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "owner_id",
+            "token_id",
+            "account_id",
+            "approval_id",
+            "msg",
+            "extra",
+        ],
+    },
+    Table {
+        name: "nft_revoke",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "owner_id",
+            "token_id",
+            "account_id",
+            "extra",
+        ],
+    },
+    Table {
+        name: "nft_list",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "nft_contract_id",
+            "token_id",
+            "seller_id",
+            "price",
+            "ft_id",
+            "extra",
+        ],
+    },
+    Table {
+        name: "nft_unlist",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "nft_contract_id",
+            "token_id",
+            "seller_id",
+            "extra",
+        ],
+    },
+    Table {
+        name: "nft_sale",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "contract_id",
+            "nft_contract_id",
+            "token_id",
+            "seller_id",
+            "buyer_id",
+            "price",
+            "ft_id",
+            "extra",
+        ],
+    },
+    Table {
+        name: "trade_liquidity_add",
+        columns: &[
+            "timestamp",
+            "trader",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "pool",
+            "tokens",
+            "amounts",
+            "shares",
+            "extra",
+        ],
+    },
+    Table {
+        name: "trade_liquidity_remove",
+        columns: &[
+            "timestamp",
+            "trader",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "pool",
+            "tokens",
+            "amounts",
+            "shares",
+            "extra",
+        ],
+    },
+    Table {
+        name: "account_create",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "account_id",
+            "creator_id",
+            "initial_balance",
+            "extra",
+        ],
+    },
+    Table {
+        name: "account_delete",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "account_id",
+            "beneficiary_id",
+            "extra",
+        ],
+    },
+    Table {
+        name: "contract_deploy",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "receipt_id",
+            "block_height",
+            "account_id",
+            "code_hash",
+            "extra",
+        ],
+    },
+    Table {
+        name: "transactions",
+        columns: &[
+            "timestamp",
+            "transaction_id",
+            "block_height",
+            "block_hash",
+            "signer_id",
+            "receiver_id",
+            "gas_burnt",
+            "tokens_burnt",
+            "status",
+            "extra",
+        ],
+    },
+    Table {
+        name: "token_price",
+        columns: &[
+            "timestamp",
+            "block_height",
+            "token_id",
+            "price_usd",
+            "decimals",
+            "extra",
+        ],
+    },
+];
+
+fn table(stream: &str) -> anyhow::Result<&'static Table> {
+    TABLES
+        .iter()
+        .find(|table| table.name == stream)
+        .ok_or_else(|| anyhow::anyhow!("Unknown stream {stream}"))
+}
+
+type Row = Vec<Option<String>>;
+
+pub async fn backfill(
+    pg_pool: sqlx::PgPool,
+    filters: &Filters,
+    options: BackfillOptions,
+) -> anyhow::Result<()> {
+    let inserted = backfill_jsonl(&pg_pool, filters, &options).await?;
+    #[cfg(feature = "parquet")]
+    let inserted = inserted + parquet::backfill_parquet(&pg_pool, &options).await?;
+    log::info!(
+        "Backfilled {inserted} rows from blocks {} to {}",
+        options.from_block,
+        options.to_block
+    );
+    Ok(())
+}
+
+async fn backfill_jsonl(
+    pg_pool: &sqlx::PgPool,
+    filters: &Filters,
+    options: &BackfillOptions,
+) -> anyhow::Result<u64> {
+    // Temporary tables only exist in one session, so everything goes through one
+    // connection. Rows stay staged until every file is read, reverts remove staged rows
+    // only, the stored ones were reverted by live ingestion already.
+    let mut connection = pg_pool.acquire().await?;
+    let mut batches: HashMap<&str, Vec<Row>> = HashMap::new();
+    // Recordings can overlap after a restart of the recorder
+    let mut seen: HashMap<&str, HashSet<String>> = HashMap::new();
+    for path in jsonl_files(&options.directory)? {
+        log::info!("Backfilling from {}", path.display());
+        for entry in read_entries(&path)? {
+            let (stream, id, values) = entry?;
+            if !options.includes(&stream) {
+                continue;
+            }
+            let Ok(table) = table(&stream) else {
+                log::warn!("Skipping entry {id} of unknown stream {stream}");
+                continue;
+            };
+            if !seen.entry(table.name).or_default().insert(id.clone()) {
+                continue;
+            }
+            let rows = batches.entry(table.name).or_default();
+
+            if let Some(revert) = values.get("revert") {
+                let block_height = parse_revert(revert)?;
+                stage(&mut connection, table, table.columns, std::mem::take(rows)).await?;
+                sqlx::query(&format!(
+                    "DELETE FROM backfill_{} WHERE block_height > $1",
+                    table.name
+                ))
+                .bind(block_height as i64)
+                .execute(&mut *connection)
+                .await?;
+                continue;
+            }
+            // Recordings have every entry, the sinks only get the ones passing the filters
+            if !passes_filters(filters, &stream, &values) {
+                continue;
+            }
+
+            match decode(table.name, &values) {
+                Ok((block_height, row)) => {
+                    if options.in_range(block_height) {
+                        rows.push(row);
+                    }
+                }
+                // The handlers skip these too
+                Err(err) => log::error!("Failed to parse {stream} event {id}: {err:?}"),
+            }
+            if rows.len() >= BATCH_SIZE {
+                stage(&mut connection, table, table.columns, std::mem::take(rows)).await?;
+            }
+        }
+    }
+    let mut inserted = 0;
+    for (stream, rows) in batches {
+        let table = table(stream)?;
+        stage(&mut connection, table, table.columns, rows).await?;
+        inserted += insert_staged(&mut connection, table).await?;
+    }
+    Ok(inserted)
+}
+
+/// Copies `rows`, which have `columns`, into the temporary `backfill_{table}` table,
+/// creating it if needed.
+async fn stage(
+    connection: &mut PgConnection,
+    table: &Table,
+    columns: &[impl AsRef<str>],
+    rows: Vec<Row>,
+) -> anyhow::Result<()> {
+    let columns = columns
+        .iter()
+        .map(AsRef::as_ref)
+        .collect::<Vec<_>>()
+        .join(", ");
+    sqlx::query(&format!(
+        "CREATE TEMP TABLE IF NOT EXISTS backfill_{table} AS SELECT {columns} FROM {table} WITH NO DATA",
+        table = table.name,
+        columns = table.columns.join(", "),
+    ))
+    .execute(&mut *connection)
+    .await?;
+    if rows.is_empty() {
+        return Ok(());
+    }
+    let mut data = String::new();
+    for row in &rows {
+        write_csv_line(&mut data, row);
+    }
+    let mut copy_in = connection
+        .copy_in_raw(&format!(
+            "COPY backfill_{} ({columns}) FROM STDIN WITH (FORMAT csv)",
+            table.name
+        ))
+        .await?;
+    copy_in.send(data.into_bytes()).await?;
+    copy_in.finish().await?;
+    Ok(())
+}
+
+/// Inserts the staged rows of `table` that aren't stored yet and drops the temporary
+/// table, returns the number of inserted rows. A staged row is stored if there are at
+/// least as many identical stored rows as identical staged rows up to it.
+async fn insert_staged(connection: &mut PgConnection, table: &Table) -> anyhow::Result<u64> {
+    let columns = table.columns;
+    let staged: i64 = sqlx::query_scalar(&format!("SELECT count(*) FROM backfill_{}", table.name))
+        .fetch_one(&mut *connection)
+        .await?;
+    let inserted = sqlx::query(&format!(
+        "INSERT INTO {table} ({columns})
+        SELECT {columns} FROM (
+            SELECT *, row_number() OVER (PARTITION BY {columns}) AS copy FROM backfill_{table}
+        ) b
+        WHERE copy > (SELECT count(*) FROM {table} t WHERE t.timestamp = b.timestamp AND {matches})
+        ON CONFLICT DO NOTHING",
+        table = table.name,
+        columns = columns.join(", "),
+        matches = columns
+            .iter()
+            .map(|column| format!("t.{column} IS NOT DISTINCT FROM b.{column}"))
+            .collect::<Vec<_>>()
+            .join(" AND "),
+    ))
+    .execute(&mut *connection)
+    .await?
+    .rows_affected();
+    if inserted > 0 && MARKETPLACE_TABLES.contains(&table.name) {
+        update_listings(connection, table).await?;
+    }
+    sqlx::query(&format!("DROP TABLE backfill_{}", table.name))
+        .execute(&mut *connection)
+        .await?;
+    log::info!("Inserted {inserted} of {staged} rows into {}", table.name);
+    Ok(inserted)
+}
+
+/// Tables of the marketplace events that `nft_listing` is derived from.
+const MARKETPLACE_TABLES: &[&str] = &["nft_list", "nft_unlist", "nft_sale"];
+
+/// Recomputes the listings of the staged marketplace events from all stored events, in
+/// the order of the handlers' `set_listing`.
+async fn update_listings(connection: &mut PgConnection, table: &Table) -> anyhow::Result<()> {
+    let updated = sqlx::query(&format!(
+        "INSERT INTO nft_listing (marketplace_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active)
+        SELECT DISTINCT ON (contract_id, nft_contract_id, token_id)
+            contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active
+        FROM (
+            SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, TRUE AS active, 0 AS rank
+            FROM nft_list
+            UNION ALL
+            SELECT contract_id, nft_contract_id, token_id, seller_id, NULL, NULL, timestamp, block_height, FALSE, 1
+            FROM nft_unlist
+            UNION ALL
+            SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, FALSE, 2
+            FROM nft_sale
+        ) AS events
+        WHERE (contract_id, nft_contract_id, token_id) IN (
+            SELECT contract_id, nft_contract_id, token_id FROM backfill_{table}
+        )
+        ORDER BY contract_id, nft_contract_id, token_id, block_height DESC, rank DESC, COALESCE(price, 0) DESC
+        ON CONFLICT (marketplace_id, nft_contract_id, token_id) DO UPDATE SET
+            seller_id = EXCLUDED.seller_id,
+            price = EXCLUDED.price,
+            ft_id = EXCLUDED.ft_id,
+            timestamp = EXCLUDED.timestamp,
+            block_height = EXCLUDED.block_height,
+            active = EXCLUDED.active",
+        table = table.name,
+    ))
+    .execute(&mut *connection)
+    .await?
+    .rows_affected();
+    log::info!("Updated {updated} listings from {}", table.name);
+    Ok(())
+}
+
+/// Every value is quoted, so that only unquoted empty fields are NULL.
+fn write_csv_line(data: &mut String, row: &[Option<String>]) {
+    for (i, value) in row.iter().enumerate() {
+        if i > 0 {
+            data.push(',');
+        }
+        if let Some(value) = value {
+            data.push('"');
+            data.push_str(&value.replace('"', "\"\""));
+            data.push('"');
+        }
+    }
+    data.push('\n');
+}
+
+fn array_literal(values: impl IntoIterator<Item = Option<String>>) -> String {
+    let values = values
+        .into_iter()
+        .map(|value| match value {
+            Some(value) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+            None => "NULL".to_string(),
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", values.join(","))
+}
+
+/// Postgres keeps microseconds, and rounds when parsing more digits, while the handlers
+/// truncate.
+fn timestamp_literal(timestamp: DateTime<Utc>) -> String {
+    timestamp.format("%Y-%m-%dT%H:%M:%S%.6fZ").to_string()
+}
+
+fn block_timestamp(block_timestamp_nanosec: u128) -> Option<String> {
+    timestamp(block_timestamp_nanosec).map(timestamp_literal)
+}
+
+/// Decodes an entry into its block height and a row with the columns of its table.
+fn decode(stream: &str, values: &HashMap<String, Value>) -> anyhow::Result<(u64, Row)> {
+    Ok(match stream {
+        "nft_mint" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftMintEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.owner_id),
+                    Some(array_literal(event.token_ids.into_iter().map(Some))),
+                    event.memo,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "nft_transfer" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftTransferEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.old_owner_id),
+                    Some(event.new_owner_id),
+                    Some(array_literal(event.token_ids.into_iter().map(Some))),
+                    event.memo,
+                    Some(array_literal(
+                        event
+                            .token_prices_near
+                            .iter()
+                            .map(|price| Some(price.unwrap_or_default().to_string())),
+                    )),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "nft_burn" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftBurnEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.owner_id),
+                    Some(array_literal(event.token_ids.into_iter().map(Some))),
+                    event.memo,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "potlock_donation" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<PotlockDonationEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.donation_id.to_string()),
+                    Some(event.donor_id),
+                    Some(event.total_amount.to_string()),
+                    Some(event.ft_id),
+                    event.message,
+                    Some(timestamp_literal(event.donated_at)),
+                    Some(event.project_id),
+                    Some(event.protocol_fee.to_string()),
+                    event.referrer_id,
+                    event.referrer_fee.map(|fee| fee.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "potlock_pot_project_donation" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<PotlockPotProjectDonationEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.donation_id.to_string()),
+                    Some(event.pot_id),
+                    Some(event.donor_id),
+                    Some(event.total_amount.to_string()),
+                    Some(event.net_amount.to_string()),
+                    event.message,
+                    Some(timestamp_literal(event.donated_at)),
+                    Some(event.project_id),
+                    event.referrer_id,
+                    event.referrer_fee.map(|fee| fee.to_string()),
+                    Some(event.protocol_fee.to_string()),
+                    event.chef_id,
+                    event.chef_fee.map(|fee| fee.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "potlock_pot_donation" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<PotlockPotDonationEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.donation_id.to_string()),
+                    Some(event.pot_id),
+                    Some(event.donor_id),
+                    Some(event.total_amount.to_string()),
+                    Some(event.net_amount.to_string()),
+                    event.message,
+                    Some(timestamp_literal(event.donated_at)),
+                    event.referrer_id,
+                    event.referrer_fee.map(|fee| fee.to_string()),
+                    Some(event.protocol_fee.to_string()),
+                    event.chef_id,
+                    event.chef_fee.map(|fee| fee.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "trade_pool" => {
+            let Decoded {
+                context,
+                event: TradeRawPoolSwapEvent(event),
+                extra,
+            } = decode_entry::<TradeRawPoolSwapEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.trader),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.pool),
+                    Some(event.token_in),
+                    Some(event.token_out),
+                    Some(event.amount_in.to_string()),
+                    Some(event.amount_out.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "trade_swap" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<TradeBalanceChangeSwapEvent>(values)?;
+            let balance_changes = event
+                .balance_changes
+                .into_iter()
+                .map(|(k, v)| (k, serde_json::Value::String(v.to_string())))
+                .collect::<serde_json::Map<_, _>>();
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.trader),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(serde_json::Value::Object(balance_changes).to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "trade_pool_change" => {
+            let Decoded { event, extra, .. } = decode_entry::<TradePoolChangeEvent>(values)?;
+            (
+                event.block_height,
+                vec![
+                    block_timestamp(event.block_timestamp_nanosec),
+                    Some(event.receipt_id),
+                    Some(event.block_height.to_string()),
+                    Some(event.pool_id),
+                    Some(event.pool.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "ft_transfer" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<FtTransferEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.old_owner_id),
+                    Some(event.new_owner_id),
+                    Some(event.amount.to_string()),
+                    event.memo,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "ft_mint" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<FtMintEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.owner_id),
+                    Some(event.amount.to_string()),
+                    event.memo,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "ft_burn" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<FtBurnEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.owner_id),
+                    Some(event.amount.to_string()),
+                    event.memo,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "near_transfer" => {
+            let Decoded { event, extra, .. } = decode_entry::<NearTransferEvent>(values)?;
+            (
+                event.block_height,
+                vec![
+                    block_timestamp(event.block_timestamp_nanosec),
+                    Some(event.transaction_id),
+                    Some(event.receipt_id),
+                    Some(event.block_height.to_string()),
+                    Some(event.sender_id),
+                    Some(event.receiver_id),
+                    Some(event.amount.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "social_post" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<SocialPostEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.account_id),
+                    Some(event.path),
+                    event.text,
+                    Some(event.value.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "social_comment" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<SocialCommentEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.account_id),
+                    Some(event.path),
+                    event.text,
+                    Some(event.item_author_id),
+                    Some(event.item_block_height.to_string()),
+                    Some(event.value.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "social_like" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<SocialLikeEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.account_id),
+                    Some(event.path),
+                    Some(event.item_author_id),
+                    Some(event.item_block_height.to_string()),
+                    Some(event.value.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "social_follow" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<SocialFollowEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.account_id),
+                    Some(event.path),
+                    Some(event.target_id),
+                    Some(event.follow.to_string()),
+                    Some(event.value.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "log_nep297" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<LogNep297Event>(values)?;
+            anyhow::ensure!(
+                !event.is_promoted(),
+                "{} logs are stored in their own tables",
+                event.standard
+            );
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.standard),
+                    Some(event.version),
+                    Some(event.event),
+                    event.data.map(|value| value.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "potlock_pot_config" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<PotlockPotConfigEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.pot_id),
+                    Some(event.created.to_string()),
+                    Some(event.owner_id),
+                    Some(array_literal(event.admins.into_iter().map(Some))),
+                    event.chef_id,
+                    Some(event.name),
+                    event.description,
+                    Some(timestamp_literal(event.application_start)),
+                    Some(timestamp_literal(event.application_end)),
+                    Some(timestamp_literal(event.public_round_start)),
+                    Some(timestamp_literal(event.public_round_end)),
+                    Some(event.config.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "potlock_project_status" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<PotlockProjectStatusEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.project_id),
+                    event.pot_id,
+                    Some(event.status),
+                    event.reviewer_id,
+                    event.review_notes,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "potlock_pot_payout" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<PotlockPotPayoutEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.payout_id),
+                    Some(event.pot_id),
+                    Some(event.project_id),
+                    Some(event.amount.to_string()),
+                    Some(timestamp_literal(event.paid_at)),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "nft_approve" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftApproveEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.owner_id),
+                    Some(event.token_id),
+                    Some(event.account_id),
+                    Some(event.approval_id.to_string()),
+                    event.msg,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "nft_revoke" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftRevokeEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.owner_id),
+                    Some(event.token_id),
+                    event.account_id,
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "nft_list" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftListEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.nft_contract_id),
+                    Some(event.token_id),
+                    Some(event.seller_id),
+                    Some(event.price.to_string()),
+                    Some(event.ft_id),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "nft_unlist" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftUnlistEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.nft_contract_id),
+                    Some(event.token_id),
+                    Some(event.seller_id),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "nft_sale" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<NftSaleEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(context.contract_id),
+                    Some(event.nft_contract_id),
+                    Some(event.token_id),
+                    Some(event.seller_id),
+                    Some(event.buyer_id),
+                    Some(event.price.to_string()),
+                    Some(event.ft_id),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "trade_liquidity_add" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<TradeLiquidityAddEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.trader),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.pool),
+                    Some(array_literal(event.tokens.into_iter().map(Some))),
+                    Some(array_literal(
+                        event.amounts.iter().map(|amount| Some(amount.to_string())),
+                    )),
+                    Some(event.shares.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "trade_liquidity_remove" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<TradeLiquidityRemoveEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.trader),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.pool),
+                    Some(array_literal(event.tokens.into_iter().map(Some))),
+                    Some(array_literal(
+                        event.amounts.iter().map(|amount| Some(amount.to_string())),
+                    )),
+                    Some(event.shares.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "account_create" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<AccountCreateEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.account_id),
+                    Some(event.creator_id),
+                    Some(event.initial_balance.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "account_delete" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<AccountDeleteEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.account_id),
+                    Some(event.beneficiary_id),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "contract_deploy" => {
+            let Decoded {
+                context,
+                event,
+                extra,
+            } = decode_entry::<ContractDeployEvent>(values)?;
+            (
+                context.block_height,
+                vec![
+                    block_timestamp(context.block_timestamp_nanosec),
+                    Some(context.transaction_id),
+                    Some(context.receipt_id),
+                    Some(context.block_height.to_string()),
+                    Some(event.account_id),
+                    Some(event.code_hash),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "transactions" => {
+            let Decoded { event, extra, .. } = decode_entry::<TransactionEvent>(values)?;
+            (
+                event.block_height,
+                vec![
+                    block_timestamp(event.block_timestamp_nanosec),
+                    Some(event.transaction_id),
+                    Some(event.block_height.to_string()),
+                    Some(event.block_hash),
+                    Some(event.signer_id),
+                    Some(event.receiver_id),
+                    Some(event.gas_burnt.to_string()),
+                    Some(event.tokens_burnt.to_string()),
+                    Some(event.status),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        "token_price" => {
+            let Decoded { event, extra, .. } = decode_entry::<TokenPriceEvent>(values)?;
+            (
+                event.block_height,
+                vec![
+                    block_timestamp(event.block_timestamp_nanosec),
+                    Some(event.block_height.to_string()),
+                    Some(event.token_id),
+                    Some(event.price_usd),
+                    Some(event.decimals.to_string()),
+                    extra.map(|extra| extra.to_string()),
+                ],
+            )
+        }
+        other => anyhow::bail!("Unknown stream {other}"),
+    })
+}
+
+#[cfg(feature = "parquet")]
+mod parquet {
+    use std::{fs::File, path::Path};
+
+    use arrow::{
+        array::{Array, AsArray},
+        datatypes::{DataType, Int64Type, TimeUnit, TimestampNanosecondType},
+    };
+    use chrono::DateTime;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use sqlx::PgConnection;
+
+    use super::{
+        array_literal, insert_staged, stage, timestamp_literal, BackfillOptions, Row, Table, TABLES,
+    };
+    use crate::parquet_sink::Manifest;
+
+    /// Loads the archived files of every table, returns the number of inserted rows.
+    pub async fn backfill_parquet(
+        pg_pool: &sqlx::PgPool,
+        options: &BackfillOptions,
+    ) -> anyhow::Result<u64> {
+        let manifest = Manifest::read(&options.directory)?;
+        let mut connection = pg_pool.acquire().await?;
+        let mut inserted = 0;
+        for table in TABLES {
+            if options.includes(table.name) {
+                inserted += backfill_table(&mut connection, &manifest, table, options).await?;
+            }
+        }
+        Ok(inserted)
+    }
+
+    async fn backfill_table(
+        connection: &mut PgConnection,
+        manifest: &Manifest,
+        table: &Table,
+        options: &BackfillOptions,
+    ) -> anyhow::Result<u64> {
+        let directory = options.directory.join(table.name);
+        if !directory.is_dir() {
+            return Ok(0);
+        }
+        let mut paths = Vec::new();
+        parquet_files(&directory, &mut paths)?;
+        paths.sort();
+
+        // Staged together, so that identical rows in different files count as different
+        // rows, like in the table
+        for path in paths {
+            log::info!("Backfilling from {}", path.display());
+            let kept_block_height = path
+                .strip_prefix(&options.directory)
+                .ok()
+                .and_then(|file| manifest.kept_block_height(table.name, file))
+                .unwrap_or(u64::MAX);
+            for batch in ParquetRecordBatchReaderBuilder::try_new(File::open(&path)?)?.build()? {
+                let batch = batch?;
+                let schema = batch.schema();
+                let columns = schema
+                    .fields()
+                    .iter()
+                    .map(|field| field.name().as_str())
+                    .collect::<Vec<_>>();
+                let block_heights = batch
+                    .column_by_name("block_height")
+                    .ok_or_else(|| anyhow::anyhow!("No block_height in {}", path.display()))?
+                    .as_primitive::<Int64Type>();
+                let mut rows = Vec::new();
+                for i in 0..batch.num_rows() {
+                    let block_height = block_heights.value(i) as u64;
+                    if !options.in_range(block_height) || block_height > kept_block_height {
+                        continue;
+                    }
+                    rows.push(
+                        batch
+                            .columns()
+                            .iter()
+                            .map(|column| cell(column, i))
+                            .collect::<anyhow::Result<Row>>()?,
+                    );
+                }
+                stage(connection, table, &columns, rows).await?;
+            }
+        }
+        insert_staged(connection, table).await
+    }
+
+    fn parquet_files(directory: &Path, paths: &mut Vec<std::path::PathBuf>) -> anyhow::Result<()> {
+        for entry in std::fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                parquet_files(&path, paths)?;
+            } else if path
+                .extension()
+                .is_some_and(|extension| extension == "parquet")
+            {
+                paths.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts a value of the column types written by the Parquet sink to its text form.
+    fn cell(column: &dyn Array, i: usize) -> anyhow::Result<Option<String>> {
+        if column.is_null(i) {
+            return Ok(None);
+        }
+        Ok(Some(match column.data_type() {
+            DataType::Utf8 => column.as_string::<i32>().value(i).to_string(),
+            DataType::Int64 => column.as_primitive::<Int64Type>().value(i).to_string(),
+            DataType::Timestamp(TimeUnit::Nanosecond, _) => {
+                timestamp_literal(DateTime::from_timestamp_nanos(
+                    column.as_primitive::<TimestampNanosecondType>().value(i),
+                ))
+            }
+            DataType::List(_) => {
+                let values = column.as_list::<i32>().value(i);
+                let values = values.as_string::<i32>();
+                array_literal(
+                    (0..values.len())
+                        .map(|j| (!values.is_null(j)).then(|| values.value(j).to_string())),
+                )
+            }
+            other => anyhow::bail!("Unsupported column type {other}"),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer(id: &str, block_height: u64, amount: u64) -> String {
+        let transfer = serde_json::json!({
+            "sender_id": "alice.near",
+            "receiver_id": "bob.near",
+            "amount": amount.to_string(),
+            "transaction_id": "tx",
+            "receipt_id": "receipt",
+            "block_height": block_height,
+            "block_timestamp_nanosec": (1_700_000_000_000_000_000 + block_height as u128).to_string(),
+        });
+        serde_json::json!({
+            "stream": "near_transfer",
+            "id": id,
+            "fields": { "transfer": transfer.to_string() },
+        })
+        .to_string()
+    }
+
+    fn revert(id: &str, block_height: u64) -> String {
+        serde_json::json!({
+            "stream": "near_transfer",
+            "id": id,
+            "fields": { "revert": serde_json::json!({ "block_height": block_height }).to_string() },
+        })
+        .to_string()
+    }
+
+    fn options(name: &str, lines: &[String]) -> BackfillOptions {
+        let directory =
+            std::env::temp_dir().join(format!("backfill_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("0.jsonl"), lines.join("\n")).unwrap();
+        BackfillOptions {
+            directory,
+            from_block: 0,
+            to_block: 100,
+            streams: Vec::new(),
+        }
+    }
+
+    async fn stored(pg_pool: &sqlx::PgPool) -> Vec<(i64, String)> {
+        sqlx::query_as(
+            "SELECT block_height, amount::TEXT FROM near_transfer ORDER BY block_height, amount",
+        )
+        .fetch_all(pg_pool)
+        .await
+        .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn inserts_missing_copies_of_identical_rows(pg_pool: sqlx::PgPool) {
+        let stored_before = options("identical_stored", &[transfer("1-0", 1, 5)]);
+        assert_eq!(
+            backfill_jsonl(&pg_pool, &Filters::default(), &stored_before)
+                .await
+                .unwrap(),
+            1
+        );
+
+        // Two identical transfers in one receipt, and one that's the same but for the amount
+        let options = options(
+            "identical",
+            &[
+                transfer("1-0", 1, 5),
+                transfer("1-1", 1, 5),
+                transfer("1-2", 1, 6),
+                // Recorded twice
+                transfer("1-2", 1, 6),
+            ],
+        );
+        assert_eq!(
+            backfill_jsonl(&pg_pool, &Filters::default(), &options)
+                .await
+                .unwrap(),
+            2
+        );
+        let expected = [
+            (1, "5".to_string()),
+            (1, "5".to_string()),
+            (1, "6".to_string()),
+        ];
+        assert_eq!(stored(&pg_pool).await, expected);
+
+        assert_eq!(
+            backfill_jsonl(&pg_pool, &Filters::default(), &options)
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(stored(&pg_pool).await, expected);
+    }
+
+    #[sqlx::test]
+    async fn reverts_only_staged_rows(pg_pool: sqlx::PgPool) {
+        // Stored by live ingestion after the revert
+        let live = options("reverts_live", &[transfer("5-0", 5, 1)]);
+        backfill_jsonl(&pg_pool, &Filters::default(), &live)
+            .await
+            .unwrap();
+
+        let options = options(
+            "reverts",
+            &[
+                transfer("3-0", 3, 2),
+                transfer("6-0", 6, 3),
+                revert("7-0", 4),
+                transfer("8-0", 6, 4),
+            ],
+        );
+        assert_eq!(
+            backfill_jsonl(&pg_pool, &Filters::default(), &options)
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            stored(&pg_pool).await,
+            [
+                (3, "2".to_string()),
+                (5, "1".to_string()),
+                (6, "4".to_string())
+            ]
+        );
+    }
+
+    #[sqlx::test]
+    async fn skips_filtered_entries(pg_pool: sqlx::PgPool) {
+        let filters: Filters = serde_json::from_value(serde_json::json!({
+            "near_transfer": [{ "field": "amount", "min": 5 }],
+        }))
+        .unwrap();
+        let options = options(
+            "filtered",
+            &[
+                transfer("1-0", 1, 4),
+                transfer("2-0", 2, 5),
+                revert("3-0", 2),
+            ],
+        );
+        assert_eq!(
+            backfill_jsonl(&pg_pool, &filters, &options).await.unwrap(),
+            1
+        );
+        assert_eq!(stored(&pg_pool).await, [(2, "5".to_string())]);
+    }
+
+    #[sqlx::test]
+    async fn updates_listings_of_marketplace_events(pg_pool: sqlx::PgPool) {
+        let list = |id: &str, block_height: u64, price: u64| {
+            let context = serde_json::json!({
+                "transaction_id": "tx",
+                "receipt_id": "receipt",
+                "block_height": block_height,
+                "block_timestamp_nanosec": (1_700_000_000_000_000_000 + block_height as u128).to_string(),
+                "contract_id": "market.near",
+            });
+            let list = serde_json::json!({
+                "nft_contract_id": "nft.near",
+                "token_id": "1",
+                "seller_id": "alice.near",
+                "price": price.to_string(),
+                "ft_id": "near",
+            });
+            serde_json::json!({
+                "stream": "nft_list",
+                "id": id,
+                "fields": { "context": context.to_string(), "list": list.to_string() },
+            })
+            .to_string()
+        };
+        let options = options("listings", &[list("1-0", 1, 10), list("2-0", 2, 20)]);
+        assert_eq!(
+            backfill_jsonl(&pg_pool, &Filters::default(), &options)
+                .await
+                .unwrap(),
+            2
+        );
+        let listing: (i64, bool, String) =
+            sqlx::query_as("SELECT block_height, active, price::TEXT FROM nft_listing")
+                .fetch_one(&pg_pool)
+                .await
+                .unwrap();
+        assert_eq!(listing, (2, true, "20".to_string()));
+    }
+}
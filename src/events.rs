@@ -1,8 +1,9 @@
-use std::{collections::HashMap, num::ParseIntError};
+use std::{collections::HashMap, num::ParseIntError, sync::OnceLock};
 
 use chrono::prelude::{DateTime, Utc};
 use inindexer::near_utils::{dec_format, dec_format_vec};
-use serde::{Deserialize, Serialize};
+use redis::{FromRedisValue, Value};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 type TransactionId = String;
 type ReceiptId = String;
@@ -48,6 +49,54 @@ pub struct NftBurnEvent {
     pub memo: Option<String>,
 }
 
+/// NEP-178 approval of `account_id` (usually a marketplace) to transfer a token.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NftApproveEvent {
+    pub owner_id: AccountId,
+    pub token_id: NftTokenId,
+    pub account_id: AccountId,
+    pub approval_id: u64,
+    pub msg: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NftRevokeEvent {
+    pub owner_id: AccountId,
+    pub token_id: NftTokenId,
+    /// `None` if all approvals of the token were revoked.
+    pub account_id: Option<AccountId>,
+}
+
+/// Marketplace events have the marketplace as `contract_id` of their context.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NftListEvent {
+    pub nft_contract_id: AccountId,
+    pub token_id: NftTokenId,
+    pub seller_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub price: Balance,
+    /// Token the price is in, `near` for NEAR.
+    pub ft_id: AccountId,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NftUnlistEvent {
+    pub nft_contract_id: AccountId,
+    pub token_id: NftTokenId,
+    pub seller_id: AccountId,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NftSaleEvent {
+    pub nft_contract_id: AccountId,
+    pub token_id: NftTokenId,
+    pub seller_id: AccountId,
+    pub buyer_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub price: Balance,
+    pub ft_id: AccountId,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PotlockEventContext {
     pub transaction_id: TransactionId,
@@ -71,9 +120,46 @@ pub struct PotlockDonationEvent {
     #[serde(with = "dec_format")]
     pub protocol_fee: Balance,
     pub referrer_id: Option<AccountId>,
+    #[serde(default, with = "dec_format")]
     pub referrer_fee: Option<Balance>,
 }
 
+/// Before `ft_id` was added, all donations were in NEAR.
+#[derive(Debug, Deserialize)]
+pub struct PotlockDonationEventV1 {
+    pub donation_id: DonationId,
+    pub donor_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub total_amount: Balance,
+    pub message: Option<String>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub donated_at: DateTime<Utc>,
+    pub project_id: ProjectId,
+    #[serde(with = "dec_format")]
+    pub protocol_fee: Balance,
+    pub referrer_id: Option<AccountId>,
+    #[serde(default, with = "dec_format")]
+    pub referrer_fee: Option<Balance>,
+}
+
+impl From<PotlockDonationEventV1> for PotlockDonationEvent {
+    fn from(event: PotlockDonationEventV1) -> Self {
+        Self {
+            donation_id: event.donation_id,
+            donor_id: event.donor_id,
+            total_amount: event.total_amount,
+            // Version 1 of the Potlock contract only took donations in NEAR
+            ft_id: "near".to_string(),
+            message: event.message,
+            donated_at: event.donated_at,
+            project_id: event.project_id,
+            protocol_fee: event.protocol_fee,
+            referrer_id: event.referrer_id,
+            referrer_fee: event.referrer_fee,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PotlockPotProjectDonationEvent {
     pub donation_id: DonationId,
@@ -88,10 +174,12 @@ pub struct PotlockPotProjectDonationEvent {
     pub donated_at: DateTime<Utc>,
     pub project_id: ProjectId,
     pub referrer_id: Option<AccountId>,
+    #[serde(default, with = "dec_format")]
     pub referrer_fee: Option<Balance>,
     #[serde(with = "dec_format")]
     pub protocol_fee: Balance,
     pub chef_id: Option<AccountId>,
+    #[serde(default, with = "dec_format")]
     pub chef_fee: Option<Balance>,
 }
 
@@ -108,13 +196,61 @@ pub struct PotlockPotDonationEvent {
     #[serde(with = "chrono::serde::ts_milliseconds")]
     pub donated_at: DateTime<Utc>,
     pub referrer_id: Option<AccountId>,
+    #[serde(default, with = "dec_format")]
     pub referrer_fee: Option<Balance>,
     #[serde(with = "dec_format")]
     pub protocol_fee: Balance,
     pub chef_id: Option<AccountId>,
+    #[serde(default, with = "dec_format")]
     pub chef_fee: Option<Balance>,
 }
 
+/// A pot was created (`created`) or its config was changed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PotlockPotConfigEvent {
+    pub pot_id: AccountId,
+    pub created: bool,
+    pub owner_id: AccountId,
+    pub admins: Vec<AccountId>,
+    pub chef_id: Option<AccountId>,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub application_start: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub application_end: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub public_round_start: DateTime<Utc>,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub public_round_end: DateTime<Utc>,
+    /// The whole config, as returned by the pot.
+    pub config: serde_json::Value,
+}
+
+/// A project registered in the registry (no `pot_id`) or applied to a pot, or its
+/// application was reviewed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PotlockProjectStatusEvent {
+    pub project_id: ProjectId,
+    pub pot_id: Option<AccountId>,
+    /// `Pending`, `Approved`, `Rejected`, ...
+    pub status: String,
+    pub reviewer_id: Option<AccountId>,
+    pub review_notes: Option<String>,
+}
+
+/// A matching pool payout to a project.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PotlockPotPayoutEvent {
+    pub payout_id: String,
+    pub pot_id: AccountId,
+    pub project_id: ProjectId,
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    #[serde(with = "chrono::serde::ts_milliseconds")]
+    pub paid_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TradeContext {
     pub trader: AccountId,
@@ -140,13 +276,26 @@ pub struct RawPoolSwap {
 #[serde(transparent)]
 pub struct TradeRawPoolSwapEvent(pub RawPoolSwap);
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TradeBalanceChangeSwapEvent {
-    #[serde(deserialize_with = "deserialize_balance_changes")]
+    #[serde(
+        serialize_with = "serialize_balance_changes",
+        deserialize_with = "deserialize_balance_changes"
+    )]
     pub balance_changes: HashMap<AccountId, i128>,
     pub pool_swaps: Vec<RawPoolSwap>,
 }
 
+fn serialize_balance_changes<S>(
+    balance_changes: &HashMap<AccountId, i128>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    serializer.collect_map(balance_changes.iter().map(|(k, v)| (k, v.to_string())))
+}
+
 fn deserialize_balance_changes<'de, D>(
     deserializer: D,
 ) -> Result<HashMap<AccountId, i128>, D::Error>
@@ -161,6 +310,32 @@ where
         .map_err(|_: ParseIntError| serde::de::Error::custom("Failed to parse i128"))
 }
 
+/// Liquidity added to a pool by the trader of the context.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TradeLiquidityAddEvent {
+    pub pool: PoolId,
+    pub tokens: Vec<AccountId>,
+    /// Amount of each of `tokens` that was added.
+    #[serde(with = "dec_format_vec")]
+    pub amounts: Vec<Balance>,
+    /// Shares of the pool that were minted.
+    #[serde(with = "dec_format")]
+    pub shares: Balance,
+}
+
+/// Liquidity removed from a pool by the trader of the context.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TradeLiquidityRemoveEvent {
+    pub pool: PoolId,
+    pub tokens: Vec<AccountId>,
+    /// Amount of each of `tokens` that was withdrawn.
+    #[serde(with = "dec_format_vec")]
+    pub amounts: Vec<Balance>,
+    /// Shares of the pool that were burned.
+    #[serde(with = "dec_format")]
+    pub shares: Balance,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TradePoolChangeEvent {
     pub pool_id: String,
@@ -170,3 +345,941 @@ pub struct TradePoolChangeEvent {
     pub block_height: u64,
     pub pool: serde_json::Value,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FtEventContext {
+    pub transaction_id: TransactionId,
+    pub receipt_id: ReceiptId,
+    pub block_height: BlockHeight,
+    #[serde(with = "dec_format")]
+    pub block_timestamp_nanosec: u128,
+    pub contract_id: AccountId,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FtTransferEvent {
+    pub old_owner_id: AccountId,
+    pub new_owner_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FtMintEvent {
+    pub owner_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FtBurnEvent {
+    pub owner_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NearTransferEvent {
+    pub sender_id: AccountId,
+    pub receiver_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub amount: Balance,
+    pub transaction_id: TransactionId,
+    pub receipt_id: ReceiptId,
+    pub block_height: BlockHeight,
+    #[serde(with = "dec_format")]
+    pub block_timestamp_nanosec: u128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocialEventContext {
+    pub transaction_id: TransactionId,
+    pub receipt_id: ReceiptId,
+    pub block_height: BlockHeight,
+    #[serde(with = "dec_format")]
+    pub block_timestamp_nanosec: u128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocialPostEvent {
+    pub account_id: AccountId,
+    /// Key of the post in social.near, e.g. `alice.near/post/main`.
+    pub path: String,
+    pub text: Option<String>,
+    /// The value as it was written to social.near.
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocialCommentEvent {
+    pub account_id: AccountId,
+    pub path: String,
+    pub text: Option<String>,
+    /// Author of the commented post.
+    pub item_author_id: AccountId,
+    pub item_block_height: BlockHeight,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocialLikeEvent {
+    pub account_id: AccountId,
+    pub path: String,
+    /// Author of the liked post or comment.
+    pub item_author_id: AccountId,
+    pub item_block_height: BlockHeight,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SocialFollowEvent {
+    pub account_id: AccountId,
+    pub path: String,
+    pub target_id: AccountId,
+    /// `false` for unfollows.
+    pub follow: bool,
+    pub value: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogNep297Context {
+    pub transaction_id: TransactionId,
+    pub receipt_id: ReceiptId,
+    pub block_height: BlockHeight,
+    #[serde(with = "dec_format")]
+    pub block_timestamp_nanosec: u128,
+    pub contract_id: AccountId,
+}
+
+/// An `EVENT_JSON:` log of any NEP-297 standard.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LogNep297Event {
+    pub standard: String,
+    pub version: String,
+    pub event: String,
+    pub data: Option<serde_json::Value>,
+}
+
+/// Standards that have their own streams and tables, so their logs aren't stored as
+/// `log_nep297` events. A standard is promoted by adding a typed stream for it and then
+/// adding it here. Overridden by the comma-separated `LOG_NEP297_PROMOTED`.
+pub const PROMOTED_STANDARDS: &[&str] = &["nep141", "nep171"];
+
+fn promoted_standards() -> &'static [String] {
+    static PROMOTED: OnceLock<Vec<String>> = OnceLock::new();
+    PROMOTED.get_or_init(|| match std::env::var("LOG_NEP297_PROMOTED") {
+        Ok(standards) => parse_standards(&standards),
+        Err(_) => PROMOTED_STANDARDS.iter().map(|s| s.to_string()).collect(),
+    })
+}
+
+fn parse_standards(standards: &str) -> Vec<String> {
+    standards
+        .split(',')
+        .map(str::trim)
+        .filter(|standard| !standard.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+impl LogNep297Event {
+    pub fn is_promoted(&self) -> bool {
+        promoted_standards().contains(&self.standard)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountEventContext {
+    pub transaction_id: TransactionId,
+    pub receipt_id: ReceiptId,
+    pub block_height: BlockHeight,
+    #[serde(with = "dec_format")]
+    pub block_timestamp_nanosec: u128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountCreateEvent {
+    pub account_id: AccountId,
+    /// Predecessor of the receipt that created the account.
+    pub creator_id: AccountId,
+    #[serde(with = "dec_format")]
+    pub initial_balance: Balance,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountDeleteEvent {
+    pub account_id: AccountId,
+    /// Account that received the remaining balance.
+    pub beneficiary_id: AccountId,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContractDeployEvent {
+    pub account_id: AccountId,
+    /// Base58 SHA-256 hash of the deployed code.
+    pub code_hash: String,
+}
+
+/// Metadata of a transaction, for joining with the events it produced.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionEvent {
+    pub transaction_id: TransactionId,
+    pub signer_id: AccountId,
+    pub receiver_id: AccountId,
+    pub block_height: BlockHeight,
+    pub block_hash: String,
+    #[serde(with = "dec_format")]
+    pub block_timestamp_nanosec: u128,
+    /// Gas burnt by the transaction and all of its receipts.
+    pub gas_burnt: u64,
+    #[serde(with = "dec_format")]
+    pub tokens_burnt: Balance,
+    /// `SuccessValue`, `SuccessReceiptId` or `Failure`.
+    pub status: String,
+}
+
+/// A price point of a token from the price feed. NEAR is priced as `wrap.near`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenPriceEvent {
+    pub token_id: AccountId,
+    /// USD per whole token, as a decimal string.
+    #[serde(deserialize_with = "deserialize_decimal")]
+    pub price_usd: String,
+    /// Decimals of the token, to convert raw amounts to whole tokens.
+    pub decimals: u32,
+    pub block_height: BlockHeight,
+    #[serde(with = "dec_format")]
+    pub block_timestamp_nanosec: u128,
+}
+
+fn deserialize_decimal<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let decimal = String::deserialize(deserializer)?;
+    let (integer, fraction) = decimal.split_once('.').unwrap_or((&decimal, "0"));
+    if integer.is_empty()
+        || fraction.is_empty()
+        || !integer
+            .bytes()
+            .chain(fraction.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return Err(serde::de::Error::custom("Failed to parse decimal"));
+    }
+    Ok(decimal)
+}
+
+/// Events whose schema can change. Entries may have a `schema_version` field; entries of
+/// older versions are decoded with `upgrade`, and entries without a version or of newer
+/// versions are decoded as the current version, ignoring fields it doesn't know.
+pub trait Versioned: DeserializeOwned {
+    const VERSION: u32 = 1;
+
+    /// Decodes an event of an older schema version.
+    fn upgrade(version: u32, json: &str) -> serde_json::Result<Self> {
+        let _ = version;
+        serde_json::from_str(json)
+    }
+}
+
+impl Versioned for NftMintEvent {}
+impl Versioned for NftTransferEvent {}
+impl Versioned for NftBurnEvent {}
+impl Versioned for NftApproveEvent {}
+impl Versioned for NftRevokeEvent {}
+impl Versioned for NftListEvent {}
+impl Versioned for NftUnlistEvent {}
+impl Versioned for NftSaleEvent {}
+
+impl Versioned for PotlockDonationEvent {
+    const VERSION: u32 = 2;
+
+    fn upgrade(version: u32, json: &str) -> serde_json::Result<Self> {
+        match version {
+            1 => serde_json::from_str::<PotlockDonationEventV1>(json).map(Into::into),
+            _ => serde_json::from_str(json),
+        }
+    }
+}
+
+impl Versioned for PotlockPotProjectDonationEvent {}
+impl Versioned for PotlockPotDonationEvent {}
+impl Versioned for PotlockPotConfigEvent {}
+impl Versioned for PotlockProjectStatusEvent {}
+impl Versioned for PotlockPotPayoutEvent {}
+impl Versioned for TradeRawPoolSwapEvent {}
+impl Versioned for TradeBalanceChangeSwapEvent {}
+impl Versioned for TradePoolChangeEvent {}
+impl Versioned for TradeLiquidityAddEvent {}
+impl Versioned for TradeLiquidityRemoveEvent {}
+impl Versioned for FtTransferEvent {}
+impl Versioned for FtMintEvent {}
+impl Versioned for FtBurnEvent {}
+impl Versioned for NearTransferEvent {}
+impl Versioned for SocialPostEvent {}
+impl Versioned for SocialCommentEvent {}
+impl Versioned for SocialLikeEvent {}
+impl Versioned for SocialFollowEvent {}
+impl Versioned for LogNep297Event {}
+impl Versioned for AccountCreateEvent {}
+impl Versioned for AccountDeleteEvent {}
+impl Versioned for ContractDeployEvent {}
+impl Versioned for TransactionEvent {}
+impl Versioned for TokenPriceEvent {}
+
+/// Context of the entries of a stream, decoded from their `context` field.
+pub trait EventContext: Sized + Serialize + Send {
+    fn decode(values: &HashMap<String, Value>) -> anyhow::Result<Self>;
+
+    /// The entry field of the context and the fields it reads, for `extra_fields`.
+    fn known_fields() -> Option<(&'static str, &'static [&'static str])>;
+}
+
+macro_rules! impl_event_context {
+    ($($context:ty),* $(,)?) => {$(
+        impl EventContext for $context {
+            fn decode(values: &HashMap<String, Value>) -> anyhow::Result<Self> {
+                decode_field(values, "context")
+            }
+
+            fn known_fields() -> Option<(&'static str, &'static [&'static str])> {
+                Some(("context", fields_of::<Self>()))
+            }
+        }
+    )*};
+}
+
+impl_event_context!(
+    NftEventContext,
+    PotlockEventContext,
+    TradeContext,
+    FtEventContext,
+    SocialEventContext,
+    LogNep297Context,
+    AccountEventContext,
+);
+
+/// Context of streams whose events have their block and receipt themselves. Serialized
+/// as `null`.
+#[derive(Debug, Serialize)]
+pub struct NoContext;
+
+impl EventContext for NoContext {
+    fn decode(_values: &HashMap<String, Value>) -> anyhow::Result<Self> {
+        Ok(NoContext)
+    }
+
+    fn known_fields() -> Option<(&'static str, &'static [&'static str])> {
+        None
+    }
+}
+
+/// An event type, along with where it is in the entries of its stream.
+pub trait StreamEvent: Versioned + Serialize + Send {
+    /// Entry field that holds the event.
+    const FIELD: &'static str;
+    type Context: EventContext;
+
+    /// Events that every sink skips, because they're stored by another stream.
+    fn is_skipped(&self) -> bool {
+        false
+    }
+}
+
+macro_rules! impl_stream_event {
+    ($($event:ty => $context:ty, $field:literal;)*) => {$(
+        impl StreamEvent for $event {
+            const FIELD: &'static str = $field;
+            type Context = $context;
+        }
+    )*};
+}
+
+impl_stream_event! {
+    NftMintEvent => NftEventContext, "mint";
+    NftTransferEvent => NftEventContext, "transfer";
+    NftBurnEvent => NftEventContext, "burn";
+    NftApproveEvent => NftEventContext, "approve";
+    NftRevokeEvent => NftEventContext, "revoke";
+    NftListEvent => NftEventContext, "list";
+    NftUnlistEvent => NftEventContext, "unlist";
+    NftSaleEvent => NftEventContext, "sale";
+    PotlockDonationEvent => PotlockEventContext, "donation";
+    PotlockPotProjectDonationEvent => PotlockEventContext, "pot_project_donation";
+    PotlockPotDonationEvent => PotlockEventContext, "pot_donation";
+    PotlockPotConfigEvent => PotlockEventContext, "pot_config";
+    PotlockProjectStatusEvent => PotlockEventContext, "project_status";
+    PotlockPotPayoutEvent => PotlockEventContext, "payout";
+    TradeRawPoolSwapEvent => TradeContext, "swap";
+    TradeBalanceChangeSwapEvent => TradeContext, "balance_change";
+    TradePoolChangeEvent => NoContext, "pool_change";
+    TradeLiquidityAddEvent => TradeContext, "liquidity_add";
+    TradeLiquidityRemoveEvent => TradeContext, "liquidity_remove";
+    FtTransferEvent => FtEventContext, "transfer";
+    FtMintEvent => FtEventContext, "mint";
+    FtBurnEvent => FtEventContext, "burn";
+    NearTransferEvent => NoContext, "transfer";
+    SocialPostEvent => SocialEventContext, "post";
+    SocialCommentEvent => SocialEventContext, "comment";
+    SocialLikeEvent => SocialEventContext, "like";
+    SocialFollowEvent => SocialEventContext, "follow";
+    AccountCreateEvent => AccountEventContext, "create";
+    AccountDeleteEvent => AccountEventContext, "delete";
+    ContractDeployEvent => AccountEventContext, "deploy";
+    TransactionEvent => NoContext, "transaction";
+    TokenPriceEvent => NoContext, "price";
+}
+
+impl StreamEvent for LogNep297Event {
+    const FIELD: &'static str = "log";
+    type Context = LogNep297Context;
+
+    fn is_skipped(&self) -> bool {
+        self.is_promoted()
+    }
+}
+
+/// An entry decoded into its context and event.
+pub struct Decoded<E: StreamEvent> {
+    pub context: E::Context,
+    pub event: E,
+    /// Fields of the entry that neither the context nor the event reads, see `extra_fields`.
+    pub extra: Option<serde_json::Value>,
+}
+
+/// Decodes the context and the event of an entry of `E`'s stream.
+pub fn decode_entry<E: StreamEvent>(values: &HashMap<String, Value>) -> anyhow::Result<Decoded<E>> {
+    let context = E::Context::decode(values)?;
+    let event = decode_event(values, E::FIELD)?;
+    let known = E::Context::known_fields()
+        .into_iter()
+        .chain([(E::FIELD, fields_of::<E>())])
+        .collect::<Vec<_>>();
+    Ok(Decoded {
+        context,
+        event,
+        extra: extra_fields(values, &known),
+    })
+}
+
+/// Time of a block from its `block_timestamp_nanosec`.
+pub fn timestamp(block_timestamp_nanosec: u128) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(
+        (block_timestamp_nanosec / 1_000_000_000) as i64,
+        (block_timestamp_nanosec % 1_000_000_000) as u32,
+    )
+}
+
+/// The optional `schema_version` field of an entry.
+pub fn schema_version(values: &HashMap<String, Value>) -> Option<u32> {
+    values
+        .get("schema_version")
+        .and_then(|version| String::from_redis_value(version).ok())
+        .and_then(|version| version.parse().ok())
+}
+
+/// Decodes the JSON in `field` of an entry.
+pub fn decode_field<T: DeserializeOwned>(
+    values: &HashMap<String, Value>,
+    field: &str,
+) -> anyhow::Result<T> {
+    let json = field_json(values, field)?;
+    serde_json::from_str(&json).map_err(|err| {
+        log::warn!("Failed to decode {field}: {err}");
+        err.into()
+    })
+}
+
+/// Decodes the event in `field` of an entry, upgrading it from the entry's schema version.
+pub fn decode_event<T: Versioned>(
+    values: &HashMap<String, Value>,
+    field: &str,
+) -> anyhow::Result<T> {
+    let json = field_json(values, field)?;
+    let version = schema_version(values);
+    match version {
+        Some(version) if version < T::VERSION => T::upgrade(version, &json),
+        _ => serde_json::from_str(&json),
+    }
+    .map_err(|err| {
+        log::warn!("Failed to decode {field} of schema version {version:?}: {err}");
+        err.into()
+    })
+}
+
+fn field_json(values: &HashMap<String, Value>, field: &str) -> anyhow::Result<String> {
+    let Some(value) = values.get(field) else {
+        log::warn!("Entry has no {field} field");
+        anyhow::bail!("No {field} field");
+    };
+    Ok(String::from_redis_value(value)?)
+}
+
+/// Fields of the entry's JSON objects that aren't in `known`, as
+/// `{"<entry field>": {"<unknown field>": ...}}`, or `None` if there are none.
+pub fn extra_fields(
+    values: &HashMap<String, Value>,
+    known: &[(&str, &[&str])],
+) -> Option<serde_json::Value> {
+    let mut extra = serde_json::Map::new();
+    for (field, known_fields) in known {
+        let Some(serde_json::Value::Object(mut object)) = values
+            .get(*field)
+            .and_then(|value| String::from_redis_value(value).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+        else {
+            continue;
+        };
+        object.retain(|key, _| !known_fields.contains(&key.as_str()));
+        if !object.is_empty() {
+            extra.insert(field.to_string(), serde_json::Value::Object(object));
+        }
+    }
+    (!extra.is_empty()).then_some(serde_json::Value::Object(extra))
+}
+
+/// Names of the fields that `T` reads from a JSON object.
+pub fn fields_of<T: DeserializeOwned>() -> &'static [&'static str] {
+    use serde::de::{Error, Visitor};
+
+    struct FieldsDeserializer<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de> serde::Deserializer<'de> for FieldsDeserializer<'_> {
+        type Error = serde::de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+            Err(Error::custom("not a struct"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(Error::custom("only reading fields"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes
+            byte_buf option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+            identifier ignored_any
+        }
+    }
+
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsDeserializer(&mut fields));
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fields: &[(&str, serde_json::Value)]) -> HashMap<String, Value> {
+        fields
+            .iter()
+            .map(|(field, value)| {
+                (
+                    field.to_string(),
+                    Value::Data(value.to_string().into_bytes()),
+                )
+            })
+            .collect()
+    }
+
+    /// Fields that aren't listed here end up in `extra`, so a field that serde reads
+    /// differently (e.g. after a rename) must be updated here too.
+    #[test]
+    fn reads_fields_of_every_event() {
+        assert_eq!(
+            fields_of::<NftEventContext>(),
+            [
+                "transaction_id",
+                "receipt_id",
+                "block_height",
+                "block_timestamp_nanosec",
+                "contract_id"
+            ]
+        );
+        assert_eq!(
+            fields_of::<NftMintEvent>(),
+            ["owner_id", "token_ids", "memo"]
+        );
+        assert_eq!(
+            fields_of::<NftTransferEvent>(),
+            [
+                "old_owner_id",
+                "new_owner_id",
+                "token_ids",
+                "memo",
+                "token_prices_near"
+            ]
+        );
+        assert_eq!(
+            fields_of::<NftBurnEvent>(),
+            ["owner_id", "token_ids", "memo"]
+        );
+        assert_eq!(
+            fields_of::<NftApproveEvent>(),
+            ["owner_id", "token_id", "account_id", "approval_id", "msg"]
+        );
+        assert_eq!(
+            fields_of::<NftRevokeEvent>(),
+            ["owner_id", "token_id", "account_id"]
+        );
+        assert_eq!(
+            fields_of::<NftListEvent>(),
+            ["nft_contract_id", "token_id", "seller_id", "price", "ft_id"]
+        );
+        assert_eq!(
+            fields_of::<NftUnlistEvent>(),
+            ["nft_contract_id", "token_id", "seller_id"]
+        );
+        assert_eq!(
+            fields_of::<NftSaleEvent>(),
+            [
+                "nft_contract_id",
+                "token_id",
+                "seller_id",
+                "buyer_id",
+                "price",
+                "ft_id"
+            ]
+        );
+        assert_eq!(
+            fields_of::<PotlockEventContext>(),
+            [
+                "transaction_id",
+                "receipt_id",
+                "block_height",
+                "block_timestamp_nanosec"
+            ]
+        );
+        assert_eq!(
+            fields_of::<PotlockDonationEvent>(),
+            [
+                "donation_id",
+                "donor_id",
+                "total_amount",
+                "ft_id",
+                "message",
+                "donated_at",
+                "project_id",
+                "protocol_fee",
+                "referrer_id",
+                "referrer_fee"
+            ]
+        );
+        assert_eq!(
+            fields_of::<PotlockPotProjectDonationEvent>(),
+            [
+                "donation_id",
+                "pot_id",
+                "donor_id",
+                "total_amount",
+                "net_amount",
+                "message",
+                "donated_at",
+                "project_id",
+                "referrer_id",
+                "referrer_fee",
+                "protocol_fee",
+                "chef_id",
+                "chef_fee"
+            ]
+        );
+        assert_eq!(
+            fields_of::<PotlockPotDonationEvent>(),
+            [
+                "donation_id",
+                "pot_id",
+                "donor_id",
+                "total_amount",
+                "net_amount",
+                "message",
+                "donated_at",
+                "referrer_id",
+                "referrer_fee",
+                "protocol_fee",
+                "chef_id",
+                "chef_fee"
+            ]
+        );
+        assert_eq!(
+            fields_of::<PotlockPotConfigEvent>(),
+            [
+                "pot_id",
+                "created",
+                "owner_id",
+                "admins",
+                "chef_id",
+                "name",
+                "description",
+                "application_start",
+                "application_end",
+                "public_round_start",
+                "public_round_end",
+                "config"
+            ]
+        );
+        assert_eq!(
+            fields_of::<PotlockProjectStatusEvent>(),
+            [
+                "project_id",
+                "pot_id",
+                "status",
+                "reviewer_id",
+                "review_notes"
+            ]
+        );
+        assert_eq!(
+            fields_of::<PotlockPotPayoutEvent>(),
+            ["payout_id", "pot_id", "project_id", "amount", "paid_at"]
+        );
+        assert_eq!(
+            fields_of::<TradeContext>(),
+            [
+                "trader",
+                "block_height",
+                "block_timestamp_nanosec",
+                "transaction_id",
+                "receipt_id"
+            ]
+        );
+        assert_eq!(
+            fields_of::<TradeBalanceChangeSwapEvent>(),
+            ["balance_changes", "pool_swaps"]
+        );
+        assert_eq!(
+            fields_of::<TradeLiquidityAddEvent>(),
+            ["pool", "tokens", "amounts", "shares"]
+        );
+        assert_eq!(
+            fields_of::<TradeLiquidityRemoveEvent>(),
+            ["pool", "tokens", "amounts", "shares"]
+        );
+        assert_eq!(
+            fields_of::<TradePoolChangeEvent>(),
+            [
+                "pool_id",
+                "receipt_id",
+                "block_timestamp_nanosec",
+                "block_height",
+                "pool"
+            ]
+        );
+        assert_eq!(
+            fields_of::<FtEventContext>(),
+            [
+                "transaction_id",
+                "receipt_id",
+                "block_height",
+                "block_timestamp_nanosec",
+                "contract_id"
+            ]
+        );
+        assert_eq!(
+            fields_of::<FtTransferEvent>(),
+            ["old_owner_id", "new_owner_id", "amount", "memo"]
+        );
+        assert_eq!(fields_of::<FtMintEvent>(), ["owner_id", "amount", "memo"]);
+        assert_eq!(fields_of::<FtBurnEvent>(), ["owner_id", "amount", "memo"]);
+        assert_eq!(
+            fields_of::<NearTransferEvent>(),
+            [
+                "sender_id",
+                "receiver_id",
+                "amount",
+                "transaction_id",
+                "receipt_id",
+                "block_height",
+                "block_timestamp_nanosec"
+            ]
+        );
+        assert_eq!(
+            fields_of::<SocialEventContext>(),
+            [
+                "transaction_id",
+                "receipt_id",
+                "block_height",
+                "block_timestamp_nanosec"
+            ]
+        );
+        assert_eq!(
+            fields_of::<SocialPostEvent>(),
+            ["account_id", "path", "text", "value"]
+        );
+        assert_eq!(
+            fields_of::<SocialCommentEvent>(),
+            [
+                "account_id",
+                "path",
+                "text",
+                "item_author_id",
+                "item_block_height",
+                "value"
+            ]
+        );
+        assert_eq!(
+            fields_of::<SocialLikeEvent>(),
+            [
+                "account_id",
+                "path",
+                "item_author_id",
+                "item_block_height",
+                "value"
+            ]
+        );
+        assert_eq!(
+            fields_of::<SocialFollowEvent>(),
+            ["account_id", "path", "target_id", "follow", "value"]
+        );
+        assert_eq!(
+            fields_of::<LogNep297Context>(),
+            [
+                "transaction_id",
+                "receipt_id",
+                "block_height",
+                "block_timestamp_nanosec",
+                "contract_id"
+            ]
+        );
+        assert_eq!(
+            fields_of::<LogNep297Event>(),
+            ["standard", "version", "event", "data"]
+        );
+        assert_eq!(
+            fields_of::<AccountEventContext>(),
+            [
+                "transaction_id",
+                "receipt_id",
+                "block_height",
+                "block_timestamp_nanosec"
+            ]
+        );
+        assert_eq!(
+            fields_of::<AccountCreateEvent>(),
+            ["account_id", "creator_id", "initial_balance"]
+        );
+        assert_eq!(
+            fields_of::<AccountDeleteEvent>(),
+            ["account_id", "beneficiary_id"]
+        );
+        assert_eq!(
+            fields_of::<ContractDeployEvent>(),
+            ["account_id", "code_hash"]
+        );
+        assert_eq!(
+            fields_of::<TransactionEvent>(),
+            [
+                "transaction_id",
+                "signer_id",
+                "receiver_id",
+                "block_height",
+                "block_hash",
+                "block_timestamp_nanosec",
+                "gas_burnt",
+                "tokens_burnt",
+                "status"
+            ]
+        );
+        assert_eq!(
+            fields_of::<TokenPriceEvent>(),
+            [
+                "token_id",
+                "price_usd",
+                "decimals",
+                "block_height",
+                "block_timestamp_nanosec"
+            ]
+        );
+        assert_eq!(
+            fields_of::<TradeRawPoolSwapEvent>(),
+            ["pool", "token_in", "token_out", "amount_in", "amount_out"]
+        );
+    }
+
+    #[test]
+    fn upgrades_old_schema_versions() {
+        let donation = serde_json::json!({
+            "donation_id": 1,
+            "donor_id": "alice.near",
+            "total_amount": "100",
+            "message": null,
+            "donated_at": 1_700_000_000_000_i64,
+            "project_id": "project.near",
+            "protocol_fee": "2",
+            "referrer_id": null,
+        });
+        let v1 = entry(&[("donation", donation.clone()), ("schema_version", 1.into())]);
+        let event = decode_event::<PotlockDonationEvent>(&v1, "donation").unwrap();
+        assert_eq!(event.ft_id, "near");
+        assert_eq!(event.total_amount, 100);
+
+        // Version 2 requires ft_id
+        let v2 = entry(&[("donation", donation), ("schema_version", 2.into())]);
+        assert!(decode_event::<PotlockDonationEvent>(&v2, "donation").is_err());
+    }
+
+    #[test]
+    fn keeps_unknown_fields() {
+        let values = entry(&[
+            (
+                "context",
+                serde_json::json!({ "transaction_id": "tx", "shard_id": 3 }),
+            ),
+            (
+                "burn",
+                serde_json::json!({ "owner_id": "alice.near", "reason": "spam" }),
+            ),
+            ("schema_version", 1.into()),
+        ]);
+        let known: &[(&str, &[&str])] = &[
+            ("context", &["transaction_id"]),
+            ("burn", fields_of::<NftBurnEvent>()),
+        ];
+        assert_eq!(
+            extra_fields(&values, known),
+            Some(serde_json::json!({
+                "context": { "shard_id": 3 },
+                "burn": { "reason": "spam" },
+            }))
+        );
+        assert_eq!(
+            extra_fields(&values, &[("context", &["transaction_id", "shard_id"])]),
+            None
+        );
+    }
+
+    #[test]
+    fn deserializes_decimals() {
+        let price = |price_usd: &str| {
+            serde_json::from_value::<TokenPriceEvent>(serde_json::json!({
+                "token_id": "wrap.near",
+                "price_usd": price_usd,
+                "decimals": 24,
+                "block_height": 1,
+                "block_timestamp_nanosec": "1",
+            }))
+            .map(|event| event.price_usd)
+        };
+        assert_eq!(price("1.25").unwrap(), "1.25");
+        assert_eq!(price("3").unwrap(), "3");
+        for invalid in ["", ".5", "1.", "1.2.3", "-1", "1e5", " 1"] {
+            assert!(price(invalid).is_err(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn parses_promoted_standards() {
+        assert_eq!(parse_standards("nep141, nep171,"), ["nep141", "nep171"]);
+        // Nothing is promoted, every log is stored
+        assert!(parse_standards("").is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+//! Per-stream filters that drop events before they reach the sink. Rules are read from
+//! the JSON file in `FILTERS_FILE`, keyed by stream:
+//!
+//! ```json
+//! {
+//!     "nft_transfer": [{ "field": "contract_id", "deny": ["spam.near"] }],
+//!     "trade_pool": [{ "field": "amount_in", "min": "1000" }, { "field": "pool", "allow_regex": "^REF-" }],
+//!     "log_nep297": [{ "field": "standard", "allow": ["nep245", "dip4"] }]
+//! }
+//! ```
+//!
+//! A rule looks up `field` in the decoded event, then in its context, after upgrading the
+//! event to the current schema version, and doesn't apply if it's not there. Nested fields
+//! are separated by dots (`"pool.token_ids"`). Amounts are compared as decimal strings, so
+//! `min` works for amounts above `u64::MAX`. For arrays, every element has to pass. An
+//! event is kept only if it passes all rules of its stream.
+
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use events_api_redis_to_db::{
+    events::{decode_entry, Decoded, StreamEvent},
+    redis_reader::EventHandler,
+};
+use redis::Value;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(transparent)]
+pub struct Filters(HashMap<String, Vec<FieldRule>>);
+
+impl Filters {
+    pub fn from_env() -> anyhow::Result<Self> {
+        let Ok(path) = std::env::var("FILTERS_FILE") else {
+            return Ok(Self::default());
+        };
+        let filters: Self = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        log::info!("Loaded filters for {} streams from {path}", filters.0.len());
+        Ok(filters)
+    }
+
+    fn rules(&self, stream_key: &str) -> &[FieldRule] {
+        self.0
+            .get(stream_key)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Whether an entry of `stream_key`, whose events are `E`, passes the rules of that
+    /// stream.
+    pub fn passes<E: StreamEvent>(
+        &self,
+        stream_key: &str,
+        values: &HashMap<String, Value>,
+    ) -> bool {
+        passes::<E>(self.rules(stream_key), values)
+    }
+
+    /// Wraps `handler` so that events of `stream_key` not passing the rules are skipped.
+    pub fn wrap<E, H>(&self, stream_key: &str, handler: H) -> Filtered<'_, E, H> {
+        Filtered {
+            stream_key: stream_key.to_string(),
+            rules: self.rules(stream_key),
+            handler,
+            filtered: AtomicU64::new(0),
+            event: PhantomData,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FieldRule {
+    field: String,
+    allow: Option<Vec<String>>,
+    #[serde(default)]
+    deny: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    allow_regex: Option<Regex>,
+    #[serde(default, deserialize_with = "deserialize_regex")]
+    deny_regex: Option<Regex>,
+    #[serde(default, deserialize_with = "deserialize_amount")]
+    min: Option<u128>,
+}
+
+impl FieldRule {
+    /// Value of the field in the event, or else in its context.
+    fn lookup<'a>(&self, entry: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        ["event", "context"].into_iter().find_map(|part| {
+            self.field
+                .split('.')
+                .try_fold(&entry[part], |value, key| value.get(key))
+        })
+    }
+
+    fn passes(&self, value: &serde_json::Value) -> bool {
+        match value {
+            serde_json::Value::Array(values) => values.iter().all(|value| self.passes(value)),
+            serde_json::Value::String(value) => self.passes_str(value),
+            serde_json::Value::Number(value) => self.passes_str(&value.to_string()),
+            _ => true,
+        }
+    }
+
+    fn passes_str(&self, value: &str) -> bool {
+        if let Some(allow) = &self.allow {
+            if !allow.iter().any(|allowed| allowed == value) {
+                return false;
+            }
+        }
+        if self.deny.iter().any(|denied| denied == value) {
+            return false;
+        }
+        if let Some(allow_regex) = &self.allow_regex {
+            if !allow_regex.is_match(value) {
+                return false;
+            }
+        }
+        if let Some(deny_regex) = &self.deny_regex {
+            if deny_regex.is_match(value) {
+                return false;
+            }
+        }
+        if let Some(min) = self.min {
+            if !value.parse::<u128>().is_ok_and(|amount| amount >= min) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|regex| Regex::new(&regex))
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+/// Amounts can be written as strings, or as numbers if they fit into a u64.
+fn deserialize_amount<'de, D>(deserializer: D) -> Result<Option<u128>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Amount {
+        Number(u64),
+        String(String),
+    }
+
+    Option::<Amount>::deserialize(deserializer)?
+        .map(|amount| match amount {
+            Amount::Number(amount) => Ok(amount.into()),
+            Amount::String(amount) => amount.parse(),
+        })
+        .transpose()
+        .map_err(serde::de::Error::custom)
+}
+
+pub struct Filtered<'a, E, H> {
+    stream_key: String,
+    rules: &'a [FieldRule],
+    handler: H,
+    filtered: AtomicU64,
+    event: PhantomData<fn() -> E>,
+}
+
+/// Entries that fail to decode pass, so that the handler reports them.
+fn passes<E: StreamEvent>(rules: &[FieldRule], values: &HashMap<String, Value>) -> bool {
+    if rules.is_empty() {
+        return true;
+    }
+    let Ok(Decoded { context, event, .. }) = decode_entry::<E>(values) else {
+        return true;
+    };
+    let (Ok(context), Ok(event)) = (serde_json::to_value(context), serde_json::to_value(event))
+    else {
+        return true;
+    };
+    let entry = serde_json::json!({ "context": context, "event": event });
+    rules
+        .iter()
+        .all(|rule| rule.lookup(&entry).map_or(true, |value| rule.passes(value)))
+}
+
+#[async_trait::async_trait]
+impl<Db, E, H> EventHandler<Db> for Filtered<'_, E, H>
+where
+    Db: Sync,
+    E: StreamEvent,
+    H: EventHandler<Db>,
+{
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        db: &Db,
+    ) -> anyhow::Result<()> {
+        if passes::<E>(self.rules, &values) {
+            return self.handler.handle(id, values, db).await;
+        }
+        let filtered = self.filtered.fetch_add(1, Ordering::Relaxed) + 1;
+        log::debug!("Filtered out {} event {id}", self.stream_key);
+        if filtered % 1000 == 0 {
+            log::info!("Filtered out {filtered} {} events so far", self.stream_key);
+        }
+        Ok(())
+    }
+
+    async fn revert(&self, id: &str, block_height: u64, db: &Db) -> anyhow::Result<()> {
+        self.handler.revert(id, block_height, db).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use events_api_redis_to_db::events::{
+        LogNep297Event, NftTransferEvent, PotlockDonationEvent, TradeRawPoolSwapEvent,
+    };
+
+    use super::*;
+
+    fn parse_filters(json: serde_json::Value) -> Filters {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn entry(fields: &[(&str, serde_json::Value)]) -> HashMap<String, Value> {
+        fields
+            .iter()
+            .map(|(field, value)| {
+                let value = match value {
+                    serde_json::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                (field.to_string(), Value::Data(value.into_bytes()))
+            })
+            .collect()
+    }
+
+    fn context() -> serde_json::Value {
+        serde_json::json!({
+            "transaction_id": "tx",
+            "receipt_id": "receipt",
+            "block_height": 100,
+            "block_timestamp_nanosec": "1700000000000000000",
+            "contract_id": "nft.near",
+            "trader": "alice.near",
+        })
+    }
+
+    fn nft_transfer(token_ids: &[&str]) -> HashMap<String, Value> {
+        entry(&[
+            ("context", context()),
+            (
+                "transfer",
+                serde_json::json!({
+                    "old_owner_id": "alice.near",
+                    "new_owner_id": "bob.near",
+                    "token_ids": token_ids,
+                    "memo": null,
+                    "token_prices_near": token_ids.iter().map(|_| "1").collect::<Vec<_>>(),
+                }),
+            ),
+        ])
+    }
+
+    fn trade_pool(amount_in: &str) -> HashMap<String, Value> {
+        entry(&[
+            ("context", context()),
+            (
+                "swap",
+                serde_json::json!({
+                    "pool": "REF-1",
+                    "token_in": "wrap.near",
+                    "token_out": "usdt.near",
+                    "amount_in": amount_in,
+                    "amount_out": "1",
+                }),
+            ),
+        ])
+    }
+
+    #[test]
+    fn matches_event_and_context_fields() {
+        let filters = parse_filters(serde_json::json!({
+            "nft_transfer": [
+                { "field": "contract_id", "deny": ["spam.near"] },
+                { "field": "new_owner_id", "allow_regex": "\\.near$" },
+            ],
+        }));
+        assert!(filters.passes::<NftTransferEvent>("nft_transfer", &nft_transfer(&["1"])));
+
+        let mut spam = nft_transfer(&["1"]);
+        let mut context = context();
+        context["contract_id"] = "spam.near".into();
+        spam.insert(
+            "context".to_string(),
+            Value::Data(context.to_string().into_bytes()),
+        );
+        assert!(!filters.passes::<NftTransferEvent>("nft_transfer", &spam));
+        // Other streams have no rules
+        assert!(filters.passes::<NftTransferEvent>("nft_mint", &spam));
+    }
+
+    #[test]
+    fn every_array_element_has_to_pass() {
+        let filters = parse_filters(serde_json::json!({
+            "nft_transfer": [{ "field": "token_ids", "deny_regex": "^spam" }],
+        }));
+        assert!(filters.passes::<NftTransferEvent>("nft_transfer", &nft_transfer(&["1", "2"])));
+        assert!(
+            !filters.passes::<NftTransferEvent>("nft_transfer", &nft_transfer(&["1", "spam-2"]))
+        );
+    }
+
+    #[test]
+    fn compares_amounts_above_u64() {
+        let filters = parse_filters(serde_json::json!({
+            "trade_pool": [{ "field": "amount_in", "min": "100000000000000000000000" }],
+        }));
+        assert!(filters.passes::<TradeRawPoolSwapEvent>(
+            "trade_pool",
+            &trade_pool("100000000000000000000001")
+        ));
+        assert!(!filters
+            .passes::<TradeRawPoolSwapEvent>("trade_pool", &trade_pool("99999999999999999999999")));
+
+        // Amounts that fit into a u64 can be numbers
+        let filters = parse_filters(serde_json::json!({
+            "trade_pool": [{ "field": "amount_in", "min": 1000 }],
+        }));
+        assert!(filters.passes::<TradeRawPoolSwapEvent>("trade_pool", &trade_pool("1000")));
+        assert!(!filters.passes::<TradeRawPoolSwapEvent>("trade_pool", &trade_pool("999")));
+    }
+
+    #[test]
+    fn matches_nested_fields() {
+        let filters = parse_filters(serde_json::json!({
+            "log_nep297": [{ "field": "data.owner_id", "allow": ["alice.near"] }],
+        }));
+        let log = |owner_id: &str| {
+            entry(&[
+                ("context", context()),
+                (
+                    "log",
+                    serde_json::json!({
+                        "standard": "nep245",
+                        "version": "1.0.0",
+                        "event": "mt_mint",
+                        "data": { "owner_id": owner_id },
+                    }),
+                ),
+            ])
+        };
+        assert!(filters.passes::<LogNep297Event>("log_nep297", &log("alice.near")));
+        assert!(!filters.passes::<LogNep297Event>("log_nep297", &log("bob.near")));
+    }
+
+    #[test]
+    fn matches_upgraded_events() {
+        let filters = parse_filters(serde_json::json!({
+            "potlock_donation": [{ "field": "ft_id", "deny": ["near"] }],
+        }));
+        // Version 1 donations have no ft_id, they were all in NEAR
+        let donation = entry(&[
+            ("schema_version", "1".into()),
+            ("context", context()),
+            (
+                "donation",
+                serde_json::json!({
+                    "donation_id": 1,
+                    "donor_id": "alice.near",
+                    "total_amount": "1000",
+                    "message": null,
+                    "donated_at": 1700000000000u64,
+                    "project_id": "project.near",
+                    "protocol_fee": "10",
+                    "referrer_id": null,
+                }),
+            ),
+        ]);
+        assert!(!filters.passes::<PotlockDonationEvent>("potlock_donation", &donation));
+    }
+
+    #[test]
+    fn passes_entries_that_fail_to_decode() {
+        let filters = parse_filters(serde_json::json!({
+            "nft_transfer": [{ "field": "contract_id", "allow": ["nft.near"] }],
+        }));
+        let entry = entry(&[("context", context())]);
+        assert!(filters.passes::<NftTransferEvent>("nft_transfer", &entry));
+    }
+}
@@ -0,0 +1,278 @@
+//! Record and replay of raw stream entries. The recorder appends every entry as
+//! `{"stream": ..., "id": ..., "fields": {...}}` to `{directory}/events-<time>-<n>.jsonl`,
+//! starting a new file when the current one exceeds `JSONL_MAX_FILE_BYTES`. `n` counts the
+//! files opened by the process, so that files opened in the same millisecond still sort
+//! in the order they were written. Replaying
+//! feeds the files, in name order, through the same handlers that process live streams.
+
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use events_api_redis_to_db::redis_reader::{parse_revert, Backpressure, EventHandler};
+use redis::{FromRedisValue, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::STREAM_KEYS;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct JsonlEntry {
+    stream: String,
+    id: String,
+    fields: HashMap<String, String>,
+}
+
+#[derive(Clone)]
+pub struct JsonlSink {
+    directory: PathBuf,
+    max_file_bytes: u64,
+    file: Arc<Mutex<Option<(File, u64)>>>,
+    files_opened: Arc<AtomicU64>,
+}
+
+impl JsonlSink {
+    pub fn open(directory: &str) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(directory)?;
+        Ok(Self {
+            directory: PathBuf::from(directory),
+            max_file_bytes: std::env::var("JSONL_MAX_FILE_BYTES")
+                .map(|size| size.parse())
+                .unwrap_or(Ok(100 * 1024 * 1024))?,
+            file: Arc::new(Mutex::new(None)),
+            files_opened: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    fn append(&self, entry: &JsonlEntry) -> anyhow::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().unwrap();
+        if matches!(&*file, Some((_, size)) if *size >= self.max_file_bytes) {
+            *file = None;
+        }
+        let (file, size) = match &mut *file {
+            Some(file) => file,
+            None => {
+                let path = self.directory.join(format!(
+                    "events-{}-{:06}.jsonl",
+                    chrono::Utc::now().format("%Y%m%dT%H%M%S%.3f"),
+                    self.files_opened.fetch_add(1, Ordering::Relaxed)
+                ));
+                log::info!("Recording to {}", path.display());
+                file.insert((OpenOptions::new().create(true).append(true).open(path)?, 0))
+            }
+        };
+        file.write_all(line.as_bytes())?;
+        *size += line.len() as u64;
+        Ok(())
+    }
+}
+
+impl Backpressure for JsonlSink {}
+
+/// Records the entries of one stream as they are, without decoding them.
+struct Recorder {
+    stream_key: &'static str,
+}
+
+#[async_trait::async_trait]
+impl EventHandler<JsonlSink> for Recorder {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        jsonl_sink: &JsonlSink,
+    ) -> anyhow::Result<()> {
+        jsonl_sink.append(&JsonlEntry {
+            stream: self.stream_key.to_string(),
+            id: id.to_string(),
+            fields: values
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), String::from_redis_value(v)?)))
+                .collect::<redis::RedisResult<_>>()?,
+        })
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        jsonl_sink: &JsonlSink,
+    ) -> anyhow::Result<()> {
+        jsonl_sink.append(&JsonlEntry {
+            stream: self.stream_key.to_string(),
+            id: id.to_string(),
+            fields: HashMap::from_iter([(
+                "revert".to_string(),
+                serde_json::json!({ "block_height": block_height }).to_string(),
+            )]),
+        })
+    }
+}
+
+/// Recorders of every stream, for `stream_all`.
+pub fn recorders(
+    store_transactions: bool,
+) -> Vec<(&'static str, Box<dyn EventHandler<JsonlSink>>)> {
+    STREAM_KEYS
+        .iter()
+        .filter(|stream_key| store_transactions || **stream_key != "transactions")
+        .map(|stream_key| {
+            (
+                *stream_key,
+                Box::new(Recorder { stream_key }) as Box<dyn EventHandler<JsonlSink>>,
+            )
+        })
+        .collect()
+}
+
+async fn replay_entry<Db: Sync>(
+    handler: &dyn EventHandler<Db>,
+    id: &str,
+    values: HashMap<String, Value>,
+    db: &Db,
+) -> anyhow::Result<()> {
+    match values.get("revert") {
+        Some(revert) => handler.revert(id, parse_revert(revert)?, db).await,
+        None => handler.handle(id, values, db).await,
+    }
+}
+
+/// Recorded files in `directory`, oldest first.
+pub fn jsonl_files(directory: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = std::fs::read_dir(directory)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    paths.retain(|path| {
+        path.extension()
+            .is_some_and(|extension| extension == "jsonl")
+    });
+    paths.sort();
+    Ok(paths)
+}
+
+/// Stream, ID and fields of a recorded entry.
+pub type RecordedEntry = (String, String, HashMap<String, Value>);
+
+/// Reads every entry recorded in `path`.
+pub fn read_entries(
+    path: &Path,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<RecordedEntry>>> {
+    Ok(BufReader::new(File::open(path)?).lines().map(|line| {
+        let entry: JsonlEntry = serde_json::from_str(&line?)?;
+        let values = entry
+            .fields
+            .into_iter()
+            .map(|(k, v)| (k, Value::Data(v.into_bytes())))
+            .collect();
+        Ok((entry.stream, entry.id, values))
+    }))
+}
+
+/// Feeds every recorded entry in `directory` through the handler of its stream.
+pub async fn replay_all<Db: Sync>(
+    directory: &Path,
+    handlers: Vec<(&'static str, Box<dyn EventHandler<Db> + '_>)>,
+    db: Db,
+) -> anyhow::Result<()> {
+    let handlers = handlers.into_iter().collect::<HashMap<_, _>>();
+    for path in jsonl_files(directory)? {
+        log::info!("Replaying {}", path.display());
+        let mut replayed = 0;
+        for entry in read_entries(&path)? {
+            let (stream, id, values) = entry?;
+            let Some(handler) = handlers.get(stream.as_str()) else {
+                log::warn!("Skipping entry {id} of unknown stream {stream}");
+                continue;
+            };
+            replay_entry(handler.as_ref(), &id, values, &db).await?;
+            replayed += 1;
+        }
+        log::info!("Replayed {replayed} entries from {}", path.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Handles entries by remembering them.
+    #[derive(Clone, Default)]
+    struct Replayed(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl EventHandler<()> for Replayed {
+        async fn handle(
+            &self,
+            id: &str,
+            values: HashMap<String, Value>,
+            _db: &(),
+        ) -> anyhow::Result<()> {
+            let event = String::from_redis_value(&values["event"])?;
+            self.0.lock().unwrap().push(format!("{id} {event}"));
+            Ok(())
+        }
+
+        async fn revert(&self, id: &str, block_height: u64, _db: &()) -> anyhow::Result<()> {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{id} revert {block_height}"));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_entries_in_order() {
+        let directory = std::env::temp_dir().join(format!("jsonl_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir_all(&directory).unwrap();
+        let sink = JsonlSink {
+            directory: directory.clone(),
+            // A new file after every entry
+            max_file_bytes: 1,
+            file: Arc::new(Mutex::new(None)),
+            files_opened: Arc::new(AtomicU64::new(0)),
+        };
+
+        let mint = Recorder {
+            stream_key: "nft_mint",
+        };
+        let burn = Recorder {
+            stream_key: "nft_burn",
+        };
+        let event = |n: u64| {
+            HashMap::from([("event".to_string(), Value::Data(n.to_string().into_bytes()))])
+        };
+        mint.handle("1-0", event(1), &sink).await.unwrap();
+        burn.handle("2-0", event(2), &sink).await.unwrap();
+        mint.revert("3-0", 1, &sink).await.unwrap();
+        mint.handle("4-0", event(4), &sink).await.unwrap();
+        assert_eq!(jsonl_files(&directory).unwrap().len(), 4);
+
+        let replayed = Replayed::default();
+        replay_all(
+            &directory,
+            vec![
+                ("nft_mint", Box::new(replayed.clone())),
+                ("nft_burn", Box::new(replayed.clone())),
+            ],
+            (),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            *replayed.0.lock().unwrap(),
+            ["1-0 1", "2-0 2", "3-0 revert 1", "4-0 4"]
+        );
+    }
+}
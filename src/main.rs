@@ -1,18 +1,82 @@
 use std::collections::HashMap;
+use std::path::Path;
 use std::str::FromStr;
+use std::time::Duration;
 
-use events_api_redis_to_db::redis_reader::{create_connection, stream_events};
+use events_api_redis_to_db::redis_reader::{
+    create_connection, stream_events, stream_events_multiplexed, Backpressure, StartPosition,
+    StreamOptions, TrimOptions,
+};
 use events_api_redis_to_db::{
     events::{
-        NftBurnEvent, NftEventContext, NftMintEvent, NftTransferEvent, PotlockDonationEvent,
-        PotlockEventContext, PotlockPotDonationEvent, PotlockPotProjectDonationEvent,
-        TradeBalanceChangeSwapEvent, TradeContext, TradePoolChangeEvent, TradeRawPoolSwapEvent,
+        decode_entry, timestamp, AccountCreateEvent, AccountDeleteEvent, ContractDeployEvent,
+        Decoded, FtBurnEvent, FtMintEvent, FtTransferEvent, LogNep297Event, NearTransferEvent,
+        NftApproveEvent, NftBurnEvent, NftListEvent, NftMintEvent, NftRevokeEvent, NftSaleEvent,
+        NftTransferEvent, NftUnlistEvent, PotlockDonationEvent, PotlockPotConfigEvent,
+        PotlockPotDonationEvent, PotlockPotPayoutEvent, PotlockPotProjectDonationEvent,
+        PotlockProjectStatusEvent, SocialCommentEvent, SocialFollowEvent, SocialLikeEvent,
+        SocialPostEvent, TokenPriceEvent, TradeBalanceChangeSwapEvent, TradeLiquidityAddEvent,
+        TradeLiquidityRemoveEvent, TradePoolChangeEvent, TradeRawPoolSwapEvent, TransactionEvent,
     },
     redis_reader::EventHandler,
 };
-use redis::{FromRedisValue, Value};
+use redis::{aio::ConnectionManager, Value};
 use sqlx::types::BigDecimal;
 
+use crate::filters::Filters;
+
+/// Every stream with its handler, its event and the field its entries are partitioned by
+/// when handled by several workers. Calls `$callback!` with the list, so that the sinks
+/// generate what they need for every stream from the same table.
+macro_rules! streams {
+    ($callback:ident) => {
+        $callback! {
+            "nft_mint" => NftMintHandler(NftMintEvent), "contract_id";
+            "nft_transfer" => NftTransferHandler(NftTransferEvent), "contract_id";
+            "nft_burn" => NftBurnHandler(NftBurnEvent), "contract_id";
+            "potlock_donation" => PotlockDonationHandler(PotlockDonationEvent), "project_id";
+            "potlock_pot_project_donation" => PotlockPotProjectDonationHandler(PotlockPotProjectDonationEvent), "pot_id";
+            "potlock_pot_donation" => PotlockPotDonationHandler(PotlockPotDonationEvent), "pot_id";
+            "trade_pool" => TradeRawPoolSwapHandler(TradeRawPoolSwapEvent), "pool";
+            "trade_swap" => TradeBalanceChangeSwapHandler(TradeBalanceChangeSwapEvent), "trader";
+            "trade_pool_change" => TradePoolChangeHandler(TradePoolChangeEvent), "pool_id";
+            "ft_transfer" => FtTransferHandler(FtTransferEvent), "contract_id";
+            "ft_mint" => FtMintHandler(FtMintEvent), "contract_id";
+            "ft_burn" => FtBurnHandler(FtBurnEvent), "contract_id";
+            "near_transfer" => NearTransferHandler(NearTransferEvent), "sender_id";
+            "social_post" => SocialPostHandler(SocialPostEvent), "account_id";
+            "social_comment" => SocialCommentHandler(SocialCommentEvent), "account_id";
+            "social_like" => SocialLikeHandler(SocialLikeEvent), "account_id";
+            "social_follow" => SocialFollowHandler(SocialFollowEvent), "account_id";
+            "log_nep297" => LogNep297Handler(LogNep297Event), "contract_id";
+            "potlock_pot_config" => PotlockPotConfigHandler(PotlockPotConfigEvent), "pot_id";
+            "potlock_project_status" => PotlockProjectStatusHandler(PotlockProjectStatusEvent), "project_id";
+            "potlock_pot_payout" => PotlockPotPayoutHandler(PotlockPotPayoutEvent), "pot_id";
+            "nft_approve" => NftApproveHandler(NftApproveEvent), "contract_id";
+            "nft_revoke" => NftRevokeHandler(NftRevokeEvent), "contract_id";
+            "nft_list" => NftListHandler(NftListEvent), "nft_contract_id";
+            "nft_unlist" => NftUnlistHandler(NftUnlistEvent), "nft_contract_id";
+            "nft_sale" => NftSaleHandler(NftSaleEvent), "nft_contract_id";
+            "trade_liquidity_add" => TradeLiquidityAddHandler(TradeLiquidityAddEvent), "pool";
+            "trade_liquidity_remove" => TradeLiquidityRemoveHandler(TradeLiquidityRemoveEvent), "pool";
+            "account_create" => AccountCreateHandler(AccountCreateEvent), "account_id";
+            "account_delete" => AccountDeleteHandler(AccountDeleteEvent), "account_id";
+            "contract_deploy" => ContractDeployHandler(ContractDeployEvent), "account_id";
+            "transactions" => TransactionHandler(TransactionEvent), "signer_id";
+            "token_price" => TokenPriceHandler(TokenPriceEvent), "token_id";
+        }
+    };
+}
+
+mod backfill;
+mod filters;
+mod jsonl;
+#[cfg(feature = "parquet")]
+mod parquet_sink;
+mod reconcile;
+mod sqlite;
+mod webhook;
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().ok();
@@ -21,81 +85,413 @@ async fn main() -> anyhow::Result<()> {
         .init()
         .unwrap();
 
+    let database_url =
+        std::env::var("DATABASE_URL").expect("DATABASE_URL enviroment variable not set");
+
+    let filters = Filters::from_env()?;
+    let start_positions = start_positions()?;
+    let mut options = StreamOptions {
+        read_count: std::env::var("READ_COUNT")
+            .map(|count| count.parse())
+            .unwrap_or(Ok(100))?,
+        min_read_count: std::env::var("MIN_READ_COUNT")
+            .map(|count| count.parse())
+            .unwrap_or(Ok(1))?,
+        target_batch_duration: Duration::from_millis(
+            std::env::var("TARGET_BATCH_MS")
+                .map(|millis| millis.parse())
+                .unwrap_or(Ok(1000))?,
+        ),
+        block_timeout: Duration::from_millis(
+            std::env::var("READ_BLOCK_MS")
+                .map(|millis| millis.parse())
+                .unwrap_or(Ok(250))?,
+        ),
+        multiplex: std::env::var("MULTIPLEX_STREAMS").is_ok_and(|multiplex| multiplex == "true"),
+        workers: std::env::var("STREAM_WORKERS")
+            .map(|workers| workers.parse())
+            .unwrap_or(Ok(1))?,
+        partition_keys: PARTITION_KEYS
+            .iter()
+            .map(|(stream_key, partition_key)| {
+                let partition_key =
+                    std::env::var(format!("PARTITION_KEY_{}", stream_key.to_uppercase()))
+                        .unwrap_or(partition_key.to_string());
+                (stream_key.to_string(), partition_key)
+            })
+            .collect(),
+        finality_blocks: std::env::var("FINALITY_BLOCKS")
+            .map(|blocks| blocks.parse())
+            .unwrap_or(Ok(0))?,
+        max_block_gaps: max_block_gaps()?,
+        pause_on_anomaly: std::env::var("PAUSE_ON_ANOMALY").is_ok_and(|pause| pause == "true"),
+        start_positions: HashMap::new(),
+        trim: if std::env::var("TRIM_STREAMS").is_ok_and(|trim| trim == "true") {
+            Some(TrimOptions {
+                consumers: std::env::var("TRIM_CONSUMERS")
+                    .map(|consumers| {
+                        consumers
+                            .split(',')
+                            .filter(|consumer| !consumer.is_empty())
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                margin_entries: std::env::var("TRIM_MARGIN_ENTRIES")
+                    .map(|entries| entries.parse())
+                    .unwrap_or(Ok(0))?,
+                margin: Duration::from_secs(
+                    std::env::var("TRIM_MARGIN_SECONDS")
+                        .map(|seconds| seconds.parse())
+                        .unwrap_or(Ok(0))?,
+                ),
+            })
+        } else {
+            None
+        },
+    };
+
+    // The transactions stream is large and only needed for joins, so it's opt-in
+    let store_transactions = std::env::var("STORE_TRANSACTIONS").is_ok_and(|store| store == "true");
+
+    let args = std::env::args().collect::<Vec<_>>();
+    match args.get(1).map(String::as_str) {
+        None => {}
+        Some("replay") => {
+            let directory = Path::new(args.get(2).expect("Usage: replay <directory>"));
+            if database_url.starts_with("sqlite:") {
+                jsonl::replay_all(
+                    directory,
+                    handlers(&filters, true),
+                    sqlite::connect(&database_url).await?,
+                )
+                .await?;
+            } else {
+                jsonl::replay_all(
+                    directory,
+                    handlers(&filters, true),
+                    sqlx::PgPool::connect(&database_url).await?,
+                )
+                .await?;
+            }
+            return Ok(());
+        }
+        Some("webhooks") => {
+            let subscribers_path =
+                Path::new(args.get(2).expect("Usage: webhooks <subscribers.json>"));
+            let webhook_sink = webhook::WebhookSink::new(
+                subscribers_path,
+                sqlx::PgPool::connect(&database_url).await?,
+            )?;
+            webhook_sink.resume_pending().await?;
+            options.start_positions = resolve_start_positions(start_positions, no_table).await?;
+            let redis_connection = create_connection(
+                &std::env::var("REDIS_URL").expect("REDIS_URL enviroment variable not set"),
+            )
+            .await;
+            stream_all(
+                "events_api_webhooks",
+                redis_connection,
+                webhook_sink,
+                handlers(&filters, store_transactions),
+                &options,
+            )
+            .await;
+            return Ok(());
+        }
+        Some("reconcile") => {
+            let reconcile_options = reconcile::ReconcileOptions::from_args(&args[2..])?;
+            let redis_connection = create_connection(
+                &std::env::var("REDIS_URL").expect("REDIS_URL enviroment variable not set"),
+            )
+            .await;
+            reconcile::reconcile(
+                redis_connection,
+                sqlx::PgPool::connect(&database_url).await?,
+                &filters,
+                reconcile_options,
+            )
+            .await?;
+            return Ok(());
+        }
+        Some("backfill") => {
+            let backfill_options = backfill::BackfillOptions::from_args(&args[2..])?;
+            backfill::backfill(
+                sqlx::PgPool::connect(&database_url).await?,
+                &filters,
+                backfill_options,
+            )
+            .await?;
+            return Ok(());
+        }
+        Some(command) => anyhow::bail!("Unknown command {command}"),
+    }
+
     let redis_connection = create_connection(
         &std::env::var("REDIS_URL").expect("REDIS_URL enviroment variable not set"),
     )
     .await;
-    let pg_pool = sqlx::PgPool::connect(
-        &std::env::var("DATABASE_URL").expect("DATABASE_URL enviroment variable not set"),
-    )
-    .await?;
+    if let Some(directory) = database_url.strip_prefix("jsonl://") {
+        let jsonl_sink = jsonl::JsonlSink::open(directory)?;
+        // Entries are recorded as they come, including reverts
+        options.finality_blocks = 0;
+        options.start_positions = resolve_start_positions(start_positions, no_table).await?;
+        stream_all(
+            "events_api_jsonl",
+            redis_connection,
+            jsonl_sink,
+            jsonl::recorders(store_transactions),
+            &options,
+        )
+        .await;
+        return Ok(());
+    }
+    #[cfg(feature = "parquet")]
+    if let Some(directory) = database_url.strip_prefix("parquet://") {
+        let parquet_sink =
+            parquet_sink::ParquetSink::open(parquet_sink::ParquetConfig::from_env(directory)?)?;
+        parquet_sink
+            .rewind(redis_connection.clone(), "events_api_parquet")
+            .await?;
+        options.start_positions = resolve_start_positions(start_positions, no_table).await?;
+        tokio::select! {
+            _ = stream_all(
+                "events_api_parquet",
+                redis_connection,
+                parquet_sink.clone(),
+                handlers(&filters, store_transactions),
+                &options,
+            ) => {}
+            _ = shutdown_signal() => log::info!("Shutting down, finishing open Parquet files"),
+        }
+        parquet_sink.finish_all()?;
+        return Ok(());
+    }
+    if database_url.starts_with("sqlite:") {
+        let sqlite_pool = sqlite::connect(&database_url).await?;
+        options.start_positions = resolve_start_positions(start_positions, |table| {
+            sqlite::max_block_height(&sqlite_pool, table)
+        })
+        .await?;
+        stream_all(
+            "events_api_sqlite",
+            redis_connection,
+            sqlite_pool,
+            handlers(&filters, store_transactions),
+            &options,
+        )
+        .await;
+    } else {
+        let pg_pool = sqlx::PgPool::connect(&database_url).await?;
+        options.start_positions =
+            resolve_start_positions(start_positions, |table| max_block_height(&pg_pool, table))
+                .await?;
+        stream_all(
+            "events_api_server",
+            redis_connection,
+            pg_pool,
+            handlers(&filters, store_transactions),
+            &options,
+        )
+        .await;
+    }
+    Ok(())
+}
+
+macro_rules! define_streams {
+    ($($stream_key:literal => $handler:ident($event:ident), $partition_key:literal;)*) => {
+        const STREAM_KEYS: &[&str] = &[$($stream_key),*];
+
+        /// Fields that entries are partitioned by when handled by several workers, can be
+        /// overridden with `PARTITION_KEY_<STREAM>`.
+        const PARTITION_KEYS: &[(&str, &str)] = &[$(($stream_key, $partition_key)),*];
+
+        /// Handlers of every stream, wrapped with the filters of their stream. The
+        /// transactions stream is only included if `store_transactions` is set.
+        fn handlers<Db>(
+            filters: &Filters,
+            store_transactions: bool,
+        ) -> Vec<(&'static str, Box<dyn EventHandler<Db> + '_>)>
+        where
+            Db: Sync,
+            $($handler: EventHandler<Db>,)*
+        {
+            let mut handlers: Vec<(&'static str, Box<dyn EventHandler<Db> + '_>)> = vec![
+                $(($stream_key, Box::new(filters.wrap::<$event, _>($stream_key, $handler))),)*
+            ];
+            if !store_transactions {
+                handlers.retain(|(stream_key, _)| *stream_key != "transactions");
+            }
+            handlers
+        }
+
+        /// Whether an entry of `stream_key` passes `filters`, see `Filters::passes`.
+        fn passes_filters(
+            filters: &Filters,
+            stream_key: &str,
+            values: &HashMap<String, Value>,
+        ) -> bool {
+            match stream_key {
+                $($stream_key => filters.passes::<$event>(stream_key, values),)*
+                _ => true,
+            }
+        }
+    };
+}
+
+streams!(define_streams);
+
+fn start_positions() -> anyhow::Result<Vec<(&'static str, Option<StartPosition>)>> {
+    let default = std::env::var("START_POSITION").ok();
+    STREAM_KEYS
+        .iter()
+        .filter_map(|stream_key| {
+            std::env::var(format!("START_POSITION_{}", stream_key.to_uppercase()))
+                .ok()
+                .or(default.clone())
+                .map(|position| Ok((*stream_key, parse_start_position(&position)?)))
+        })
+        .collect()
+}
+
+/// `MAX_BLOCK_GAP_<STREAM>` for each stream, or `MAX_BLOCK_GAP`.
+fn max_block_gaps() -> anyhow::Result<HashMap<String, u64>> {
+    let default = std::env::var("MAX_BLOCK_GAP").ok();
+    STREAM_KEYS
+        .iter()
+        .filter_map(|stream_key| {
+            std::env::var(format!("MAX_BLOCK_GAP_{}", stream_key.to_uppercase()))
+                .ok()
+                .or(default.clone())
+                .map(|blocks| Ok((stream_key.to_string(), blocks.parse()?)))
+        })
+        .collect()
+}
+
+fn parse_start_position(position: &str) -> anyhow::Result<Option<StartPosition>> {
+    Ok(Some(match position {
+        "latest" => StartPosition::Latest,
+        "earliest" => StartPosition::Earliest,
+        "table" => return Ok(None),
+        _ => {
+            if let Some(block_height) = position.strip_prefix("block:") {
+                StartPosition::AfterBlock(block_height.parse()?)
+            } else if let Ok(time) = chrono::DateTime::parse_from_rfc3339(position) {
+                StartPosition::Timestamp(time.timestamp_millis().try_into()?)
+            } else if position
+                .split('-')
+                .all(|part| !part.is_empty() && part.parse::<u64>().is_ok())
+            {
+                StartPosition::Id(position.to_string())
+            } else {
+                anyhow::bail!("Invalid start position {position}");
+            }
+        }
+    }))
+}
 
-    let nft_mint_task = stream_events(
-        "nft_mint",
-        NftMintHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let nft_transfer_task = stream_events(
-        "nft_transfer",
-        NftTransferHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let nft_burn_task = stream_events(
-        "nft_burn",
-        NftBurnHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let potlock_donation_task = stream_events(
-        "potlock_donation",
-        PotlockDonationHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let potlock_pot_project_donation_task = stream_events(
-        "potlock_pot_project_donation",
-        PotlockPotProjectDonationHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let potlock_pot_donation_task = stream_events(
-        "potlock_pot_donation",
-        PotlockPotDonationHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let trade_raw_pool_swap_task = stream_events(
-        "trade_pool",
-        TradeRawPoolSwapHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let trade_balance_change_swap_task = stream_events(
-        "trade_swap",
-        TradeBalanceChangeSwapHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-    let trade_pool_change_task = stream_events(
-        "trade_pool_change",
-        TradePoolChangeHandler,
-        redis_connection.clone(),
-        pg_pool.clone(),
-    );
-
-    tokio::join!(
-        nft_mint_task,
-        nft_transfer_task,
-        nft_burn_task,
-        potlock_donation_task,
-        potlock_pot_project_donation_task,
-        potlock_pot_donation_task,
-        trade_raw_pool_swap_task,
-        trade_balance_change_swap_task,
-        trade_pool_change_task,
-    );
+async fn resolve_start_positions<F, Fut>(
+    start_positions: Vec<(&'static str, Option<StartPosition>)>,
+    max_block_height: F,
+) -> anyhow::Result<HashMap<String, StartPosition>>
+where
+    F: Fn(&'static str) -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<Option<u64>>>,
+{
+    let mut resolved = HashMap::new();
+    for (stream_key, start_position) in start_positions {
+        let start_position = match start_position {
+            Some(start_position) => start_position,
+            None => match max_block_height(stream_key).await? {
+                Some(block_height) => StartPosition::AfterBlock(block_height),
+                None => StartPosition::Earliest,
+            },
+        };
+        resolved.insert(stream_key.to_string(), start_position);
+    }
+    Ok(resolved)
+}
+
+/// Resolves on SIGTERM or Ctrl-C.
+#[cfg(feature = "parquet")]
+async fn shutdown_signal() {
+    let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+async fn no_table(stream_key: &str) -> anyhow::Result<Option<u64>> {
+    anyhow::bail!("Start position of {stream_key} can't be `table` for this sink")
+}
+
+async fn max_block_height(pg_pool: &sqlx::PgPool, table: &str) -> anyhow::Result<Option<u64>> {
+    let max_block_height: Option<i64> =
+        sqlx::query_scalar(&format!("SELECT MAX(block_height) FROM {table}"))
+            .fetch_one(pg_pool)
+            .await?;
+    Ok(max_block_height.map(|block_height| block_height as u64))
+}
+
+async fn stream_all<Db>(
+    consumer: &str,
+    redis_connection: ConnectionManager,
+    db: Db,
+    handlers: Vec<(&'static str, Box<dyn EventHandler<Db> + '_>)>,
+    options: &StreamOptions,
+) where
+    Db: Clone + Backpressure,
+{
+    if options.multiplex {
+        stream_events_multiplexed(consumer, handlers, redis_connection, db, options).await;
+        return;
+    }
+    futures::future::join_all(handlers.into_iter().map(|(stream_key, handler)| {
+        stream_events(
+            consumer,
+            stream_key,
+            handler,
+            redis_connection.clone(),
+            db.clone(),
+            options,
+        )
+    }))
+    .await;
+}
+
+/// Removes the rows of `table` from blocks after `block_height`.
+async fn revert_table(
+    pg_pool: &sqlx::PgPool,
+    table: &str,
+    block_height: u64,
+) -> anyhow::Result<()> {
+    // Later blocks have later timestamps, bounding the delete by the last row that stays
+    // keeps it to the latest chunks of the hypertable
+    let kept: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(&format!(
+        "SELECT timestamp FROM {table} WHERE block_height <= $1 ORDER BY timestamp DESC LIMIT 1"
+    ))
+    .bind(block_height as i64)
+    .fetch_optional(pg_pool)
+    .await?;
+    match kept {
+        Some(timestamp) => {
+            sqlx::query(&format!(
+                "DELETE FROM {table} WHERE block_height > $1 AND timestamp >= $2"
+            ))
+            .bind(block_height as i64)
+            .bind(timestamp)
+            .execute(pg_pool)
+            .await?
+        }
+        None => {
+            sqlx::query(&format!("DELETE FROM {table} WHERE block_height > $1"))
+                .bind(block_height as i64)
+                .execute(pg_pool)
+                .await?
+        }
+    };
     Ok(())
 }
 
@@ -105,30 +501,30 @@ struct NftMintHandler;
 impl EventHandler for NftMintHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<NftEventContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<NftMintEvent>(&String::from_redis_value(
-                values.get("mint").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftMintEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO nft_mint (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_ids, memo)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                INSERT INTO nft_mint (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_ids, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.transaction_id,
                 context.receipt_id,
                 context.block_height as i64,
                 context.contract_id,
                 event.owner_id,
                 &event.token_ids,
-                event.memo
+                event.memo,
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -137,6 +533,16 @@ impl EventHandler for NftMintHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_mint", block_height).await?;
+        Ok(())
+    }
 }
 
 struct NftTransferHandler;
@@ -145,23 +551,22 @@ struct NftTransferHandler;
 impl EventHandler for NftTransferHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<NftEventContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<NftTransferEvent>(&String::from_redis_value(
-                values.get("transfer").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftTransferEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO nft_transfer (timestamp, transaction_id, receipt_id, block_height, contract_id, old_owner_id, new_owner_id, token_ids, memo, token_prices_near)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                INSERT INTO nft_transfer (timestamp, transaction_id, receipt_id, block_height, contract_id, old_owner_id, new_owner_id, token_ids, memo, token_prices_near, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.transaction_id,
                 context.receipt_id,
                 context.block_height as i64,
@@ -170,7 +575,8 @@ impl EventHandler for NftTransferHandler {
                 event.new_owner_id,
                 &event.token_ids,
                 event.memo,
-                &event.token_prices_near.iter().map(|price| price.unwrap_or_default()).map(|price| BigDecimal::from_str(&price.to_string()).unwrap()).collect::<Vec<_>>()
+                &event.token_prices_near.iter().map(|price| price.unwrap_or_default()).map(|price| BigDecimal::from_str(&price.to_string()).unwrap()).collect::<Vec<_>>(),
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -179,6 +585,16 @@ impl EventHandler for NftTransferHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_transfer", block_height).await?;
+        Ok(())
+    }
 }
 
 struct NftBurnHandler;
@@ -187,30 +603,30 @@ struct NftBurnHandler;
 impl EventHandler for NftBurnHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<NftEventContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<NftBurnEvent>(&String::from_redis_value(
-                values.get("burn").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftBurnEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO nft_burn (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_ids, memo)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                INSERT INTO nft_burn (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_ids, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.transaction_id,
                 context.receipt_id,
                 context.block_height as i64,
                 context.contract_id,
                 event.owner_id,
                 &event.token_ids,
-                event.memo
+                event.memo,
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -219,6 +635,16 @@ impl EventHandler for NftBurnHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_burn", block_height).await?;
+        Ok(())
+    }
 }
 
 struct PotlockDonationHandler;
@@ -227,23 +653,22 @@ struct PotlockDonationHandler;
 impl EventHandler for PotlockDonationHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<PotlockEventContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<PotlockDonationEvent>(&String::from_redis_value(
-                values.get("donation").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockDonationEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO potlock_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, donor_id, total_amount, ft_id, message, donated_at, project_id, protocol_fee, referrer_id, referrer_fee)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                INSERT INTO potlock_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, donor_id, total_amount, ft_id, message, donated_at, project_id, protocol_fee, referrer_id, referrer_fee, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.transaction_id,
                 context.receipt_id,
                 context.block_height as i64,
@@ -256,7 +681,8 @@ impl EventHandler for PotlockDonationHandler {
                 event.project_id,
                 BigDecimal::from_str(&event.protocol_fee.to_string()).unwrap(),
                 event.referrer_id,
-                event.referrer_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap())
+                event.referrer_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap()),
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -265,6 +691,16 @@ impl EventHandler for PotlockDonationHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "potlock_donation", block_height).await?;
+        Ok(())
+    }
 }
 
 struct PotlockPotProjectDonationHandler;
@@ -273,23 +709,22 @@ struct PotlockPotProjectDonationHandler;
 impl EventHandler for PotlockPotProjectDonationHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<PotlockEventContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<PotlockPotProjectDonationEvent>(&String::from_redis_value(
-                values.get("pot_project_donation").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotProjectDonationEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO potlock_pot_project_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, pot_id, donor_id, total_amount, net_amount, message, donated_at, project_id, referrer_id, referrer_fee, protocol_fee, chef_id, chef_fee)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+                INSERT INTO potlock_pot_project_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, pot_id, donor_id, total_amount, net_amount, message, donated_at, project_id, referrer_id, referrer_fee, protocol_fee, chef_id, chef_fee, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.transaction_id,
                 context.receipt_id,
                 context.block_height as i64,
@@ -305,7 +740,8 @@ impl EventHandler for PotlockPotProjectDonationHandler {
                 event.referrer_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap()),
                 BigDecimal::from_str(&event.protocol_fee.to_string()).unwrap(),
                 event.chef_id,
-                event.chef_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap())
+                event.chef_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap()),
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -314,6 +750,16 @@ impl EventHandler for PotlockPotProjectDonationHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "potlock_pot_project_donation", block_height).await?;
+        Ok(())
+    }
 }
 
 struct PotlockPotDonationHandler;
@@ -322,23 +768,22 @@ struct PotlockPotDonationHandler;
 impl EventHandler for PotlockPotDonationHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<PotlockEventContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<PotlockPotDonationEvent>(&String::from_redis_value(
-                values.get("pot_donation").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotDonationEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO potlock_pot_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, pot_id, donor_id, total_amount, net_amount, message, donated_at, referrer_id, referrer_fee, protocol_fee, chef_id, chef_fee)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+                INSERT INTO potlock_pot_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, pot_id, donor_id, total_amount, net_amount, message, donated_at, referrer_id, referrer_fee, protocol_fee, chef_id, chef_fee, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.transaction_id,
                 context.receipt_id,
                 context.block_height as i64,
@@ -353,7 +798,8 @@ impl EventHandler for PotlockPotDonationHandler {
                 event.referrer_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap()),
                 BigDecimal::from_str(&event.protocol_fee.to_string()).unwrap(),
                 event.chef_id,
-                event.chef_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap())
+                event.chef_fee.map(|fee| BigDecimal::from_str(&fee.to_string()).unwrap()),
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -362,6 +808,16 @@ impl EventHandler for PotlockPotDonationHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "potlock_pot_donation", block_height).await?;
+        Ok(())
+    }
 }
 
 struct TradeRawPoolSwapHandler;
@@ -370,23 +826,22 @@ struct TradeRawPoolSwapHandler;
 impl EventHandler for TradeRawPoolSwapHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<TradeContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<TradeRawPoolSwapEvent>(&String::from_redis_value(
-                values.get("swap").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeRawPoolSwapEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO trade_pool (timestamp, trader, transaction_id, receipt_id, block_height, pool, token_in, token_out, amount_in, amount_out)
-                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                INSERT INTO trade_pool (timestamp, trader, transaction_id, receipt_id, block_height, pool, token_in, token_out, amount_in, amount_out, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.trader,
                 context.transaction_id,
                 context.receipt_id,
@@ -396,6 +851,7 @@ impl EventHandler for TradeRawPoolSwapHandler {
                 event.0.token_out,
                 BigDecimal::from_str(&event.0.amount_in.to_string()).unwrap(),
                 BigDecimal::from_str(&event.0.amount_out.to_string()).unwrap(),
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -404,6 +860,16 @@ impl EventHandler for TradeRawPoolSwapHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "trade_pool", block_height).await?;
+        Ok(())
+    }
 }
 
 struct TradeBalanceChangeSwapHandler;
@@ -412,28 +878,28 @@ struct TradeBalanceChangeSwapHandler;
 impl EventHandler for TradeBalanceChangeSwapHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let (Ok(context), Ok(event)) = (
-            serde_json::from_str::<TradeContext>(&String::from_redis_value(
-                values.get("context").unwrap(),
-            )?),
-            serde_json::from_str::<TradeBalanceChangeSwapEvent>(&String::from_redis_value(
-                values.get("balance_change").unwrap(),
-            )?),
-        ) {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeBalanceChangeSwapEvent>(&values)
+        {
             sqlx::query!(
                 r#"
-                INSERT INTO trade_swap (timestamp, trader, transaction_id, receipt_id, block_height, balance_changes)
-                VALUES ($1, $2, $3, $4, $5, $6)
+                INSERT INTO trade_swap (timestamp, trader, transaction_id, receipt_id, block_height, balance_changes, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
                 "#,
-                chrono::DateTime::from_timestamp((context.block_timestamp_nanosec / 1_000_000_000) as i64, (context.block_timestamp_nanosec % 1_000_000_000) as u32),
+                timestamp(context.block_timestamp_nanosec),
                 context.trader,
                 context.transaction_id,
                 context.receipt_id,
                 context.block_height as i64,
-                serde_json::Value::Object(event.balance_changes.into_iter().map(|(k, v)| (k, serde_json::Value::String(v.to_string()))).collect())
+                serde_json::Value::Object(event.balance_changes.into_iter().map(|(k, v)| (k, serde_json::Value::String(v.to_string()))).collect()),
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -442,6 +908,16 @@ impl EventHandler for TradeBalanceChangeSwapHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "trade_swap", block_height).await?;
+        Ok(())
+    }
 }
 
 struct TradePoolChangeHandler;
@@ -450,25 +926,22 @@ struct TradePoolChangeHandler;
 impl EventHandler for TradePoolChangeHandler {
     async fn handle(
         &self,
+        _id: &str,
         values: HashMap<String, Value>,
         pg_pool: &sqlx::PgPool,
     ) -> anyhow::Result<()> {
-        if let Ok(event) = serde_json::from_str::<TradePoolChangeEvent>(&String::from_redis_value(
-            values.get("pool_change").unwrap(),
-        )?) {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TradePoolChangeEvent>(&values) {
             sqlx::query!(
                 r#"
-                INSERT INTO trade_pool_change (timestamp, receipt_id, block_height, pool_id, pool)
-                VALUES ($1, $2, $3, $4, $5)
+                INSERT INTO trade_pool_change (timestamp, receipt_id, block_height, pool_id, pool, extra)
+                VALUES ($1, $2, $3, $4, $5, $6)
                 "#,
-                chrono::DateTime::from_timestamp(
-                    (event.block_timestamp_nanosec / 1_000_000_000) as i64,
-                    (event.block_timestamp_nanosec % 1_000_000_000) as u32
-                ),
+                timestamp(event.block_timestamp_nanosec),
                 event.receipt_id,
                 event.block_height as i64,
                 event.pool_id,
                 serde_json::to_value(event.pool)?,
+                extra
             )
             .execute(pg_pool)
             .await?;
@@ -477,4 +950,1594 @@ impl EventHandler for TradePoolChangeHandler {
         }
         Ok(())
     }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "trade_pool_change", block_height).await?;
+        Ok(())
+    }
+}
+
+struct FtTransferHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for FtTransferHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtTransferEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO ft_transfer (timestamp, transaction_id, receipt_id, block_height, contract_id, old_owner_id, new_owner_id, amount, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.old_owner_id,
+                event.new_owner_id,
+                BigDecimal::from_str(&event.amount.to_string()).unwrap(),
+                event.memo,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse ft transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "ft_transfer", block_height).await?;
+        Ok(())
+    }
+}
+
+struct FtMintHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for FtMintHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtMintEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO ft_mint (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, amount, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.owner_id,
+                BigDecimal::from_str(&event.amount.to_string()).unwrap(),
+                event.memo,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse ft mint event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "ft_mint", block_height).await?;
+        Ok(())
+    }
+}
+
+struct FtBurnHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for FtBurnHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtBurnEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO ft_burn (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, amount, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.owner_id,
+                BigDecimal::from_str(&event.amount.to_string()).unwrap(),
+                event.memo,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse ft burn event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "ft_burn", block_height).await?;
+        Ok(())
+    }
+}
+
+struct NearTransferHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for NearTransferHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<NearTransferEvent>(&values) {
+            sqlx::query!(
+                r#"
+                INSERT INTO near_transfer (timestamp, transaction_id, receipt_id, block_height, sender_id, receiver_id, amount, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+                timestamp(event.block_timestamp_nanosec),
+                event.transaction_id,
+                event.receipt_id,
+                event.block_height as i64,
+                event.sender_id,
+                event.receiver_id,
+                BigDecimal::from_str(&event.amount.to_string()).unwrap(),
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse near transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "near_transfer", block_height).await?;
+        Ok(())
+    }
+}
+
+struct SocialPostHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for SocialPostHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialPostEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO social_post (timestamp, transaction_id, receipt_id, block_height, account_id, path, text, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.account_id,
+                event.path,
+                event.text,
+                event.value,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social post event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "social_post", block_height).await?;
+        Ok(())
+    }
+}
+
+struct SocialCommentHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for SocialCommentHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialCommentEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO social_comment (timestamp, transaction_id, receipt_id, block_height, account_id, path, text, item_author_id, item_block_height, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.account_id,
+                event.path,
+                event.text,
+                event.item_author_id,
+                event.item_block_height as i64,
+                event.value,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social comment event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "social_comment", block_height).await?;
+        Ok(())
+    }
+}
+
+struct SocialLikeHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for SocialLikeHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialLikeEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO social_like (timestamp, transaction_id, receipt_id, block_height, account_id, path, item_author_id, item_block_height, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.account_id,
+                event.path,
+                event.item_author_id,
+                event.item_block_height as i64,
+                event.value,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social like event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "social_like", block_height).await?;
+        Ok(())
+    }
+}
+
+struct SocialFollowHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for SocialFollowHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialFollowEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO social_follow (timestamp, transaction_id, receipt_id, block_height, account_id, path, target_id, follow, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.account_id,
+                event.path,
+                event.target_id,
+                event.follow,
+                event.value,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social follow event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "social_follow", block_height).await?;
+        Ok(())
+    }
+}
+
+struct LogNep297Handler;
+
+#[async_trait::async_trait]
+impl EventHandler for LogNep297Handler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<LogNep297Event>(&values)
+        {
+            if event.is_promoted() {
+                return Ok(());
+            }
+            sqlx::query!(
+                r#"
+                INSERT INTO log_nep297 (timestamp, transaction_id, receipt_id, block_height, contract_id, standard, version, event, data, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.standard,
+                event.version,
+                event.event,
+                event.data,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nep297 log event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "log_nep297", block_height).await?;
+        Ok(())
+    }
+}
+
+struct PotlockPotConfigHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for PotlockPotConfigHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotConfigEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO potlock_pot_config (timestamp, transaction_id, receipt_id, block_height, pot_id, created, owner_id, admins, chef_id, name, description, application_start, application_end, public_round_start, public_round_end, config, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.pot_id,
+                event.created,
+                event.owner_id,
+                &event.admins,
+                event.chef_id,
+                event.name,
+                event.description,
+                event.application_start,
+                event.application_end,
+                event.public_round_start,
+                event.public_round_end,
+                event.config,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock pot config event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "potlock_pot_config", block_height).await?;
+        Ok(())
+    }
+}
+
+struct PotlockProjectStatusHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for PotlockProjectStatusHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockProjectStatusEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO potlock_project_status (timestamp, transaction_id, receipt_id, block_height, project_id, pot_id, status, reviewer_id, review_notes, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.project_id,
+                event.pot_id,
+                event.status,
+                event.reviewer_id,
+                event.review_notes,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock project status event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "potlock_project_status", block_height).await?;
+        Ok(())
+    }
+}
+
+struct PotlockPotPayoutHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for PotlockPotPayoutHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotPayoutEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO potlock_pot_payout (timestamp, transaction_id, receipt_id, block_height, payout_id, pot_id, project_id, amount, paid_at, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.payout_id,
+                event.pot_id,
+                event.project_id,
+                BigDecimal::from_str(&event.amount.to_string()).unwrap(),
+                event.paid_at,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock pot payout event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "potlock_pot_payout", block_height).await?;
+        Ok(())
+    }
+}
+
+struct NftApproveHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for NftApproveHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftApproveEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO nft_approve (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_id, account_id, approval_id, msg, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.owner_id,
+                event.token_id,
+                event.account_id,
+                event.approval_id as i64,
+                event.msg,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nft approve event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_approve", block_height).await?;
+        Ok(())
+    }
+}
+
+struct NftRevokeHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for NftRevokeHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftRevokeEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO nft_revoke (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_id, account_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.owner_id,
+                event.token_id,
+                event.account_id,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nft revoke event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_revoke", block_height).await?;
+        Ok(())
+    }
+}
+
+/// A row of `nft_listing`, as set by a marketplace event.
+struct Listing<'a> {
+    marketplace_id: &'a str,
+    nft_contract_id: &'a str,
+    token_id: &'a str,
+    seller_id: &'a str,
+    price: Option<u128>,
+    ft_id: Option<&'a str>,
+    timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    block_height: u64,
+    active: bool,
+}
+
+/// Updates a listing unless an event that comes later already did, since list, unlist and
+/// sale events are read from different streams. Events are ordered by block, then sales
+/// come after unlists and unlists after lists, then by price, so that events of the same
+/// block leave the same listing whatever order they're handled in. `rebuild_listings`
+/// orders them the same way.
+async fn set_listing(tx: &mut sqlx::PgConnection, listing: &Listing<'_>) -> anyhow::Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO nft_listing (marketplace_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (marketplace_id, nft_contract_id, token_id) DO UPDATE SET
+            seller_id = EXCLUDED.seller_id,
+            price = EXCLUDED.price,
+            ft_id = EXCLUDED.ft_id,
+            timestamp = EXCLUDED.timestamp,
+            block_height = EXCLUDED.block_height,
+            active = EXCLUDED.active
+        WHERE (
+            nft_listing.block_height,
+            CASE WHEN nft_listing.active THEN 0 WHEN nft_listing.price IS NULL THEN 1 ELSE 2 END,
+            COALESCE(nft_listing.price, 0)
+        ) <= (
+            EXCLUDED.block_height,
+            CASE WHEN EXCLUDED.active THEN 0 WHEN EXCLUDED.price IS NULL THEN 1 ELSE 2 END,
+            COALESCE(EXCLUDED.price, 0)
+        )
+        "#,
+        listing.marketplace_id,
+        listing.nft_contract_id,
+        listing.token_id,
+        listing.seller_id,
+        listing.price.map(|price| BigDecimal::from_str(&price.to_string()).unwrap()),
+        listing.ft_id,
+        listing.timestamp,
+        listing.block_height as i64,
+        listing.active,
+    )
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Recomputes the listings changed after `block_height` from the marketplace events that
+/// are left after a revert. Only the reverted stream lost events, the others may still
+/// have events after the block, so all remaining events count. Listings without any are
+/// removed.
+async fn rebuild_listings(pg_pool: &sqlx::PgPool, block_height: u64) -> anyhow::Result<()> {
+    let mut tx = pg_pool.begin().await?;
+    let listings = sqlx::query!(
+        "DELETE FROM nft_listing WHERE block_height > $1 RETURNING marketplace_id, nft_contract_id, token_id",
+        block_height as i64
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    for listing in listings {
+        sqlx::query!(
+            r#"
+            INSERT INTO nft_listing (marketplace_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active)
+            SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active FROM (
+                SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, TRUE AS active, 0 AS rank
+                FROM nft_list WHERE contract_id = $1 AND nft_contract_id = $2 AND token_id = $3
+                UNION ALL
+                SELECT contract_id, nft_contract_id, token_id, seller_id, NULL, NULL, timestamp, block_height, FALSE, 1
+                FROM nft_unlist WHERE contract_id = $1 AND nft_contract_id = $2 AND token_id = $3
+                UNION ALL
+                SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, FALSE, 2
+                FROM nft_sale WHERE contract_id = $1 AND nft_contract_id = $2 AND token_id = $3
+            ) AS events
+            ORDER BY block_height DESC, rank DESC, COALESCE(price, 0) DESC
+            LIMIT 1
+            "#,
+            listing.marketplace_id,
+            listing.nft_contract_id,
+            listing.token_id,
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+struct NftListHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for NftListHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftListEvent>(&values)
+        {
+            let mut tx = pg_pool.begin().await?;
+            set_listing(
+                &mut tx,
+                &Listing {
+                    marketplace_id: &context.contract_id,
+                    nft_contract_id: &event.nft_contract_id,
+                    token_id: &event.token_id,
+                    seller_id: &event.seller_id,
+                    price: Some(event.price),
+                    ft_id: Some(&event.ft_id),
+                    timestamp: timestamp(context.block_timestamp_nanosec),
+                    block_height: context.block_height,
+                    active: true,
+                },
+            )
+            .await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO nft_list (timestamp, transaction_id, receipt_id, block_height, contract_id, nft_contract_id, token_id, seller_id, price, ft_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.nft_contract_id,
+                event.token_id,
+                event.seller_id,
+                BigDecimal::from_str(&event.price.to_string()).unwrap(),
+                event.ft_id,
+                extra
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        } else {
+            log::error!("Failed to parse nft list event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_list", block_height).await?;
+        rebuild_listings(pg_pool, block_height).await?;
+        Ok(())
+    }
+}
+
+struct NftUnlistHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for NftUnlistHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftUnlistEvent>(&values)
+        {
+            let mut tx = pg_pool.begin().await?;
+            set_listing(
+                &mut tx,
+                &Listing {
+                    marketplace_id: &context.contract_id,
+                    nft_contract_id: &event.nft_contract_id,
+                    token_id: &event.token_id,
+                    seller_id: &event.seller_id,
+                    price: None,
+                    ft_id: None,
+                    timestamp: timestamp(context.block_timestamp_nanosec),
+                    block_height: context.block_height,
+                    active: false,
+                },
+            )
+            .await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO nft_unlist (timestamp, transaction_id, receipt_id, block_height, contract_id, nft_contract_id, token_id, seller_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.nft_contract_id,
+                event.token_id,
+                event.seller_id,
+                extra
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        } else {
+            log::error!("Failed to parse nft unlist event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_unlist", block_height).await?;
+        rebuild_listings(pg_pool, block_height).await?;
+        Ok(())
+    }
+}
+
+struct NftSaleHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for NftSaleHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftSaleEvent>(&values)
+        {
+            let mut tx = pg_pool.begin().await?;
+            set_listing(
+                &mut tx,
+                &Listing {
+                    marketplace_id: &context.contract_id,
+                    nft_contract_id: &event.nft_contract_id,
+                    token_id: &event.token_id,
+                    seller_id: &event.seller_id,
+                    price: Some(event.price),
+                    ft_id: Some(&event.ft_id),
+                    timestamp: timestamp(context.block_timestamp_nanosec),
+                    block_height: context.block_height,
+                    active: false,
+                },
+            )
+            .await?;
+            sqlx::query!(
+                r#"
+                INSERT INTO nft_sale (timestamp, transaction_id, receipt_id, block_height, contract_id, nft_contract_id, token_id, seller_id, buyer_id, price, ft_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                context.contract_id,
+                event.nft_contract_id,
+                event.token_id,
+                event.seller_id,
+                event.buyer_id,
+                BigDecimal::from_str(&event.price.to_string()).unwrap(),
+                event.ft_id,
+                extra
+            )
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        } else {
+            log::error!("Failed to parse nft sale event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "nft_sale", block_height).await?;
+        rebuild_listings(pg_pool, block_height).await?;
+        Ok(())
+    }
+}
+
+struct TradeLiquidityAddHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for TradeLiquidityAddHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeLiquidityAddEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO trade_liquidity_add (timestamp, trader, transaction_id, receipt_id, block_height, pool, tokens, amounts, shares, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.trader,
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.pool,
+                &event.tokens,
+                &event.amounts.iter().map(|amount| BigDecimal::from_str(&amount.to_string()).unwrap()).collect::<Vec<_>>(),
+                BigDecimal::from_str(&event.shares.to_string()).unwrap(),
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse liquidity add event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "trade_liquidity_add", block_height).await?;
+        Ok(())
+    }
+}
+
+struct TradeLiquidityRemoveHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for TradeLiquidityRemoveHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeLiquidityRemoveEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO trade_liquidity_remove (timestamp, trader, transaction_id, receipt_id, block_height, pool, tokens, amounts, shares, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.trader,
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.pool,
+                &event.tokens,
+                &event.amounts.iter().map(|amount| BigDecimal::from_str(&amount.to_string()).unwrap()).collect::<Vec<_>>(),
+                BigDecimal::from_str(&event.shares.to_string()).unwrap(),
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse liquidity remove event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "trade_liquidity_remove", block_height).await?;
+        Ok(())
+    }
+}
+
+struct AccountCreateHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for AccountCreateHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<AccountCreateEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO account_create (timestamp, transaction_id, receipt_id, block_height, account_id, creator_id, initial_balance, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.account_id,
+                event.creator_id,
+                BigDecimal::from_str(&event.initial_balance.to_string()).unwrap(),
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse account create event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "account_create", block_height).await?;
+        Ok(())
+    }
+}
+
+struct AccountDeleteHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for AccountDeleteHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<AccountDeleteEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO account_delete (timestamp, transaction_id, receipt_id, block_height, account_id, beneficiary_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.account_id,
+                event.beneficiary_id,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse account delete event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "account_delete", block_height).await?;
+        Ok(())
+    }
+}
+
+struct ContractDeployHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for ContractDeployHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<ContractDeployEvent>(&values)
+        {
+            sqlx::query!(
+                r#"
+                INSERT INTO contract_deploy (timestamp, transaction_id, receipt_id, block_height, account_id, code_hash, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                timestamp(context.block_timestamp_nanosec),
+                context.transaction_id,
+                context.receipt_id,
+                context.block_height as i64,
+                event.account_id,
+                event.code_hash,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse contract deploy event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "contract_deploy", block_height).await?;
+        Ok(())
+    }
+}
+
+struct TransactionHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for TransactionHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TransactionEvent>(&values) {
+            sqlx::query!(
+                r#"
+                INSERT INTO transactions (timestamp, transaction_id, block_height, block_hash, signer_id, receiver_id, gas_burnt, tokens_burnt, status, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT (transaction_id, timestamp) DO NOTHING
+                "#,
+                timestamp(event.block_timestamp_nanosec),
+                event.transaction_id,
+                event.block_height as i64,
+                event.block_hash,
+                event.signer_id,
+                event.receiver_id,
+                event.gas_burnt as i64,
+                BigDecimal::from_str(&event.tokens_burnt.to_string()).unwrap(),
+                event.status,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse transaction event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "transactions", block_height).await?;
+        Ok(())
+    }
+}
+
+struct TokenPriceHandler;
+
+#[async_trait::async_trait]
+impl EventHandler for TokenPriceHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TokenPriceEvent>(&values) {
+            sqlx::query!(
+                r#"
+                INSERT INTO token_price (timestamp, block_height, token_id, price_usd, decimals, extra)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+                timestamp(event.block_timestamp_nanosec),
+                event.block_height as i64,
+                event.token_id,
+                BigDecimal::from_str(&event.price_usd).unwrap(),
+                event.decimals as i64,
+                extra
+            )
+            .execute(pg_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse token price event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        pg_pool: &sqlx::PgPool,
+    ) -> anyhow::Result<()> {
+        revert_table(pg_pool, "token_price", block_height).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn insert_transfer(pg_pool: &sqlx::PgPool, block_height: i64, seconds: i64) {
+        sqlx::query(
+            "INSERT INTO near_transfer (timestamp, transaction_id, receipt_id, block_height, sender_id, receiver_id, amount)
+            VALUES (to_timestamp($1), '', '', $2, 'a.near', 'b.near', 1)",
+        )
+        .bind(seconds as f64)
+        .bind(block_height)
+        .execute(pg_pool)
+        .await
+        .unwrap();
+    }
+
+    async fn block_heights(pg_pool: &sqlx::PgPool) -> Vec<i64> {
+        sqlx::query_scalar("SELECT block_height FROM near_transfer ORDER BY block_height")
+            .fetch_all(pg_pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn reverts_rows_after_block(pg_pool: sqlx::PgPool) {
+        for block_height in 1..=4 {
+            insert_transfer(&pg_pool, block_height, block_height).await;
+        }
+        revert_table(&pg_pool, "near_transfer", 2).await.unwrap();
+        assert_eq!(block_heights(&pg_pool).await, [1, 2]);
+
+        // Nothing stays, so there's no timestamp to bound the delete by
+        revert_table(&pg_pool, "near_transfer", 0).await.unwrap();
+        assert!(block_heights(&pg_pool).await.is_empty());
+    }
+
+    /// Stores a marketplace event of `table` for the same token, like its handler does.
+    async fn store_marketplace_event(
+        pg_pool: &sqlx::PgPool,
+        table: &str,
+        block_height: u64,
+        price: Option<u128>,
+    ) {
+        let mut tx = pg_pool.begin().await.unwrap();
+        set_listing(
+            &mut tx,
+            &Listing {
+                marketplace_id: "market.near",
+                nft_contract_id: "nft.near",
+                token_id: "1",
+                seller_id: "alice.near",
+                price,
+                ft_id: price.map(|_| "near"),
+                timestamp: chrono::DateTime::from_timestamp(block_height as i64, 0),
+                block_height,
+                active: table == "nft_list",
+            },
+        )
+        .await
+        .unwrap();
+        let (columns, values) = match table {
+            "nft_list" => (", price, ft_id", ", $3::NUMERIC, 'near'"),
+            "nft_sale" => (
+                ", buyer_id, price, ft_id",
+                ", 'bob.near', $3::NUMERIC, 'near'",
+            ),
+            _ => ("", ""),
+        };
+        sqlx::query(&format!(
+            "INSERT INTO {table} (timestamp, transaction_id, receipt_id, block_height, contract_id, nft_contract_id, token_id, seller_id{columns})
+            VALUES (to_timestamp($1), '', '', $2, 'market.near', 'nft.near', '1', 'alice.near'{values})"
+        ))
+        .bind(block_height as f64)
+        .bind(block_height as i64)
+        .bind(price.map(|price| price.to_string()))
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+    }
+
+    async fn listing(pg_pool: &sqlx::PgPool) -> Option<(i64, bool, Option<String>)> {
+        sqlx::query_as("SELECT block_height, active, price::TEXT FROM nft_listing")
+            .fetch_optional(pg_pool)
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test]
+    async fn orders_listing_events_of_one_block(pg_pool: sqlx::PgPool) {
+        let events = [
+            ("nft_list", Some(10)),
+            ("nft_sale", Some(5)),
+            ("nft_unlist", None),
+            ("nft_list", Some(20)),
+        ];
+        for order in [[0, 1, 2, 3], [3, 2, 1, 0], [1, 3, 0, 2]] {
+            sqlx::query("TRUNCATE nft_listing, nft_list, nft_unlist, nft_sale")
+                .execute(&pg_pool)
+                .await
+                .unwrap();
+            for i in order {
+                let (table, price) = events[i];
+                store_marketplace_event(&pg_pool, table, 5, price).await;
+            }
+            assert_eq!(
+                listing(&pg_pool).await,
+                Some((5, false, Some("5".to_string())))
+            );
+
+            // A rebuild picks the same event
+            sqlx::query("UPDATE nft_listing SET block_height = 6")
+                .execute(&pg_pool)
+                .await
+                .unwrap();
+            rebuild_listings(&pg_pool, 5).await.unwrap();
+            assert_eq!(
+                listing(&pg_pool).await,
+                Some((5, false, Some("5".to_string())))
+            );
+        }
+
+        // Of two lists, the higher price wins either way
+        for prices in [[10, 20], [20, 10]] {
+            sqlx::query("TRUNCATE nft_listing, nft_list, nft_unlist, nft_sale")
+                .execute(&pg_pool)
+                .await
+                .unwrap();
+            for price in prices {
+                store_marketplace_event(&pg_pool, "nft_list", 5, Some(price)).await;
+            }
+            assert_eq!(
+                listing(&pg_pool).await,
+                Some((5, true, Some("20".to_string())))
+            );
+        }
+    }
+
+    #[sqlx::test]
+    async fn rebuilds_listings_from_remaining_events(pg_pool: sqlx::PgPool) {
+        store_marketplace_event(&pg_pool, "nft_list", 3, Some(10)).await;
+        store_marketplace_event(&pg_pool, "nft_unlist", 6, None).await;
+        store_marketplace_event(&pg_pool, "nft_list", 7, Some(20)).await;
+
+        // The unlist stream wasn't reverted, its event after the block still counts
+        revert_table(&pg_pool, "nft_list", 5).await.unwrap();
+        rebuild_listings(&pg_pool, 5).await.unwrap();
+        assert_eq!(listing(&pg_pool).await, Some((6, false, None)));
+
+        revert_table(&pg_pool, "nft_unlist", 2).await.unwrap();
+        rebuild_listings(&pg_pool, 2).await.unwrap();
+        assert_eq!(
+            listing(&pg_pool).await,
+            Some((3, true, Some("10".to_string())))
+        );
+
+        revert_table(&pg_pool, "nft_list", 2).await.unwrap();
+        rebuild_listings(&pg_pool, 2).await.unwrap();
+        assert_eq!(listing(&pg_pool).await, None);
+    }
+
+    #[sqlx::test]
+    async fn stores_transactions_once(pg_pool: sqlx::PgPool) {
+        let transaction = serde_json::json!({
+            "transaction_id": "tx",
+            "signer_id": "alice.near",
+            "receiver_id": "bob.near",
+            "block_height": 1,
+            "block_hash": "hash",
+            "block_timestamp_nanosec": "1700000000000000000",
+            "gas_burnt": 1,
+            "tokens_burnt": "1",
+            "status": "SuccessValue",
+        });
+        let values = HashMap::from([(
+            "transaction".to_string(),
+            Value::Data(transaction.to_string().into_bytes()),
+        )]);
+        for id in ["1-0", "2-0"] {
+            TransactionHandler
+                .handle(id, values.clone(), &pg_pool)
+                .await
+                .unwrap();
+        }
+        let count: i64 = sqlx::query_scalar("SELECT count(*) FROM transactions")
+            .fetch_one(&pg_pool)
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[sqlx::test]
+    async fn values_rows_at_nearest_price(pg_pool: sqlx::PgPool) {
+        // NEAR amounts are in yoctoNEAR, whatever decimals the feed has for wrap.near
+        for (seconds, price_usd) in [(100, 2), (200, 3)] {
+            sqlx::query(
+                "INSERT INTO token_price (timestamp, block_height, token_id, price_usd, decimals)
+                VALUES (to_timestamp($1), 0, 'wrap.near', $2, 18)",
+            )
+            .bind(seconds as f64)
+            .bind(price_usd as i64)
+            .execute(&pg_pool)
+            .await
+            .unwrap();
+        }
+        for seconds in [50, 120, 190, 250] {
+            sqlx::query(
+                "INSERT INTO nft_transfer (timestamp, transaction_id, receipt_id, block_height, contract_id, old_owner_id, new_owner_id, token_ids, token_prices_near)
+                VALUES (to_timestamp($1), '', '', 0, 'nft.near', 'a.near', 'b.near', '{1}', '{1000000000000000000000000}')",
+            )
+            .bind(seconds as f64)
+            .execute(&pg_pool)
+            .await
+            .unwrap();
+        }
+        let prices: Vec<String> = sqlx::query_scalar(
+            "SELECT round(token_prices_usd[1], 6)::TEXT FROM nft_transfer_usd ORDER BY timestamp",
+        )
+        .fetch_all(&pg_pool)
+        .await
+        .unwrap();
+        assert_eq!(prices, ["2.000000", "2.000000", "3.000000", "3.000000"]);
+    }
 }
@@ -0,0 +1,2646 @@
+//! Parquet archive sink. Each stream is written to
+//! `{directory}/{stream}/date=YYYY-MM-DD/hour=HH/{first_id}.parquet`, partitioned by
+//! `block_timestamp_nanosec`. Amounts are stored as strings, since u128 doesn't fit into
+//! decimal(38,0). Fields of the entries that the event types don't read are kept as JSON
+//! in the `extra` column. Every finished file is appended to `{directory}/manifest.jsonl` along
+//! with the range of Redis entry IDs it covers.
+//!
+//! Files are only readable after they're closed, so rows of open files are lost if the
+//! process dies. On startup, cursors are rewound to the last ID in the manifest, and
+//! since files are named after their first entry ID, the re-read entries overwrite the
+//! incomplete file. Before the first file of a stream is opened, the ID preceding its
+//! first entry is recorded in the manifest, so that streams without finished files are
+//! rewound too. Open files are finished on SIGTERM and Ctrl-C.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use arrow::{
+    array::{
+        ArrayRef, Int64Array, ListBuilder, StringArray, StringBuilder, TimestampNanosecondArray,
+    },
+    datatypes::{DataType, Field, Schema, SchemaRef, TimeUnit},
+    record_batch::RecordBatch,
+};
+use chrono::{DateTime, Utc};
+use events_api_redis_to_db::{
+    events::{
+        decode_entry, timestamp, AccountCreateEvent, AccountDeleteEvent, ContractDeployEvent,
+        Decoded, FtBurnEvent, FtMintEvent, FtTransferEvent, LogNep297Event, NearTransferEvent,
+        NftApproveEvent, NftBurnEvent, NftListEvent, NftMintEvent, NftRevokeEvent, NftSaleEvent,
+        NftTransferEvent, NftUnlistEvent, PotlockDonationEvent, PotlockPotConfigEvent,
+        PotlockPotDonationEvent, PotlockPotPayoutEvent, PotlockPotProjectDonationEvent,
+        PotlockProjectStatusEvent, SocialCommentEvent, SocialFollowEvent, SocialLikeEvent,
+        SocialPostEvent, TokenPriceEvent, TradeBalanceChangeSwapEvent, TradeLiquidityAddEvent,
+        TradeLiquidityRemoveEvent, TradePoolChangeEvent, TradeRawPoolSwapEvent, TransactionEvent,
+    },
+    redis_reader::{previous_id, save_last_id, Backpressure, EventHandler, Reverts},
+};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use redis::{aio::ConnectionManager, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    AccountCreateHandler, AccountDeleteHandler, ContractDeployHandler, FtBurnHandler,
+    FtMintHandler, FtTransferHandler, LogNep297Handler, NearTransferHandler, NftApproveHandler,
+    NftBurnHandler, NftListHandler, NftMintHandler, NftRevokeHandler, NftSaleHandler,
+    NftTransferHandler, NftUnlistHandler, PotlockDonationHandler, PotlockPotConfigHandler,
+    PotlockPotDonationHandler, PotlockPotPayoutHandler, PotlockPotProjectDonationHandler,
+    PotlockProjectStatusHandler, SocialCommentHandler, SocialFollowHandler, SocialLikeHandler,
+    SocialPostHandler, TokenPriceHandler, TradeBalanceChangeSwapHandler, TradeLiquidityAddHandler,
+    TradeLiquidityRemoveHandler, TradePoolChangeHandler, TradeRawPoolSwapHandler,
+    TransactionHandler,
+};
+
+const MANIFEST_FILE: &str = "manifest.jsonl";
+
+pub struct ParquetConfig {
+    pub directory: PathBuf,
+    /// Maximum number of rows in a row group.
+    pub row_group_size: usize,
+    /// A file is closed when it reaches this many rows, even if the hour isn't over yet.
+    pub max_rows_per_file: usize,
+}
+
+impl ParquetConfig {
+    pub fn from_env(directory: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            directory: PathBuf::from(directory),
+            row_group_size: std::env::var("PARQUET_ROW_GROUP_SIZE")
+                .map(|size| size.parse())
+                .unwrap_or(Ok(10_000))?,
+            max_rows_per_file: std::env::var("PARQUET_MAX_ROWS_PER_FILE")
+                .map(|size| size.parse())
+                .unwrap_or(Ok(1_000_000))?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestEntry {
+    stream: String,
+    file: PathBuf,
+    first_id: String,
+    last_id: String,
+    rows: usize,
+}
+
+/// Position of a stream before its first file was opened.
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestStart {
+    stream: String,
+    start_id: String,
+}
+
+/// Revert of a stream. Open files are finished first, so rows of finished files before
+/// it from blocks after `block_height` are no longer valid.
+#[derive(Debug, Serialize, Deserialize)]
+struct ManifestRevert {
+    stream: String,
+    revert_id: String,
+    block_height: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ManifestLine {
+    File(ManifestEntry),
+    Start(ManifestStart),
+    Revert(ManifestRevert),
+}
+
+/// What backfilling needs to know about the archive.
+pub struct Manifest {
+    /// Last entry ID of each finished file, by path relative to the directory.
+    last_ids: HashMap<PathBuf, String>,
+    reverts: HashMap<String, Reverts>,
+}
+
+impl Manifest {
+    pub fn read(directory: &Path) -> anyhow::Result<Self> {
+        let mut last_ids = HashMap::new();
+        let mut reverts: HashMap<String, Vec<(String, u64)>> = HashMap::new();
+        let manifest_path = directory.join(MANIFEST_FILE);
+        if manifest_path.exists() {
+            for line in BufReader::new(File::open(manifest_path)?).lines() {
+                match serde_json::from_str(&line?)? {
+                    ManifestLine::File(entry) => {
+                        last_ids.insert(entry.file, entry.last_id);
+                    }
+                    ManifestLine::Start(_) => (),
+                    ManifestLine::Revert(revert) => {
+                        reverts
+                            .entry(revert.stream)
+                            .or_default()
+                            .push((revert.revert_id, revert.block_height));
+                    }
+                }
+            }
+        }
+        Ok(Self {
+            last_ids,
+            reverts: reverts
+                .into_iter()
+                .map(|(stream, reverts)| (stream, Reverts::new(reverts)))
+                .collect(),
+        })
+    }
+
+    /// Highest block height of the rows of `file` that no later revert removed. Files that
+    /// weren't finished are named after their first entry ID, and no revert happened
+    /// while they were open.
+    pub fn kept_block_height(&self, stream: &str, file: &Path) -> Option<u64> {
+        let last_id = match self.last_ids.get(file) {
+            Some(last_id) => last_id.clone(),
+            None => file.file_stem()?.to_string_lossy().into_owned(),
+        };
+        self.reverts.get(stream)?.kept_after(&last_id)
+    }
+}
+
+#[derive(Clone)]
+pub struct ParquetSink {
+    config: Arc<ParquetConfig>,
+    files: Arc<Mutex<HashMap<&'static str, OpenFile>>>,
+    /// Streams that have a position in the manifest.
+    started: Arc<Mutex<HashSet<String>>>,
+}
+
+impl ParquetSink {
+    pub fn open(config: ParquetConfig) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&config.directory)?;
+        let sink = Self {
+            config: Arc::new(config),
+            files: Arc::new(Mutex::new(HashMap::new())),
+            started: Arc::new(Mutex::new(HashSet::new())),
+        };
+        *sink.started.lock().unwrap() = sink.committed_ids()?.into_keys().collect();
+        Ok(sink)
+    }
+
+    /// Finishes every open file, for a clean shutdown.
+    pub fn finish_all(&self) -> anyhow::Result<()> {
+        let mut files = self.files.lock().unwrap();
+        for (stream, file) in files.drain() {
+            self.finish(stream, file)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the cursors of `consumer` back to the last entry ID stored in a finished file,
+    /// or to the position before the first file of streams that have none.
+    pub async fn rewind(
+        &self,
+        connection: ConnectionManager,
+        consumer: &str,
+    ) -> anyhow::Result<()> {
+        for (stream_key, last_id) in self.committed_ids()? {
+            log::info!("Rewinding {stream_key} to the last archived ID {last_id}");
+            save_last_id(connection.clone(), consumer, &stream_key, &last_id).await?;
+        }
+        Ok(())
+    }
+
+    fn committed_ids(&self) -> anyhow::Result<HashMap<String, String>> {
+        let manifest_path = self.config.directory.join(MANIFEST_FILE);
+        if !manifest_path.exists() {
+            return Ok(HashMap::new());
+        }
+        let mut committed_ids = HashMap::new();
+        for line in BufReader::new(File::open(manifest_path)?).lines() {
+            match serde_json::from_str(&line?)? {
+                ManifestLine::File(entry) => {
+                    committed_ids.insert(entry.stream, entry.last_id);
+                }
+                ManifestLine::Start(start) => {
+                    committed_ids.entry(start.stream).or_insert(start.start_id);
+                }
+                ManifestLine::Revert(_) => (),
+            }
+        }
+        Ok(committed_ids)
+    }
+
+    fn append_manifest(&self, line: &impl Serialize) -> anyhow::Result<()> {
+        let mut manifest = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.config.directory.join(MANIFEST_FILE))?;
+        writeln!(manifest, "{}", serde_json::to_string(line)?)?;
+        manifest.sync_all()?;
+        Ok(())
+    }
+
+    fn write(
+        &self,
+        stream: &'static str,
+        id: &str,
+        block_timestamp_nanosec: u128,
+        columns: Columns,
+        row: Vec<Cell>,
+    ) -> anyhow::Result<()> {
+        let timestamp = timestamp(block_timestamp_nanosec)
+            .ok_or_else(|| anyhow::anyhow!("Invalid timestamp {block_timestamp_nanosec}"))?;
+        let partition = timestamp.format("date=%Y-%m-%d/hour=%H").to_string();
+
+        let mut files = self.files.lock().unwrap();
+        if let Some(file) = files.get(stream) {
+            if file.partition != partition || file.rows_total() >= self.config.max_rows_per_file {
+                let file = files.remove(stream).unwrap();
+                self.finish(stream, file)?;
+            }
+        }
+        let file = match files.entry(stream) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                if self.started.lock().unwrap().insert(stream.to_string()) {
+                    self.append_manifest(&ManifestStart {
+                        stream: stream.to_string(),
+                        start_id: previous_id(id)?,
+                    })?;
+                }
+                entry.insert(OpenFile::create(
+                    &self.config,
+                    stream,
+                    partition,
+                    id,
+                    columns,
+                )?)
+            }
+        };
+        file.rows.push(row);
+        file.last_id = id.to_string();
+        if file.rows.len() >= self.config.row_group_size {
+            file.flush_rows()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the open file of `stream` and records the revert, so that backfilling
+    /// leaves out the rows it removed.
+    fn revert(&self, stream: &'static str, id: &str, block_height: u64) -> anyhow::Result<()> {
+        if let Some(file) = self.files.lock().unwrap().remove(stream) {
+            self.finish(stream, file)?;
+        }
+        self.append_manifest(&ManifestRevert {
+            stream: stream.to_string(),
+            revert_id: id.to_string(),
+            block_height,
+        })
+    }
+
+    fn finish(&self, stream: &str, mut file: OpenFile) -> anyhow::Result<()> {
+        file.flush_rows()?;
+        let entry = ManifestEntry {
+            stream: stream.to_string(),
+            file: file
+                .path
+                .strip_prefix(&self.config.directory)
+                .unwrap_or(&file.path)
+                .to_path_buf(),
+            first_id: file.first_id,
+            last_id: file.last_id,
+            rows: file.rows_written,
+        };
+        file.writer.close()?;
+
+        self.append_manifest(&entry)?;
+        log::info!(
+            "Finished {} ({} rows, {}..={})",
+            entry.file.display(),
+            entry.rows,
+            entry.first_id,
+            entry.last_id
+        );
+        Ok(())
+    }
+}
+
+struct OpenFile {
+    partition: String,
+    path: PathBuf,
+    schema: SchemaRef,
+    columns: Columns,
+    writer: ArrowWriter<File>,
+    rows: Vec<Vec<Cell>>,
+    rows_written: usize,
+    first_id: String,
+    last_id: String,
+}
+
+impl Backpressure for ParquetSink {}
+
+impl OpenFile {
+    fn create(
+        config: &ParquetConfig,
+        stream: &str,
+        partition: String,
+        first_id: &str,
+        columns: Columns,
+    ) -> anyhow::Result<Self> {
+        let directory = config.directory.join(stream).join(&partition);
+        std::fs::create_dir_all(&directory)?;
+        let path = directory.join(format!("{first_id}.parquet"));
+        let schema = Arc::new(Schema::new(
+            columns
+                .iter()
+                .map(|(name, column_type)| column_type.field(name))
+                .collect::<Vec<_>>(),
+        ));
+        let properties = WriterProperties::builder()
+            .set_max_row_group_size(config.row_group_size)
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(File::create(&path)?, schema.clone(), Some(properties))?;
+        Ok(Self {
+            partition,
+            path,
+            schema,
+            columns,
+            writer,
+            rows: Vec::new(),
+            rows_written: 0,
+            first_id: first_id.to_string(),
+            last_id: first_id.to_string(),
+        })
+    }
+
+    fn rows_total(&self) -> usize {
+        self.rows_written + self.rows.len()
+    }
+
+    fn flush_rows(&mut self) -> anyhow::Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let arrays = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, (name, column_type))| column_type.build(name, index, &self.rows))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), arrays)?)?;
+        self.rows_written += self.rows.len();
+        self.rows.clear();
+        Ok(())
+    }
+}
+
+type Columns = &'static [(&'static str, ColumnType)];
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnType {
+    String,
+    NullableString,
+    Int64,
+    Timestamp,
+    StringList,
+}
+
+impl ColumnType {
+    fn field(&self, name: &str) -> Field {
+        match self {
+            ColumnType::String => Field::new(name, DataType::Utf8, false),
+            ColumnType::NullableString => Field::new(name, DataType::Utf8, true),
+            ColumnType::Int64 => Field::new(name, DataType::Int64, false),
+            ColumnType::Timestamp => Field::new(
+                name,
+                DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into())),
+                false,
+            ),
+            ColumnType::StringList => Field::new(
+                name,
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, true))),
+                false,
+            ),
+        }
+    }
+
+    fn build(&self, name: &str, index: usize, rows: &[Vec<Cell>]) -> anyhow::Result<ArrayRef> {
+        let mismatch = || anyhow::anyhow!("Column {name} doesn't match its type {self:?}");
+        Ok(match self {
+            ColumnType::String | ColumnType::NullableString => Arc::new(
+                rows.iter()
+                    .map(|row| match &row[index] {
+                        Cell::String(value) => Ok(value.as_deref()),
+                        _ => Err(mismatch()),
+                    })
+                    .collect::<anyhow::Result<StringArray>>()?,
+            ),
+            ColumnType::Int64 => Arc::new(
+                rows.iter()
+                    .map(|row| match &row[index] {
+                        Cell::Int64(value) => Ok(Some(*value)),
+                        _ => Err(mismatch()),
+                    })
+                    .collect::<anyhow::Result<Int64Array>>()?,
+            ),
+            ColumnType::Timestamp => Arc::new(
+                rows.iter()
+                    .map(|row| match &row[index] {
+                        Cell::Timestamp(value) => Ok(Some(*value)),
+                        _ => Err(mismatch()),
+                    })
+                    .collect::<anyhow::Result<TimestampNanosecondArray>>()?
+                    .with_timezone("UTC"),
+            ),
+            ColumnType::StringList => {
+                let mut builder = ListBuilder::new(StringBuilder::new());
+                for row in rows {
+                    let Cell::StringList(values) = &row[index] else {
+                        return Err(mismatch());
+                    };
+                    for value in values {
+                        builder.values().append_value(value);
+                    }
+                    builder.append(true);
+                }
+                Arc::new(builder.finish())
+            }
+        })
+    }
+}
+
+enum Cell {
+    String(Option<String>),
+    Int64(i64),
+    Timestamp(i64),
+    StringList(Vec<String>),
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::String(Some(value))
+    }
+}
+
+impl From<Option<String>> for Cell {
+    fn from(value: Option<String>) -> Self {
+        Cell::String(value)
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Int64(value)
+    }
+}
+
+impl From<Vec<String>> for Cell {
+    fn from(value: Vec<String>) -> Self {
+        Cell::StringList(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Cell {
+    fn from(value: DateTime<Utc>) -> Self {
+        Cell::Timestamp(value.timestamp_nanos_opt().unwrap_or_default())
+    }
+}
+
+const NFT_MINT_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("owner_id", ColumnType::String),
+    ("token_ids", ColumnType::StringList),
+    ("memo", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftMintHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftMintEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_mint",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_MINT_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.owner_id.into(),
+                    event.token_ids.into(),
+                    event.memo.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft mint event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_mint", id, block_height)
+    }
+}
+
+const NFT_TRANSFER_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("old_owner_id", ColumnType::String),
+    ("new_owner_id", ColumnType::String),
+    ("token_ids", ColumnType::StringList),
+    ("memo", ColumnType::NullableString),
+    ("token_prices_near", ColumnType::StringList),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftTransferHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftTransferEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_transfer",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_TRANSFER_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.old_owner_id.into(),
+                    event.new_owner_id.into(),
+                    event.token_ids.into(),
+                    event.memo.into(),
+                    event
+                        .token_prices_near
+                        .iter()
+                        .map(|price| price.unwrap_or_default().to_string())
+                        .collect::<Vec<_>>()
+                        .into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_transfer", id, block_height)
+    }
+}
+
+const NFT_BURN_COLUMNS: Columns = NFT_MINT_COLUMNS;
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftBurnHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftBurnEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_burn",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_BURN_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.owner_id.into(),
+                    event.token_ids.into(),
+                    event.memo.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft burn event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_burn", id, block_height)
+    }
+}
+
+const POTLOCK_DONATION_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("donation_id", ColumnType::Int64),
+    ("donor_id", ColumnType::String),
+    ("total_amount", ColumnType::String),
+    ("ft_id", ColumnType::String),
+    ("message", ColumnType::NullableString),
+    ("donated_at", ColumnType::Timestamp),
+    ("project_id", ColumnType::String),
+    ("protocol_fee", ColumnType::String),
+    ("referrer_id", ColumnType::NullableString),
+    ("referrer_fee", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for PotlockDonationHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockDonationEvent>(&values)
+        {
+            parquet_sink.write(
+                "potlock_donation",
+                id,
+                context.block_timestamp_nanosec,
+                POTLOCK_DONATION_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    (event.donation_id as i64).into(),
+                    event.donor_id.into(),
+                    event.total_amount.to_string().into(),
+                    event.ft_id.into(),
+                    event.message.into(),
+                    event.donated_at.into(),
+                    event.project_id.into(),
+                    event.protocol_fee.to_string().into(),
+                    event.referrer_id.into(),
+                    event.referrer_fee.map(|fee| fee.to_string()).into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse potlock donation event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("potlock_donation", id, block_height)
+    }
+}
+
+const POTLOCK_POT_PROJECT_DONATION_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("donation_id", ColumnType::Int64),
+    ("pot_id", ColumnType::String),
+    ("donor_id", ColumnType::String),
+    ("total_amount", ColumnType::String),
+    ("net_amount", ColumnType::String),
+    ("message", ColumnType::NullableString),
+    ("donated_at", ColumnType::Timestamp),
+    ("project_id", ColumnType::String),
+    ("referrer_id", ColumnType::NullableString),
+    ("referrer_fee", ColumnType::NullableString),
+    ("protocol_fee", ColumnType::String),
+    ("chef_id", ColumnType::NullableString),
+    ("chef_fee", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for PotlockPotProjectDonationHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotProjectDonationEvent>(&values)
+        {
+            parquet_sink.write(
+                "potlock_pot_project_donation",
+                id,
+                context.block_timestamp_nanosec,
+                POTLOCK_POT_PROJECT_DONATION_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    (event.donation_id as i64).into(),
+                    event.pot_id.into(),
+                    event.donor_id.into(),
+                    event.total_amount.to_string().into(),
+                    event.net_amount.to_string().into(),
+                    event.message.into(),
+                    event.donated_at.into(),
+                    event.project_id.into(),
+                    event.referrer_id.into(),
+                    event.referrer_fee.map(|fee| fee.to_string()).into(),
+                    event.protocol_fee.to_string().into(),
+                    event.chef_id.into(),
+                    event.chef_fee.map(|fee| fee.to_string()).into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse potlock pot project donation event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("potlock_pot_project_donation", id, block_height)
+    }
+}
+
+const POTLOCK_POT_DONATION_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("donation_id", ColumnType::Int64),
+    ("pot_id", ColumnType::String),
+    ("donor_id", ColumnType::String),
+    ("total_amount", ColumnType::String),
+    ("net_amount", ColumnType::String),
+    ("message", ColumnType::NullableString),
+    ("donated_at", ColumnType::Timestamp),
+    ("referrer_id", ColumnType::NullableString),
+    ("referrer_fee", ColumnType::NullableString),
+    ("protocol_fee", ColumnType::String),
+    ("chef_id", ColumnType::NullableString),
+    ("chef_fee", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for PotlockPotDonationHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotDonationEvent>(&values)
+        {
+            parquet_sink.write(
+                "potlock_pot_donation",
+                id,
+                context.block_timestamp_nanosec,
+                POTLOCK_POT_DONATION_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    (event.donation_id as i64).into(),
+                    event.pot_id.into(),
+                    event.donor_id.into(),
+                    event.total_amount.to_string().into(),
+                    event.net_amount.to_string().into(),
+                    event.message.into(),
+                    event.donated_at.into(),
+                    event.referrer_id.into(),
+                    event.referrer_fee.map(|fee| fee.to_string()).into(),
+                    event.protocol_fee.to_string().into(),
+                    event.chef_id.into(),
+                    event.chef_fee.map(|fee| fee.to_string()).into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse potlock pot donation event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("potlock_pot_donation", id, block_height)
+    }
+}
+
+const TRADE_POOL_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("trader", ColumnType::String),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("pool", ColumnType::String),
+    ("token_in", ColumnType::String),
+    ("token_out", ColumnType::String),
+    ("amount_in", ColumnType::String),
+    ("amount_out", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for TradeRawPoolSwapHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeRawPoolSwapEvent>(&values)
+        {
+            parquet_sink.write(
+                "trade_pool",
+                id,
+                context.block_timestamp_nanosec,
+                TRADE_POOL_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.trader.into(),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.0.pool.into(),
+                    event.0.token_in.into(),
+                    event.0.token_out.into(),
+                    event.0.amount_in.to_string().into(),
+                    event.0.amount_out.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse raw pool swap event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("trade_pool", id, block_height)
+    }
+}
+
+const TRADE_SWAP_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("trader", ColumnType::String),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("balance_changes", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for TradeBalanceChangeSwapHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeBalanceChangeSwapEvent>(&values)
+        {
+            parquet_sink.write(
+                "trade_swap",
+                id,
+                context.block_timestamp_nanosec,
+                TRADE_SWAP_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.trader.into(),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    serde_json::Value::Object(
+                        event
+                            .balance_changes
+                            .into_iter()
+                            .map(|(k, v)| (k, serde_json::Value::String(v.to_string())))
+                            .collect(),
+                    )
+                    .to_string()
+                    .into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse balance change swap event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("trade_swap", id, block_height)
+    }
+}
+
+const TRADE_POOL_CHANGE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("pool_id", ColumnType::String),
+    ("pool", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for TradePoolChangeHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TradePoolChangeEvent>(&values) {
+            parquet_sink.write(
+                "trade_pool_change",
+                id,
+                event.block_timestamp_nanosec,
+                TRADE_POOL_CHANGE_COLUMNS,
+                vec![
+                    Cell::Timestamp(event.block_timestamp_nanosec as i64),
+                    event.receipt_id.into(),
+                    (event.block_height as i64).into(),
+                    event.pool_id.into(),
+                    event.pool.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse pool change event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("trade_pool_change", id, block_height)
+    }
+}
+
+const FT_TRANSFER_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("old_owner_id", ColumnType::String),
+    ("new_owner_id", ColumnType::String),
+    ("amount", ColumnType::String),
+    ("memo", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for FtTransferHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtTransferEvent>(&values)
+        {
+            parquet_sink.write(
+                "ft_transfer",
+                id,
+                context.block_timestamp_nanosec,
+                FT_TRANSFER_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.old_owner_id.into(),
+                    event.new_owner_id.into(),
+                    event.amount.to_string().into(),
+                    event.memo.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse ft transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("ft_transfer", id, block_height)
+    }
+}
+
+const FT_MINT_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("owner_id", ColumnType::String),
+    ("amount", ColumnType::String),
+    ("memo", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for FtMintHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtMintEvent>(&values)
+        {
+            parquet_sink.write(
+                "ft_mint",
+                id,
+                context.block_timestamp_nanosec,
+                FT_MINT_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.owner_id.into(),
+                    event.amount.to_string().into(),
+                    event.memo.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse ft mint event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("ft_mint", id, block_height)
+    }
+}
+
+const FT_BURN_COLUMNS: Columns = FT_MINT_COLUMNS;
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for FtBurnHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtBurnEvent>(&values)
+        {
+            parquet_sink.write(
+                "ft_burn",
+                id,
+                context.block_timestamp_nanosec,
+                FT_BURN_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.owner_id.into(),
+                    event.amount.to_string().into(),
+                    event.memo.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse ft burn event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("ft_burn", id, block_height)
+    }
+}
+
+const NEAR_TRANSFER_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("sender_id", ColumnType::String),
+    ("receiver_id", ColumnType::String),
+    ("amount", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NearTransferHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<NearTransferEvent>(&values) {
+            parquet_sink.write(
+                "near_transfer",
+                id,
+                event.block_timestamp_nanosec,
+                NEAR_TRANSFER_COLUMNS,
+                vec![
+                    Cell::Timestamp(event.block_timestamp_nanosec as i64),
+                    event.transaction_id.into(),
+                    event.receipt_id.into(),
+                    (event.block_height as i64).into(),
+                    event.sender_id.into(),
+                    event.receiver_id.into(),
+                    event.amount.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse near transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("near_transfer", id, block_height)
+    }
+}
+
+const SOCIAL_POST_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("account_id", ColumnType::String),
+    ("path", ColumnType::String),
+    ("text", ColumnType::NullableString),
+    ("value", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for SocialPostHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialPostEvent>(&values)
+        {
+            parquet_sink.write(
+                "social_post",
+                id,
+                context.block_timestamp_nanosec,
+                SOCIAL_POST_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.account_id.into(),
+                    event.path.into(),
+                    event.text.into(),
+                    event.value.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse social post event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("social_post", id, block_height)
+    }
+}
+
+const SOCIAL_COMMENT_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("account_id", ColumnType::String),
+    ("path", ColumnType::String),
+    ("text", ColumnType::NullableString),
+    ("item_author_id", ColumnType::String),
+    ("item_block_height", ColumnType::Int64),
+    ("value", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for SocialCommentHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialCommentEvent>(&values)
+        {
+            parquet_sink.write(
+                "social_comment",
+                id,
+                context.block_timestamp_nanosec,
+                SOCIAL_COMMENT_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.account_id.into(),
+                    event.path.into(),
+                    event.text.into(),
+                    event.item_author_id.into(),
+                    (event.item_block_height as i64).into(),
+                    event.value.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse social comment event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("social_comment", id, block_height)
+    }
+}
+
+const SOCIAL_LIKE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("account_id", ColumnType::String),
+    ("path", ColumnType::String),
+    ("item_author_id", ColumnType::String),
+    ("item_block_height", ColumnType::Int64),
+    ("value", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for SocialLikeHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialLikeEvent>(&values)
+        {
+            parquet_sink.write(
+                "social_like",
+                id,
+                context.block_timestamp_nanosec,
+                SOCIAL_LIKE_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.account_id.into(),
+                    event.path.into(),
+                    event.item_author_id.into(),
+                    (event.item_block_height as i64).into(),
+                    event.value.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse social like event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("social_like", id, block_height)
+    }
+}
+
+const SOCIAL_FOLLOW_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("account_id", ColumnType::String),
+    ("path", ColumnType::String),
+    ("target_id", ColumnType::String),
+    ("follow", ColumnType::String),
+    ("value", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for SocialFollowHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialFollowEvent>(&values)
+        {
+            parquet_sink.write(
+                "social_follow",
+                id,
+                context.block_timestamp_nanosec,
+                SOCIAL_FOLLOW_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.account_id.into(),
+                    event.path.into(),
+                    event.target_id.into(),
+                    event.follow.to_string().into(),
+                    event.value.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse social follow event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("social_follow", id, block_height)
+    }
+}
+
+const LOG_NEP297_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("standard", ColumnType::String),
+    ("version", ColumnType::String),
+    ("event", ColumnType::String),
+    ("data", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for LogNep297Handler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<LogNep297Event>(&values)
+        {
+            if event.is_promoted() {
+                return Ok(());
+            }
+            parquet_sink.write(
+                "log_nep297",
+                id,
+                context.block_timestamp_nanosec,
+                LOG_NEP297_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.standard.into(),
+                    event.version.into(),
+                    event.event.into(),
+                    event.data.map(|value| value.to_string()).into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nep297 log event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("log_nep297", id, block_height)
+    }
+}
+
+const POTLOCK_POT_CONFIG_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("pot_id", ColumnType::String),
+    ("created", ColumnType::String),
+    ("owner_id", ColumnType::String),
+    ("admins", ColumnType::StringList),
+    ("chef_id", ColumnType::NullableString),
+    ("name", ColumnType::String),
+    ("description", ColumnType::NullableString),
+    ("application_start", ColumnType::Timestamp),
+    ("application_end", ColumnType::Timestamp),
+    ("public_round_start", ColumnType::Timestamp),
+    ("public_round_end", ColumnType::Timestamp),
+    ("config", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for PotlockPotConfigHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotConfigEvent>(&values)
+        {
+            parquet_sink.write(
+                "potlock_pot_config",
+                id,
+                context.block_timestamp_nanosec,
+                POTLOCK_POT_CONFIG_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.pot_id.into(),
+                    event.created.to_string().into(),
+                    event.owner_id.into(),
+                    event.admins.into(),
+                    event.chef_id.into(),
+                    event.name.into(),
+                    event.description.into(),
+                    event.application_start.into(),
+                    event.application_end.into(),
+                    event.public_round_start.into(),
+                    event.public_round_end.into(),
+                    event.config.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse potlock pot config event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("potlock_pot_config", id, block_height)
+    }
+}
+
+const POTLOCK_PROJECT_STATUS_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("project_id", ColumnType::String),
+    ("pot_id", ColumnType::NullableString),
+    ("status", ColumnType::String),
+    ("reviewer_id", ColumnType::NullableString),
+    ("review_notes", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for PotlockProjectStatusHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockProjectStatusEvent>(&values)
+        {
+            parquet_sink.write(
+                "potlock_project_status",
+                id,
+                context.block_timestamp_nanosec,
+                POTLOCK_PROJECT_STATUS_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.project_id.into(),
+                    event.pot_id.into(),
+                    event.status.into(),
+                    event.reviewer_id.into(),
+                    event.review_notes.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse potlock project status event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("potlock_project_status", id, block_height)
+    }
+}
+
+const POTLOCK_POT_PAYOUT_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("payout_id", ColumnType::String),
+    ("pot_id", ColumnType::String),
+    ("project_id", ColumnType::String),
+    ("amount", ColumnType::String),
+    ("paid_at", ColumnType::Timestamp),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for PotlockPotPayoutHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotPayoutEvent>(&values)
+        {
+            parquet_sink.write(
+                "potlock_pot_payout",
+                id,
+                context.block_timestamp_nanosec,
+                POTLOCK_POT_PAYOUT_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.payout_id.into(),
+                    event.pot_id.into(),
+                    event.project_id.into(),
+                    event.amount.to_string().into(),
+                    event.paid_at.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse potlock pot payout event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("potlock_pot_payout", id, block_height)
+    }
+}
+
+const NFT_APPROVE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("owner_id", ColumnType::String),
+    ("token_id", ColumnType::String),
+    ("account_id", ColumnType::String),
+    ("approval_id", ColumnType::Int64),
+    ("msg", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftApproveHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftApproveEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_approve",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_APPROVE_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.owner_id.into(),
+                    event.token_id.into(),
+                    event.account_id.into(),
+                    (event.approval_id as i64).into(),
+                    event.msg.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft approve event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_approve", id, block_height)
+    }
+}
+
+const NFT_REVOKE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("owner_id", ColumnType::String),
+    ("token_id", ColumnType::String),
+    ("account_id", ColumnType::NullableString),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftRevokeHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftRevokeEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_revoke",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_REVOKE_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.owner_id.into(),
+                    event.token_id.into(),
+                    event.account_id.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft revoke event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_revoke", id, block_height)
+    }
+}
+
+const NFT_LIST_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("nft_contract_id", ColumnType::String),
+    ("token_id", ColumnType::String),
+    ("seller_id", ColumnType::String),
+    ("price", ColumnType::String),
+    ("ft_id", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftListHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftListEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_list",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_LIST_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.nft_contract_id.into(),
+                    event.token_id.into(),
+                    event.seller_id.into(),
+                    event.price.to_string().into(),
+                    event.ft_id.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft list event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_list", id, block_height)
+    }
+}
+
+const NFT_UNLIST_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("nft_contract_id", ColumnType::String),
+    ("token_id", ColumnType::String),
+    ("seller_id", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftUnlistHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftUnlistEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_unlist",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_UNLIST_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.nft_contract_id.into(),
+                    event.token_id.into(),
+                    event.seller_id.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft unlist event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_unlist", id, block_height)
+    }
+}
+
+const NFT_SALE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("contract_id", ColumnType::String),
+    ("nft_contract_id", ColumnType::String),
+    ("token_id", ColumnType::String),
+    ("seller_id", ColumnType::String),
+    ("buyer_id", ColumnType::String),
+    ("price", ColumnType::String),
+    ("ft_id", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for NftSaleHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftSaleEvent>(&values)
+        {
+            parquet_sink.write(
+                "nft_sale",
+                id,
+                context.block_timestamp_nanosec,
+                NFT_SALE_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    context.contract_id.into(),
+                    event.nft_contract_id.into(),
+                    event.token_id.into(),
+                    event.seller_id.into(),
+                    event.buyer_id.into(),
+                    event.price.to_string().into(),
+                    event.ft_id.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse nft sale event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("nft_sale", id, block_height)
+    }
+}
+
+const TRADE_LIQUIDITY_ADD_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("trader", ColumnType::String),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("pool", ColumnType::String),
+    ("tokens", ColumnType::StringList),
+    ("amounts", ColumnType::StringList),
+    ("shares", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for TradeLiquidityAddHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeLiquidityAddEvent>(&values)
+        {
+            parquet_sink.write(
+                "trade_liquidity_add",
+                id,
+                context.block_timestamp_nanosec,
+                TRADE_LIQUIDITY_ADD_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.trader.into(),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.pool.into(),
+                    event.tokens.into(),
+                    event
+                        .amounts
+                        .iter()
+                        .map(|amount| amount.to_string())
+                        .collect::<Vec<_>>()
+                        .into(),
+                    event.shares.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse liquidity add event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("trade_liquidity_add", id, block_height)
+    }
+}
+
+const TRADE_LIQUIDITY_REMOVE_COLUMNS: Columns = TRADE_LIQUIDITY_ADD_COLUMNS;
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for TradeLiquidityRemoveHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeLiquidityRemoveEvent>(&values)
+        {
+            parquet_sink.write(
+                "trade_liquidity_remove",
+                id,
+                context.block_timestamp_nanosec,
+                TRADE_LIQUIDITY_REMOVE_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.trader.into(),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.pool.into(),
+                    event.tokens.into(),
+                    event
+                        .amounts
+                        .iter()
+                        .map(|amount| amount.to_string())
+                        .collect::<Vec<_>>()
+                        .into(),
+                    event.shares.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse liquidity remove event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("trade_liquidity_remove", id, block_height)
+    }
+}
+
+const ACCOUNT_CREATE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("account_id", ColumnType::String),
+    ("creator_id", ColumnType::String),
+    ("initial_balance", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for AccountCreateHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<AccountCreateEvent>(&values)
+        {
+            parquet_sink.write(
+                "account_create",
+                id,
+                context.block_timestamp_nanosec,
+                ACCOUNT_CREATE_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.account_id.into(),
+                    event.creator_id.into(),
+                    event.initial_balance.to_string().into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse account create event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("account_create", id, block_height)
+    }
+}
+
+const ACCOUNT_DELETE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("account_id", ColumnType::String),
+    ("beneficiary_id", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for AccountDeleteHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<AccountDeleteEvent>(&values)
+        {
+            parquet_sink.write(
+                "account_delete",
+                id,
+                context.block_timestamp_nanosec,
+                ACCOUNT_DELETE_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.account_id.into(),
+                    event.beneficiary_id.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse account delete event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("account_delete", id, block_height)
+    }
+}
+
+const CONTRACT_DEPLOY_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("account_id", ColumnType::String),
+    ("code_hash", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for ContractDeployHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<ContractDeployEvent>(&values)
+        {
+            parquet_sink.write(
+                "contract_deploy",
+                id,
+                context.block_timestamp_nanosec,
+                CONTRACT_DEPLOY_COLUMNS,
+                vec![
+                    Cell::Timestamp(context.block_timestamp_nanosec as i64),
+                    context.transaction_id.into(),
+                    context.receipt_id.into(),
+                    (context.block_height as i64).into(),
+                    event.account_id.into(),
+                    event.code_hash.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse contract deploy event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("contract_deploy", id, block_height)
+    }
+}
+
+const TRANSACTIONS_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("transaction_id", ColumnType::String),
+    ("block_height", ColumnType::Int64),
+    ("block_hash", ColumnType::String),
+    ("signer_id", ColumnType::String),
+    ("receiver_id", ColumnType::String),
+    ("gas_burnt", ColumnType::Int64),
+    ("tokens_burnt", ColumnType::String),
+    ("status", ColumnType::String),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for TransactionHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TransactionEvent>(&values) {
+            parquet_sink.write(
+                "transactions",
+                id,
+                event.block_timestamp_nanosec,
+                TRANSACTIONS_COLUMNS,
+                vec![
+                    Cell::Timestamp(event.block_timestamp_nanosec as i64),
+                    event.transaction_id.into(),
+                    (event.block_height as i64).into(),
+                    event.block_hash.into(),
+                    event.signer_id.into(),
+                    event.receiver_id.into(),
+                    (event.gas_burnt as i64).into(),
+                    event.tokens_burnt.to_string().into(),
+                    event.status.into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse transaction event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("transactions", id, block_height)
+    }
+}
+
+const TOKEN_PRICE_COLUMNS: Columns = &[
+    ("timestamp", ColumnType::Timestamp),
+    ("block_height", ColumnType::Int64),
+    ("token_id", ColumnType::String),
+    ("price_usd", ColumnType::String),
+    ("decimals", ColumnType::Int64),
+    ("extra", ColumnType::NullableString),
+];
+
+#[async_trait::async_trait]
+impl EventHandler<ParquetSink> for TokenPriceHandler {
+    async fn handle(
+        &self,
+        id: &str,
+        values: HashMap<String, Value>,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TokenPriceEvent>(&values) {
+            parquet_sink.write(
+                "token_price",
+                id,
+                event.block_timestamp_nanosec,
+                TOKEN_PRICE_COLUMNS,
+                vec![
+                    Cell::Timestamp(event.block_timestamp_nanosec as i64),
+                    (event.block_height as i64).into(),
+                    event.token_id.into(),
+                    event.price_usd.into(),
+                    (event.decimals as i64).into(),
+                    extra.map(|extra| extra.to_string()).into(),
+                ],
+            )?;
+        } else {
+            log::error!("Failed to parse token price event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        id: &str,
+        block_height: u64,
+        parquet_sink: &ParquetSink,
+    ) -> anyhow::Result<()> {
+        parquet_sink.revert("token_price", id, block_height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sink(name: &str) -> ParquetSink {
+        let directory =
+            std::env::temp_dir().join(format!("parquet_sink_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        ParquetSink::open(ParquetConfig {
+            directory,
+            row_group_size: 10,
+            max_rows_per_file: 2,
+        })
+        .unwrap()
+    }
+
+    fn write_row(sink: &ParquetSink, id: &str) {
+        sink.write(
+            "near_transfer",
+            id,
+            1_700_000_000_000_000_000,
+            &[("receipt_id", ColumnType::String)],
+            vec![id.to_string().into()],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn records_reverts_for_backfilling() {
+        let sink = sink("reverts");
+        write_row(&sink, "10-0");
+        sink.revert("near_transfer", "11-0", 5).unwrap();
+        write_row(&sink, "12-0");
+        sink.revert("near_transfer", "13-0", 8).unwrap();
+        write_row(&sink, "14-0");
+
+        let manifest = Manifest::read(&sink.config.directory).unwrap();
+        let file = |first_id: &str| {
+            PathBuf::from(format!(
+                "near_transfer/date=2023-11-14/hour=22/{first_id}.parquet"
+            ))
+        };
+        // The first file was finished by the first revert, the last one is still open
+        assert_eq!(
+            manifest.kept_block_height("near_transfer", &file("10-0")),
+            Some(5)
+        );
+        assert_eq!(
+            manifest.kept_block_height("near_transfer", &file("12-0")),
+            Some(8)
+        );
+        assert_eq!(
+            manifest.kept_block_height("near_transfer", &file("14-0")),
+            None
+        );
+        assert_eq!(manifest.kept_block_height("nft_mint", &file("10-0")), None);
+    }
+
+    #[test]
+    fn rewinds_to_start_of_first_open_file() {
+        let sink = sink("start");
+        write_row(&sink, "10-0");
+        assert_eq!(
+            sink.committed_ids().unwrap().get("near_transfer").unwrap(),
+            &format!("9-{}", u64::MAX)
+        );
+        // A reopened sink doesn't record the start again
+        let reopened = ParquetSink::open(ParquetConfig {
+            directory: sink.config.directory.clone(),
+            row_group_size: 10,
+            max_rows_per_file: 2,
+        })
+        .unwrap();
+        write_row(&reopened, "10-0");
+        let manifest = std::fs::read_to_string(sink.config.directory.join(MANIFEST_FILE)).unwrap();
+        assert_eq!(manifest.lines().count(), 1);
+    }
+
+    #[test]
+    fn rewinds_to_last_finished_file() {
+        let sink = sink("finished");
+        for id in ["10-0", "10-1", "11-0"] {
+            write_row(&sink, id);
+        }
+        assert_eq!(
+            sink.committed_ids().unwrap().get("near_transfer").unwrap(),
+            "10-1"
+        );
+        sink.finish_all().unwrap();
+        assert_eq!(
+            sink.committed_ids().unwrap().get("near_transfer").unwrap(),
+            "11-0"
+        );
+        assert!(sink.files.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn keeps_unknown_fields_in_extra() {
+        use arrow::array::AsArray;
+        use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+        let sink = sink("extra");
+        let transfer = serde_json::json!({
+            "sender_id": "alice.near",
+            "receiver_id": "bob.near",
+            "amount": "1",
+            "transaction_id": "tx",
+            "receipt_id": "receipt",
+            "block_height": 1,
+            "block_timestamp_nanosec": "1700000000000000000",
+            "memo": "hi",
+        });
+        let values = HashMap::from([(
+            "transfer".to_string(),
+            Value::Data(transfer.to_string().into_bytes()),
+        )]);
+        NearTransferHandler
+            .handle("10-0", values, &sink)
+            .await
+            .unwrap();
+        sink.finish_all().unwrap();
+
+        let path = sink
+            .config
+            .directory
+            .join("near_transfer/date=2023-11-14/hour=22/10-0.parquet");
+        let batch = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let extra = batch.column_by_name("extra").unwrap().as_string::<i32>();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(extra.value(0)).unwrap(),
+            serde_json::json!({ "transfer": { "memo": "hi" } })
+        );
+    }
+}
@@ -0,0 +1,877 @@
+//! Compares the entries of a stream with the rows stored in Postgres. Every entry in the
+//! range is looked up in the table of its stream by its natural key (receipt and token IDs,
+//! donation ID, ...) and the timestamp of its block, and reported as missing, duplicated,
+//! or mismatched if the stored columns differ from the entry. Entries removed by a revert
+//! later in the range and entries dropped by `FILTERS_FILE` aren't expected to be stored.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use events_api_redis_to_db::{
+    events::{
+        decode_entry, timestamp, AccountCreateEvent, AccountDeleteEvent, ContractDeployEvent,
+        Decoded, FtBurnEvent, FtMintEvent, FtTransferEvent, LogNep297Event, NearTransferEvent,
+        NftApproveEvent, NftBurnEvent, NftListEvent, NftMintEvent, NftRevokeEvent, NftSaleEvent,
+        NftTransferEvent, NftUnlistEvent, PotlockDonationEvent, PotlockPotConfigEvent,
+        PotlockPotDonationEvent, PotlockPotPayoutEvent, PotlockPotProjectDonationEvent,
+        PotlockProjectStatusEvent, SocialCommentEvent, SocialFollowEvent, SocialLikeEvent,
+        SocialPostEvent, TokenPriceEvent, TradeBalanceChangeSwapEvent, TradeLiquidityAddEvent,
+        TradeLiquidityRemoveEvent, TradePoolChangeEvent, TradeRawPoolSwapEvent, TransactionEvent,
+    },
+    redis_reader::{block_height_of, parse_revert, range_events, EventHandler, Reverts},
+};
+use redis::{aio::ConnectionManager, Value};
+use sqlx::Row;
+
+use crate::{filters::Filters, handlers, passes_filters};
+
+pub struct ReconcileOptions {
+    pub stream_key: String,
+    pub start: String,
+    pub end: String,
+    pub insert_missing: bool,
+}
+
+impl ReconcileOptions {
+    /// Parses `<stream> [start] [end] [--insert-missing]`. `start` and `end` are stream IDs
+    /// or RFC 3339 timestamps, and default to the whole stream.
+    pub fn from_args(args: &[String]) -> anyhow::Result<Self> {
+        let insert_missing = args.iter().any(|arg| arg == "--insert-missing");
+        let mut args = args.iter().filter(|arg| *arg != "--insert-missing");
+        let Some(stream_key) = args.next() else {
+            anyhow::bail!("Usage: reconcile <stream> [start] [end] [--insert-missing]");
+        };
+        Ok(Self {
+            stream_key: stream_key.clone(),
+            start: range_bound(args.next().map(String::as_str).unwrap_or("-")),
+            end: range_bound(args.next().map(String::as_str).unwrap_or("+")),
+            insert_missing,
+        })
+    }
+}
+
+/// Stream IDs start with the time in milliseconds, so a timestamp can be used as a bound.
+fn range_bound(bound: &str) -> String {
+    match DateTime::parse_from_rfc3339(bound) {
+        Ok(time) => time.timestamp_millis().to_string(),
+        Err(_) => bound.to_string(),
+    }
+}
+
+/// What a stream entry should look like in its table. Columns are SQL expressions that
+/// are compared as text. A key expression with `$` is a condition on its own, with `$` being
+/// the value.
+struct ExpectedRow {
+    timestamp: Option<DateTime<Utc>>,
+    key: Vec<(&'static str, String)>,
+    columns: Vec<(&'static str, String)>,
+}
+
+type ExpectedRowFn = fn(&HashMap<String, Value>) -> anyhow::Result<ExpectedRow>;
+
+#[derive(Debug, Default)]
+struct Report {
+    checked: u64,
+    skipped: u64,
+    missing: u64,
+    inserted: u64,
+    duplicated: u64,
+    mismatched: u64,
+}
+
+pub async fn reconcile(
+    redis_connection: ConnectionManager,
+    pg_pool: sqlx::PgPool,
+    filters: &Filters,
+    options: ReconcileOptions,
+) -> anyhow::Result<()> {
+    let expected_row: ExpectedRowFn = match options.stream_key.as_str() {
+        "nft_mint" => nft_mint,
+        "nft_transfer" => nft_transfer,
+        "nft_burn" => nft_burn,
+        "potlock_donation" => potlock_donation,
+        "potlock_pot_project_donation" => potlock_pot_project_donation,
+        "potlock_pot_donation" => potlock_pot_donation,
+        "trade_pool" => trade_pool,
+        "trade_swap" => trade_swap,
+        "trade_pool_change" => trade_pool_change,
+        "ft_transfer" => ft_transfer,
+        "ft_mint" => ft_mint,
+        "ft_burn" => ft_burn,
+        "near_transfer" => near_transfer,
+        "social_post" => social_post,
+        "social_comment" => social_comment,
+        "social_like" => social_like,
+        "social_follow" => social_follow,
+        "log_nep297" => log_nep297,
+        "potlock_pot_config" => potlock_pot_config,
+        "potlock_project_status" => potlock_project_status,
+        "potlock_pot_payout" => potlock_pot_payout,
+        "nft_approve" => nft_approve,
+        "nft_revoke" => nft_revoke,
+        "nft_list" => nft_list,
+        "nft_unlist" => nft_unlist,
+        "nft_sale" => nft_sale,
+        "trade_liquidity_add" => trade_liquidity_add,
+        "trade_liquidity_remove" => trade_liquidity_remove,
+        "account_create" => account_create,
+        "account_delete" => account_delete,
+        "contract_deploy" => contract_deploy,
+        "transactions" => transactions,
+        "token_price" => token_price,
+        other => anyhow::bail!("Unknown stream {other}"),
+    };
+    // Filtered entries are counted as skipped below rather than passed to the handler
+    let no_filters = Filters::default();
+    let Some((_, handler)) = handlers(&no_filters, true)
+        .into_iter()
+        .find(|(stream_key, _)| *stream_key == options.stream_key)
+    else {
+        anyhow::bail!("Unknown stream {}", options.stream_key);
+    };
+
+    // The range is read twice a page at a time, first for the reverts and then to check
+    // the entries, so that it doesn't have to fit in memory
+    let reverts = read_reverts(&redis_connection, &options).await?;
+    log::info!(
+        "Reconciling {} from {} to {}",
+        options.stream_key,
+        options.start,
+        options.end
+    );
+    let mut report = Report::default();
+    let mut start = options.start.clone();
+    loop {
+        let page = next_page(&redis_connection, &options, &mut start).await?;
+        if page.is_empty() {
+            break;
+        }
+        for (id, values) in page {
+            if values.contains_key("revert") || reverted(&reverts, &id, &values) {
+                continue;
+            }
+            if !passes_filters(filters, &options.stream_key, &values) {
+                report.skipped += 1;
+                continue;
+            }
+            check(
+                &*handler,
+                expected_row,
+                &options,
+                id,
+                values,
+                &pg_pool,
+                &mut report,
+            )
+            .await?;
+        }
+    }
+    log::info!("Reconciled {}: {report:?}", options.stream_key);
+    Ok(())
+}
+
+/// Reads the page of the range after `start`, and moves `start` past it.
+async fn next_page(
+    redis_connection: &ConnectionManager,
+    options: &ReconcileOptions,
+    start: &mut String,
+) -> anyhow::Result<Vec<(String, HashMap<String, Value>)>> {
+    let page = range_events(
+        redis_connection.clone(),
+        &options.stream_key,
+        start,
+        &options.end,
+        1000,
+    )
+    .await?;
+    if let Some((last_id, _)) = page.last() {
+        *start = format!("({last_id}");
+    }
+    Ok(page)
+}
+
+/// Reads the reverts in the range.
+async fn read_reverts(
+    redis_connection: &ConnectionManager,
+    options: &ReconcileOptions,
+) -> anyhow::Result<Reverts> {
+    let mut reverts = Vec::new();
+    let mut start = options.start.clone();
+    loop {
+        let page = next_page(redis_connection, options, &mut start).await?;
+        if page.is_empty() {
+            break;
+        }
+        for (id, values) in page {
+            if let Some(revert) = values.get("revert") {
+                reverts.push((id, parse_revert(revert)?));
+            }
+        }
+    }
+    Ok(Reverts::new(reverts))
+}
+
+/// Whether a revert later in the range removed the entry.
+fn reverted(reverts: &Reverts, id: &str, values: &HashMap<String, Value>) -> bool {
+    reverts
+        .kept_after(id)
+        .is_some_and(|block_height| block_height_of(values).unwrap_or_default() > block_height)
+}
+
+async fn check(
+    handler: &dyn EventHandler,
+    expected_row: ExpectedRowFn,
+    options: &ReconcileOptions,
+    id: String,
+    values: HashMap<String, Value>,
+    pg_pool: &sqlx::PgPool,
+    report: &mut Report,
+) -> anyhow::Result<()> {
+    report.checked += 1;
+    let expected = match expected_row(&values) {
+        Ok(expected) => expected,
+        Err(err) => {
+            // The handler skips entries it can't parse or doesn't store
+            log::warn!("Skipping entry {id}: {err:?}");
+            report.skipped += 1;
+            return Ok(());
+        }
+    };
+
+    let mut sql = format!(
+        "SELECT {} FROM {} WHERE timestamp = $1",
+        expected
+            .columns
+            .iter()
+            .map(|(column, _)| format!("({column})::TEXT"))
+            .collect::<Vec<_>>()
+            .join(", "),
+        options.stream_key
+    );
+    for (i, (column, _)) in expected.key.iter().enumerate() {
+        let parameter = format!("${}", i + 2);
+        if column.contains('$') {
+            sql.push_str(&format!(" AND {}", column.replace('$', &parameter)));
+        } else {
+            sql.push_str(&format!(" AND ({column})::TEXT = {parameter}"));
+        }
+    }
+    let mut query = sqlx::query(&sql).bind(expected.timestamp);
+    for (_, value) in &expected.key {
+        query = query.bind(value);
+    }
+    let rows = query.fetch_all(pg_pool).await?;
+
+    match rows.as_slice() {
+        [] => {
+            report.missing += 1;
+            log::warn!("Missing {} row for entry {id}", options.stream_key);
+            if options.insert_missing {
+                handler.handle(&id, values, pg_pool).await?;
+                report.inserted += 1;
+            }
+        }
+        [row] => {
+            for (i, (column, value)) in expected.columns.iter().enumerate() {
+                let stored: Option<String> = row.try_get(i)?;
+                if stored.as_ref() != Some(value) {
+                    report.mismatched += 1;
+                    log::warn!(
+                        "Mismatched {} row for entry {id}: {column} is {stored:?}, expected {value:?}",
+                        options.stream_key
+                    );
+                    break;
+                }
+            }
+        }
+        rows => {
+            report.duplicated += 1;
+            log::warn!("{} {} rows for entry {id}", rows.len(), options.stream_key);
+        }
+    }
+    Ok(())
+}
+
+fn nft_mint(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftMintEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("array_to_string(token_ids, ',')", event.token_ids.join(",")),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("owner_id", event.owner_id),
+        ],
+    })
+}
+
+fn nft_transfer(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftTransferEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("array_to_string(token_ids, ',')", event.token_ids.join(",")),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("old_owner_id", event.old_owner_id),
+            ("new_owner_id", event.new_owner_id),
+        ],
+    })
+}
+
+fn nft_burn(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftBurnEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("array_to_string(token_ids, ',')", event.token_ids.join(",")),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("owner_id", event.owner_id),
+        ],
+    })
+}
+
+fn potlock_donation(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<PotlockDonationEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![("donation_id", event.donation_id.to_string())],
+        columns: vec![
+            ("receipt_id", context.receipt_id),
+            ("block_height", context.block_height.to_string()),
+            ("donor_id", event.donor_id),
+            ("project_id", event.project_id),
+            ("total_amount", event.total_amount.to_string()),
+        ],
+    })
+}
+
+fn potlock_pot_project_donation(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<PotlockPotProjectDonationEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("pot_id", event.pot_id),
+            ("donation_id", event.donation_id.to_string()),
+        ],
+        columns: vec![
+            ("receipt_id", context.receipt_id),
+            ("block_height", context.block_height.to_string()),
+            ("donor_id", event.donor_id),
+            ("project_id", event.project_id),
+            ("total_amount", event.total_amount.to_string()),
+            ("net_amount", event.net_amount.to_string()),
+        ],
+    })
+}
+
+fn potlock_pot_donation(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<PotlockPotDonationEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("pot_id", event.pot_id),
+            ("donation_id", event.donation_id.to_string()),
+        ],
+        columns: vec![
+            ("receipt_id", context.receipt_id),
+            ("block_height", context.block_height.to_string()),
+            ("donor_id", event.donor_id),
+            ("total_amount", event.total_amount.to_string()),
+            ("net_amount", event.net_amount.to_string()),
+        ],
+    })
+}
+
+fn trade_pool(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded {
+        context,
+        event: TradeRawPoolSwapEvent(event),
+        ..
+    } = decode_entry::<TradeRawPoolSwapEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![("receipt_id", context.receipt_id), ("pool", event.pool)],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("trader", context.trader),
+            ("token_in", event.token_in),
+            ("token_out", event.token_out),
+            ("amount_in", event.amount_in.to_string()),
+            ("amount_out", event.amount_out.to_string()),
+        ],
+    })
+}
+
+fn trade_swap(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    // The event is parsed only to skip the entries the handler doesn't store
+    let Decoded { context, .. } = decode_entry::<TradeBalanceChangeSwapEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("trader", context.trader),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("transaction_id", context.transaction_id),
+        ],
+    })
+}
+
+fn trade_pool_change(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { event, .. } = decode_entry::<TradePoolChangeEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(event.block_timestamp_nanosec),
+        key: vec![("receipt_id", event.receipt_id), ("pool_id", event.pool_id)],
+        columns: vec![("block_height", event.block_height.to_string())],
+    })
+}
+
+fn ft_transfer(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<FtTransferEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("old_owner_id", event.old_owner_id),
+            ("new_owner_id", event.new_owner_id),
+            ("amount", event.amount.to_string()),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+        ],
+    })
+}
+
+fn ft_mint(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<FtMintEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("owner_id", event.owner_id),
+            ("amount", event.amount.to_string()),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+        ],
+    })
+}
+
+fn ft_burn(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<FtBurnEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("owner_id", event.owner_id),
+            ("amount", event.amount.to_string()),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+        ],
+    })
+}
+
+fn near_transfer(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { event, .. } = decode_entry::<NearTransferEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(event.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", event.receipt_id),
+            ("sender_id", event.sender_id),
+            ("receiver_id", event.receiver_id),
+            ("amount", event.amount.to_string()),
+        ],
+        columns: vec![
+            ("block_height", event.block_height.to_string()),
+            ("transaction_id", event.transaction_id),
+        ],
+    })
+}
+
+fn social_post(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<SocialPostEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("account_id", event.account_id),
+            ("path", event.path),
+        ],
+        columns: vec![("block_height", context.block_height.to_string())],
+    })
+}
+
+fn social_comment(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<SocialCommentEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("account_id", event.account_id),
+            ("path", event.path),
+            ("item_author_id", event.item_author_id),
+            ("item_block_height", event.item_block_height.to_string()),
+        ],
+        columns: vec![("block_height", context.block_height.to_string())],
+    })
+}
+
+fn social_like(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<SocialLikeEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("account_id", event.account_id),
+            ("path", event.path),
+            ("item_author_id", event.item_author_id),
+            ("item_block_height", event.item_block_height.to_string()),
+        ],
+        columns: vec![("block_height", context.block_height.to_string())],
+    })
+}
+
+fn social_follow(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<SocialFollowEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("account_id", event.account_id),
+            ("path", event.path),
+            ("target_id", event.target_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("follow", event.follow.to_string()),
+        ],
+    })
+}
+
+fn log_nep297(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<LogNep297Event>(values)?;
+    anyhow::ensure!(
+        !event.is_promoted(),
+        "{} logs are stored in their own tables",
+        event.standard
+    );
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("standard", event.standard),
+            ("event", event.event),
+            // Compared as JSONB, since Postgres formats it differently
+            (
+                "data IS NOT DISTINCT FROM NULLIF($::JSONB, 'null')",
+                event.data.unwrap_or_default().to_string(),
+            ),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("version", event.version),
+        ],
+    })
+}
+
+fn potlock_pot_config(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<PotlockPotConfigEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![("receipt_id", context.receipt_id), ("pot_id", event.pot_id)],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("created", event.created.to_string()),
+            ("owner_id", event.owner_id),
+            ("name", event.name),
+        ],
+    })
+}
+
+fn potlock_project_status(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<PotlockProjectStatusEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("project_id", event.project_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("pot_id", event.pot_id.unwrap_or_default()),
+            ("status", event.status),
+        ],
+    })
+}
+
+fn potlock_pot_payout(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<PotlockPotPayoutEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![("pot_id", event.pot_id), ("payout_id", event.payout_id)],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("project_id", event.project_id),
+            ("amount", event.amount.to_string()),
+        ],
+    })
+}
+
+fn nft_approve(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftApproveEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("token_id", event.token_id),
+            ("account_id", event.account_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("owner_id", event.owner_id),
+            ("approval_id", event.approval_id.to_string()),
+        ],
+    })
+}
+
+fn nft_revoke(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftRevokeEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("token_id", event.token_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("owner_id", event.owner_id),
+            ("account_id", event.account_id.unwrap_or_default()),
+        ],
+    })
+}
+
+fn nft_list(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftListEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("nft_contract_id", event.nft_contract_id),
+            ("token_id", event.token_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("seller_id", event.seller_id),
+            ("price", event.price.to_string()),
+            ("ft_id", event.ft_id),
+        ],
+    })
+}
+
+fn nft_unlist(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftUnlistEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("nft_contract_id", event.nft_contract_id),
+            ("token_id", event.token_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("seller_id", event.seller_id),
+        ],
+    })
+}
+
+fn nft_sale(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<NftSaleEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("nft_contract_id", event.nft_contract_id),
+            ("token_id", event.token_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("contract_id", context.contract_id),
+            ("seller_id", event.seller_id),
+            ("buyer_id", event.buyer_id),
+            ("price", event.price.to_string()),
+        ],
+    })
+}
+
+fn trade_liquidity_add(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<TradeLiquidityAddEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![("receipt_id", context.receipt_id), ("pool", event.pool)],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("trader", context.trader),
+            ("shares", event.shares.to_string()),
+        ],
+    })
+}
+
+fn trade_liquidity_remove(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<TradeLiquidityRemoveEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![("receipt_id", context.receipt_id), ("pool", event.pool)],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("trader", context.trader),
+            ("shares", event.shares.to_string()),
+        ],
+    })
+}
+
+fn account_create(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<AccountCreateEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("account_id", event.account_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("creator_id", event.creator_id),
+            ("initial_balance", event.initial_balance.to_string()),
+        ],
+    })
+}
+
+fn account_delete(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<AccountDeleteEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("account_id", event.account_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("beneficiary_id", event.beneficiary_id),
+        ],
+    })
+}
+
+fn contract_deploy(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { context, event, .. } = decode_entry::<ContractDeployEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(context.block_timestamp_nanosec),
+        key: vec![
+            ("receipt_id", context.receipt_id),
+            ("account_id", event.account_id),
+        ],
+        columns: vec![
+            ("block_height", context.block_height.to_string()),
+            ("code_hash", event.code_hash),
+        ],
+    })
+}
+
+fn transactions(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { event, .. } = decode_entry::<TransactionEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(event.block_timestamp_nanosec),
+        key: vec![("transaction_id", event.transaction_id)],
+        columns: vec![
+            ("block_height", event.block_height.to_string()),
+            ("signer_id", event.signer_id),
+            ("receiver_id", event.receiver_id),
+            ("gas_burnt", event.gas_burnt.to_string()),
+            ("status", event.status),
+        ],
+    })
+}
+
+fn token_price(values: &HashMap<String, Value>) -> anyhow::Result<ExpectedRow> {
+    let Decoded { event, .. } = decode_entry::<TokenPriceEvent>(values)?;
+    Ok(ExpectedRow {
+        timestamp: timestamp(event.block_timestamp_nanosec),
+        key: vec![("token_id", event.token_id)],
+        columns: vec![
+            ("block_height", event.block_height.to_string()),
+            ("price_usd", event.price_usd),
+            ("decimals", event.decimals.to_string()),
+        ],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(block_height: u64) -> HashMap<String, Value> {
+        HashMap::from([(
+            "event".to_string(),
+            Value::Data(format!(r#"{{"block_height":{block_height}}}"#).into_bytes()),
+        )])
+    }
+
+    #[test]
+    fn later_reverts_remove_entries() {
+        let reverts = Reverts::new([("20-0".to_string(), 8), ("10-0".to_string(), 5)]);
+        assert!(reverted(&reverts, "5-0", &entry(6)));
+        assert!(!reverted(&reverts, "5-0", &entry(5)));
+        assert!(!reverted(&reverts, "15-0", &entry(6)));
+        assert!(reverted(&reverts, "15-0", &entry(9)));
+        assert!(!reverted(&reverts, "25-0", &entry(100)));
+        assert!(!reverted(&Reverts::default(), "5-0", &entry(6)));
+    }
+
+    fn like(item_author_id: &str, item_block_height: u64) -> HashMap<String, Value> {
+        let context = r#"{"transaction_id":"tx","receipt_id":"receipt","block_height":10,"block_timestamp_nanosec":"0"}"#;
+        let like = format!(
+            r#"{{"account_id":"alice.near","path":"alice.near/index/like","item_author_id":"{item_author_id}","item_block_height":{item_block_height},"value":{{}}}}"#
+        );
+        HashMap::from([
+            (
+                "context".to_string(),
+                Value::Data(context.as_bytes().to_vec()),
+            ),
+            ("like".to_string(), Value::Data(like.into_bytes())),
+        ])
+    }
+
+    #[test]
+    fn likes_of_one_receipt_have_distinct_keys() {
+        let keys = [
+            like("bob.near", 1),
+            like("bob.near", 2),
+            like("carol.near", 1),
+        ]
+        .iter()
+        .map(|values| social_like(values).unwrap().key)
+        .collect::<Vec<_>>();
+        assert_ne!(keys[0], keys[1]);
+        assert_ne!(keys[0], keys[2]);
+        assert_ne!(keys[1], keys[2]);
+    }
+}
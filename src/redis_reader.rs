@@ -1,6 +1,16 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    hash::{DefaultHasher, Hash, Hasher},
+    time::{Duration, Instant},
+};
 
-use redis::{aio::ConnectionManager, Value};
+use redis::{
+    aio::{ConnectionLike, ConnectionManager},
+    FromRedisValue, Value,
+};
+use serde::Deserialize;
+
+use crate::events::schema_version;
 
 pub async fn create_connection(connection_url: &str) -> ConnectionManager {
     let redis_client = redis::Client::open(connection_url).expect("Failed to create redis client");
@@ -9,62 +19,952 @@ pub async fn create_connection(connection_url: &str) -> ConnectionManager {
         .expect("Failed to create redis connection")
 }
 
-pub async fn stream_events(
-    stream_key: &str,
-    handler: impl EventHandler,
+/// Overwrites the saved position of `consumer` in `stream_key`, so that the next
+/// `stream_events` call resumes after `last_id`.
+pub async fn save_last_id(
     connection: ConnectionManager,
-    pg_pool: sqlx::PgPool,
-) {
-    let save_key = &format!("events_api_server_last_id_{stream_key}");
+    consumer: &str,
+    stream_key: &str,
+    last_id: &str,
+) -> redis::RedisResult<()> {
     let mut db = redis_db::RedisDB::new(connection).await;
-    let mut last_id = db.get(save_key).await.unwrap_or("$".to_string());
-    log::info!("Last ID for {stream_key}: {last_id}");
+    db.set(&last_id_key(consumer, stream_key), last_id).await?;
+    Ok(())
+}
+
+/// Hash of how many entries of each `schema_version` the consumer has handled.
+fn schema_versions_key(consumer: &str, stream_key: &str) -> String {
+    format!("{consumer}_schema_versions_{stream_key}")
+}
+
+fn last_id_key(consumer: &str, stream_key: &str) -> String {
+    format!("{consumer}_last_id_{stream_key}")
+}
+
+/// Block height of the entry at the saved position, to check the order of the next one
+/// after a restart.
+fn last_block_key(consumer: &str, stream_key: &str) -> String {
+    format!("{consumer}_last_block_{stream_key}")
+}
+
+/// Entries after the saved position that were already handled.
+fn handled_key(consumer: &str, stream_key: &str) -> String {
+    format!("{consumer}_handled_{stream_key}")
+}
+
+fn pause_key(consumer: &str, stream_key: &str) -> String {
+    format!("{consumer}_paused_{stream_key}")
+}
+
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// Maximum number of entries per `XREAD`. The count grows towards it while the stream
+    /// is behind and entries are handled quickly enough.
+    pub read_count: usize,
+    /// Minimum number of entries per `XREAD`.
+    pub min_read_count: usize,
+    /// The count shrinks when handling a batch takes longer than this.
+    pub target_batch_duration: Duration,
+    /// How long `XREAD` waits for new entries.
+    pub block_timeout: Duration,
+    /// Read all streams with one `XREAD` using `stream_events_multiplexed` instead of a
+    /// `stream_events` loop per stream.
+    pub multiplex: bool,
+    /// Number of entries of a stream handled at the same time.
+    pub workers: usize,
+    /// Field that entries of a stream are partitioned by when there's more than one worker.
+    /// Streams without one are handled sequentially.
+    pub partition_keys: HashMap<String, String>,
+    /// Entries are held back until the stream has an entry this many blocks newer, so that
+    /// reverts of recent blocks can be applied before anything is stored.
+    pub finality_blocks: u64,
+    /// Consecutive entries of a stream more than this many blocks apart are reported as a
+    /// gap. Streams without one aren't checked for gaps.
+    pub max_block_gaps: HashMap<String, u64>,
+    /// When an entry goes back in block height or makes a gap, stop until the operator
+    /// deletes the `{consumer}_paused_{stream_key}` key. A stream that is still paused
+    /// when it starts waits too. Other streams read with `multiplex` keep going meanwhile.
+    pub pause_on_anomaly: bool,
+    /// Where to start streams that have no saved position yet, `Latest` if not set.
+    pub start_positions: HashMap<String, StartPosition>,
+    /// Trim streams after saving the position, if set.
+    pub trim: Option<TrimOptions>,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            read_count: 100,
+            min_read_count: 1,
+            target_batch_duration: Duration::from_secs(1),
+            block_timeout: Duration::from_millis(250),
+            multiplex: false,
+            workers: 1,
+            partition_keys: HashMap::new(),
+            finality_blocks: 0,
+            max_block_gaps: HashMap::new(),
+            pause_on_anomaly: false,
+            start_positions: HashMap::new(),
+            trim: None,
+        }
+    }
+}
 
-    'outer: loop {
+#[derive(Debug, Clone, Default)]
+pub struct TrimOptions {
+    /// Other consumers reading the same streams. Entries are only trimmed once all of them
+    /// have saved a position past them.
+    pub consumers: Vec<String>,
+    /// Number of entries to keep before the lowest saved position.
+    pub margin_entries: u64,
+    /// Entries added this long before the lowest saved position are kept too.
+    pub margin: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub enum StartPosition {
+    /// Only entries added after the stream starts being read.
+    #[default]
+    Latest,
+    /// Everything in the stream.
+    Earliest,
+    /// Entries after this ID.
+    Id(String),
+    /// Entries added from this time, in milliseconds.
+    Timestamp(u64),
+    /// Entries from blocks after this one.
+    AfterBlock(u64),
+}
+
+pub async fn stream_events<Db: Backpressure>(
+    consumer: &str,
+    stream_key: &str,
+    handler: impl EventHandler<Db>,
+    connection: impl ConnectionLike + Send + Sync + 'static,
+    database: Db,
+    options: &StreamOptions,
+) {
+    let mut db = redis_db::RedisDB::new(connection).await;
+    let Some(mut reader) = StreamReader::new(&mut db, consumer, stream_key, options).await else {
+        return;
+    };
+    reader.wait_while_paused(&mut db).await;
+    loop {
+        wait_for_capacity(stream_key, &database).await;
         let entries = db
-            .xread(100, stream_key, &last_id) // will fetch up to 100 if running behind, or wait for the next 1 if not
+            .xread(
+                reader.read_count, // will fetch up to read_count if running behind, or wait for the next 1 if not
+                options.block_timeout,
+                stream_key,
+                &reader.read_id,
+            )
             .await
             .expect("Failed to read redis stream");
-        for (id, data) in entries {
-            if let Err(err) = handler.handle(data, &pg_pool).await {
-                log::error!("Failed to handle event {id}: {err:?}");
+        let processed = reader.process(&mut db, entries, &handler, &database).await;
+        // Saved when stopping too, so that what was handled isn't handled again
+        reader.save(&mut db).await;
+        match processed {
+            Processed::Running => (),
+            Processed::Paused => reader.wait_while_paused(&mut db).await,
+            Processed::Stopped => break,
+        }
+    }
+}
+
+/// Reads several streams with one `XREAD`, so that there's a single polling loop. Each
+/// stream keeps its own position, a stream whose handler fails stops alone, and a paused
+/// stream is left out of the reads until it's resumed.
+pub async fn stream_events_multiplexed<Db: Backpressure>(
+    consumer: &str,
+    handlers: Vec<(&str, Box<dyn EventHandler<Db> + '_>)>,
+    connection: impl ConnectionLike + Send + Sync + 'static,
+    database: Db,
+    options: &StreamOptions,
+) {
+    let mut db = redis_db::RedisDB::new(connection).await;
+    let mut streams = Vec::new();
+    for (stream_key, handler) in handlers {
+        if let Some(reader) = StreamReader::new(&mut db, consumer, stream_key, options).await {
+            streams.push((reader, handler));
+        }
+    }
+    while !streams.is_empty() {
+        wait_for_capacity(consumer, &database).await;
+        for (reader, _) in streams.iter_mut() {
+            reader.is_paused(&mut db).await;
+        }
+        let read_count = streams
+            .iter()
+            .filter(|(reader, _)| !reader.paused)
+            .map(|(reader, _)| reader.read_count)
+            .max()
+            .unwrap_or(options.read_count);
+        let read_ids = streams
+            .iter()
+            .filter(|(reader, _)| !reader.paused)
+            .map(|(reader, _)| (reader.stream_key, reader.read_id.as_str()))
+            .collect::<Vec<_>>();
+        if read_ids.is_empty() {
+            tokio::time::sleep(PAUSE_CHECK_INTERVAL).await;
+            continue;
+        }
+        let mut entries = db
+            .xread_streams(read_count, options.block_timeout, &read_ids)
+            .await
+            .expect("Failed to read redis streams");
+        let mut stopped = Vec::new();
+        for (i, (reader, handler)) in streams.iter_mut().enumerate() {
+            if reader.paused {
+                continue;
+            }
+            let Some(entries) = entries.remove(reader.stream_key) else {
+                // Nothing new, so its count shrinks and stops holding up the shared one
+                reader.adapt_read_count(0, Duration::ZERO);
+                continue;
+            };
+            let processed = reader
+                .process(&mut db, entries, &**handler, &database)
+                .await;
+            reader.save(&mut db).await;
+            if processed == Processed::Stopped {
+                stopped.push(i);
+            }
+        }
+        for i in stopped.into_iter().rev() {
+            streams.remove(i);
+        }
+    }
+}
+
+/// How often a paused stream checks whether it was resumed.
+const PAUSE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// What a stream does after a batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Processed {
+    Running,
+    /// Paused at an anomaly. The entries from it on are read again once it's resumed.
+    Paused,
+    Stopped,
+}
+
+/// Position and finality state of one stream.
+struct StreamReader<'a> {
+    consumer: &'a str,
+    stream_key: &'a str,
+    save_key: String,
+    options: &'a StreamOptions,
+    last_id: String,
+    read_id: String,
+    /// Entries that were read but aren't final yet. `last_id` only moves past handled
+    /// entries, so the pending ones are read again after a restart.
+    pending: VecDeque<(String, HashMap<String, Value>, u64)>,
+    max_block_height: u64,
+    /// Block height of the last entry read.
+    last_block_height: Option<u64>,
+    /// Block height of the entry at `last_id`.
+    last_id_block_height: Option<u64>,
+    /// Entry that the stream was paused at. The operator lets it through by resuming, so
+    /// it isn't checked again.
+    resumed_id: Option<String>,
+    /// Waiting for the operator to delete the pause key.
+    paused: bool,
+    /// Entries after `last_id` that were handled by one worker while another one failed.
+    /// They're skipped when they're read again.
+    handled: HashSet<String>,
+    handled_changed: bool,
+    anomalies: u64,
+    read_count: usize,
+    /// Entries handled per schema version since the last save.
+    schema_versions: HashMap<String, u64>,
+}
+
+impl<'a> StreamReader<'a> {
+    /// Loads the saved position, or finds the start position. Returns `None` if that
+    /// failed, the stream can't be read then.
+    async fn new(
+        db: &mut redis_db::RedisDB,
+        consumer: &'a str,
+        stream_key: &'a str,
+        options: &'a StreamOptions,
+    ) -> Option<Self> {
+        let save_key = last_id_key(consumer, stream_key);
+        let last_id = match db.get(&save_key).await {
+            // Saved by older versions before anything was read
+            Ok(last_id) if last_id == "$" => match latest_id(db, stream_key).await {
+                Ok(latest_id) => latest_id,
+                Err(err) => {
+                    log::error!("Failed to find the latest entry of {stream_key}: {err:?}");
+                    log::error!("Stopped reading events from {stream_key}");
+                    return None;
+                }
+            },
+            Ok(last_id) => last_id,
+            Err(_) => {
+                let start_position = options
+                    .start_positions
+                    .get(stream_key)
+                    .cloned()
+                    .unwrap_or_default();
+                log::info!("No last ID for {stream_key}, starting from {start_position:?}");
+                match start_id(db, stream_key, start_position).await {
+                    Ok(start_id) => start_id,
+                    Err(err) => {
+                        log::error!("Failed to find start position of {stream_key}: {err:?}");
+                        log::error!("Stopped reading events from {stream_key}");
+                        return None;
+                    }
+                }
+            }
+        };
+        log::info!("Last ID for {stream_key}: {last_id}");
+        let last_block_height = db
+            .get(&last_block_key(consumer, stream_key))
+            .await
+            .ok()
+            .and_then(|block_height| block_height.parse().ok());
+        let pause_key = pause_key(consumer, stream_key);
+        let (resumed_id, paused) = match db.get(&pause_key).await {
+            Ok(reason) => {
+                log::warn!("{stream_key} is paused at {reason}, delete {pause_key} to continue");
+                (reason.split_once(':').map(|(id, _)| id.to_string()), true)
+            }
+            Err(_) => (None, false),
+        };
+        let handled_key = handled_key(consumer, stream_key);
+        let handled = match db.smembers(&handled_key).await {
+            Ok(handled) => handled,
+            Err(err) => {
+                log::error!("Failed to read {handled_key}: {err:?}");
                 log::error!("Stopped reading events from {stream_key}");
-                break 'outer;
+                return None;
+            }
+        };
+        let versions_key = schema_versions_key(consumer, stream_key);
+        match db.hgetall(&versions_key).await {
+            Ok(versions) if !versions.is_empty() => {
+                let mut versions = versions.into_iter().collect::<Vec<_>>();
+                versions.sort();
+                log::info!("Schema versions seen in {stream_key}: {versions:?}");
+            }
+            Ok(_) => (),
+            Err(err) => log::warn!("Failed to read {versions_key}: {err:?}"),
+        }
+        Some(Self {
+            consumer,
+            stream_key,
+            save_key,
+            options,
+            read_id: last_id.clone(),
+            last_id,
+            pending: VecDeque::new(),
+            max_block_height: 0,
+            last_block_height,
+            last_id_block_height: last_block_height,
+            resumed_id,
+            paused,
+            handled,
+            handled_changed: false,
+            anomalies: 0,
+            // Start big in case the stream is behind, it shrinks quickly if it's not
+            read_count: options.read_count,
+            schema_versions: HashMap::new(),
+        })
+    }
+
+    /// Handles the entries that became final, up to an anomaly that pauses the stream.
+    async fn process<Db: Sync, H: EventHandler<Db> + ?Sized>(
+        &mut self,
+        db: &mut redis_db::RedisDB,
+        entries: Vec<(String, HashMap<String, Value>)>,
+        handler: &H,
+        database: &Db,
+    ) -> Processed {
+        let stream_key = self.stream_key;
+        let started = Instant::now();
+        let read = entries.len();
+        let mut ready = Vec::new();
+        for (id, data) in entries {
+            let previous_read_id = std::mem::replace(&mut self.read_id, id.clone());
+            if let Some(revert) = data.get("revert") {
+                // Entries before the revert are stored first, so that it can remove them
+                if !self.handle_ready(&mut ready, handler, database).await {
+                    return Processed::Stopped;
+                }
+                let block_height = match parse_revert(revert) {
+                    Ok(block_height) => block_height,
+                    Err(err) => {
+                        log::error!("Failed to parse revert {id}: {err:?}");
+                        log::error!("Stopped reading events from {stream_key}");
+                        return Processed::Stopped;
+                    }
+                };
+                log::warn!("Reverting {stream_key} to block {block_height}");
+                self.pending
+                    .retain(|(_, _, height)| *height <= block_height);
+                self.max_block_height = self.max_block_height.min(block_height);
+                self.last_block_height = Some(block_height);
+                if let Err(err) = handler.revert(&id, block_height, database).await {
+                    log::error!("Failed to revert {stream_key} to block {block_height}: {err:?}");
+                    log::error!("Stopped reading events from {stream_key}");
+                    return Processed::Stopped;
+                }
+                if self.pending.is_empty() {
+                    self.last_id = id;
+                    self.last_id_block_height = Some(block_height);
+                }
+                continue;
+            }
+
+            let block_height = block_height_of(&data);
+            let max_block_gap = self.options.max_block_gaps.get(stream_key).copied();
+            let anomaly = match self.resumed_id.take() {
+                Some(resumed_id) if resumed_id == id => None,
+                _ => check_order(self.last_block_height, block_height, max_block_gap),
+            };
+            if let Some(anomaly) = anomaly {
+                self.anomalies += 1;
+                log::warn!(
+                    "{stream_key} entry {id}: {anomaly} ({} anomalies so far)",
+                    self.anomalies
+                );
+                if self.options.pause_on_anomaly {
+                    // Entries before it are stored, it and the rest are read again once
+                    // the operator resumes the stream
+                    if !self.handle_ready(&mut ready, handler, database).await {
+                        return Processed::Stopped;
+                    }
+                    self.read_id = previous_read_id;
+                    self.resumed_id = Some(id.clone());
+                    self.paused = true;
+                    self.save_position(db).await;
+                    let pause_key = pause_key(self.consumer, stream_key);
+                    db.set(&pause_key, &format!("{id}: {anomaly}"))
+                        .await
+                        .expect("Failed to set pause key");
+                    log::warn!("Paused {stream_key}, delete {pause_key} to continue");
+                    return Processed::Paused;
+                }
+            }
+            let block_height = match block_height {
+                Some(block_height) => block_height,
+                None => {
+                    // Held with the entry before it, so that it isn't handled early
+                    let block_height = self.last_block_height.unwrap_or(self.max_block_height);
+                    log::warn!(
+                        "{stream_key} entry {id} has no block height, holding it at {block_height}"
+                    );
+                    block_height
+                }
+            };
+            self.last_block_height = Some(block_height);
+            self.max_block_height = self.max_block_height.max(block_height);
+            self.pending.push_back((id, data, block_height));
+            while let Some((_, _, height)) = self.pending.front() {
+                if height + self.options.finality_blocks > self.max_block_height {
+                    break;
+                }
+                ready.push(self.pending.pop_front().unwrap());
             }
+        }
+        if !self.handle_ready(&mut ready, handler, database).await {
+            return Processed::Stopped;
+        }
+        self.adapt_read_count(read, started.elapsed());
+        Processed::Running
+    }
+
+    /// Whether the stream is still paused. It's resumed once the operator deleted the
+    /// pause key.
+    async fn is_paused(&mut self, db: &mut redis_db::RedisDB) -> bool {
+        if self.paused
+            && db
+                .get(&pause_key(self.consumer, self.stream_key))
+                .await
+                .is_err()
+        {
+            log::info!("Resumed {}", self.stream_key);
+            self.paused = false;
+        }
+        self.paused
+    }
 
-            last_id = id;
+    async fn wait_while_paused(&mut self, db: &mut redis_db::RedisDB) {
+        while self.is_paused(db).await {
+            tokio::time::sleep(PAUSE_CHECK_INTERVAL).await;
         }
-        db.set(save_key, &last_id)
+    }
+
+    /// Doubles the read count while the stream is behind (reads are full) and batches are
+    /// handled within the target duration, halves it otherwise.
+    fn adapt_read_count(&mut self, read: usize, elapsed: Duration) {
+        let options = self.options;
+        let read_count = if read >= self.read_count && elapsed < options.target_batch_duration {
+            self.read_count.saturating_mul(2)
+        } else {
+            self.read_count / 2
+        }
+        .clamp(options.min_read_count.max(1), options.read_count.max(1));
+        if read_count != self.read_count {
+            log::debug!(
+                "Read count of {} is now {read_count} ({read} entries handled in {elapsed:?})",
+                self.stream_key
+            );
+            self.read_count = read_count;
+        }
+    }
+
+    /// Handles final entries, in parallel by partition key if there's more than one
+    /// worker. Entries with the same key are handled in order, and `last_id` only moves
+    /// past entries that were handled along with everything before them. Entries handled
+    /// after one that failed go to `handled`. Returns `false` if one of them failed.
+    async fn handle_ready<Db: Sync, H: EventHandler<Db> + ?Sized>(
+        &mut self,
+        ready: &mut Vec<(String, HashMap<String, Value>, u64)>,
+        handler: &H,
+        database: &Db,
+    ) -> bool {
+        if ready.is_empty() {
+            return true;
+        }
+        let workers = self.options.workers.max(1);
+        let partition_key = self.options.partition_keys.get(self.stream_key);
+        let mut ids = Vec::new();
+        let mut skipped = Vec::new();
+        let mut shards = (0..workers).map(|_| Vec::new()).collect::<Vec<_>>();
+        for (i, (id, data, block_height)) in ready.drain(..).enumerate() {
+            ids.push((id.clone(), block_height));
+            if self.handled.remove(&id) {
+                log::debug!(
+                    "Skipping {id} of {}, it was already handled",
+                    self.stream_key
+                );
+                self.handled_changed = true;
+                skipped.push(i);
+                continue;
+            }
+            let shard = match partition_key {
+                Some(partition_key) if workers > 1 => {
+                    let mut hasher = DefaultHasher::new();
+                    field_of(&data, partition_key).hash(&mut hasher);
+                    hasher.finish() as usize % workers
+                }
+                _ => 0,
+            };
+            shards[shard].push((i, id, data));
+        }
+
+        let results = futures::future::join_all(shards.into_iter().map(|shard| async move {
+            let mut handled = Vec::new();
+            for (i, id, data) in shard {
+                let version = schema_version(&data);
+                if let Err(err) = handler.handle(&id, data, database).await {
+                    log::error!("Failed to handle event {id}: {err:?}");
+                    return (handled, Some(i));
+                }
+                handled.push((i, version));
+            }
+            (handled, None)
+        }))
+        .await;
+        let failed = results.iter().filter_map(|(_, failed)| *failed).min();
+        for (_, version) in results.iter().flat_map(|(handled, _)| handled) {
+            let version =
+                version.map_or_else(|| "unversioned".to_string(), |version| version.to_string());
+            *self.schema_versions.entry(version).or_default() += 1;
+        }
+
+        let handled = failed.unwrap_or(ids.len());
+        if handled > 0 {
+            let (id, block_height) = &ids[handled - 1];
+            self.last_id.clone_from(id);
+            self.last_id_block_height = Some(*block_height);
+        }
+        if let Some(failed) = failed {
+            let handled_after = results
+                .into_iter()
+                .flat_map(|(handled, _)| handled.into_iter().map(|(i, _)| i))
+                .chain(skipped)
+                .filter(|i| *i > failed)
+                .map(|i| ids[i].0.clone())
+                .collect::<Vec<_>>();
+            if !handled_after.is_empty() {
+                self.handled.extend(handled_after);
+                self.handled_changed = true;
+            }
+            log::error!("Stopped reading events from {}", self.stream_key);
+            return false;
+        }
+        true
+    }
+
+    async fn save_position(&mut self, db: &mut redis_db::RedisDB) {
+        if self.handled_changed {
+            let handled_key = handled_key(self.consumer, self.stream_key);
+            let handled = self.handled.iter().cloned().collect::<Vec<_>>();
+            db.replace_set(&handled_key, &handled)
+                .await
+                .expect("Failed to set handled entries");
+            self.handled_changed = false;
+        }
+        db.set(&self.save_key, &self.last_id)
             .await
             .expect("Failed to set last ID");
+        if let Some(block_height) = self.last_id_block_height {
+            let last_block_key = last_block_key(self.consumer, self.stream_key);
+            if let Err(err) = db.set(&last_block_key, &block_height.to_string()).await {
+                log::warn!("Failed to set {last_block_key}: {err:?}");
+            }
+        }
+    }
+
+    async fn save(&mut self, db: &mut redis_db::RedisDB) {
+        self.save_position(db).await;
+        if !self.schema_versions.is_empty() {
+            let versions_key = schema_versions_key(self.consumer, self.stream_key);
+            for (version, count) in self.schema_versions.drain() {
+                if let Err(err) = db.hincrby(&versions_key, &version, count).await {
+                    log::warn!("Failed to update {versions_key}: {err:?}");
+                }
+            }
+        }
+        if let Some(trim) = &self.options.trim {
+            if let Err(err) = trim_stream(db, self.consumer, self.stream_key, trim).await {
+                log::warn!("Failed to trim {}: {err:?}", self.stream_key);
+            }
+        }
+    }
+}
+
+/// Sinks that can tell when they can't keep up, so that reading slows down instead of
+/// piling up work.
+pub trait Backpressure: Send + Sync {
+    fn is_saturated(&self) -> bool {
+        false
+    }
+}
+
+/// All connections are taken and none is idle.
+impl Backpressure for sqlx::PgPool {
+    fn is_saturated(&self) -> bool {
+        self.num_idle() == 0 && self.size() >= self.options().get_max_connections()
+    }
+}
+
+impl Backpressure for sqlx::SqlitePool {
+    fn is_saturated(&self) -> bool {
+        self.num_idle() == 0 && self.size() >= self.options().get_max_connections()
+    }
+}
+
+async fn wait_for_capacity(name: &str, database: &impl Backpressure) {
+    if !database.is_saturated() {
+        return;
+    }
+    log::debug!("Database is saturated, waiting before reading {name}");
+    while database.is_saturated() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Removes the entries of `stream_key` that every consumer has saved a position past,
+/// except for the margin.
+async fn trim_stream(
+    db: &mut redis_db::RedisDB,
+    consumer: &str,
+    stream_key: &str,
+    trim: &TrimOptions,
+) -> redis::RedisResult<()> {
+    let mut last_ids = Vec::new();
+    for consumer in trim.consumers.iter().map(String::as_str).chain([consumer]) {
+        let Ok(last_id) = db.get(&last_id_key(consumer, stream_key)).await else {
+            // A consumer that hasn't started yet would miss the trimmed entries
+            return Ok(());
+        };
+        last_ids.push(last_id);
+    }
+    let Some(min_id) = last_ids.into_iter().min_by_key(|last_id| id_parts(last_id)) else {
+        return Ok(());
+    };
+    if min_id == "$" {
+        return Ok(());
+    }
+
+    // XREVRANGE includes `min_id` itself, which isn't trimmed by MINID either
+    let kept = db
+        .xrevrange(stream_key, &min_id, "-", trim.margin_entries as usize + 1)
+        .await?;
+    let Some((mut trim_id, _)) = kept.into_iter().last() else {
+        return Ok(());
+    };
+    let time_limit = id_time(&min_id).saturating_sub(trim.margin.as_millis() as u64);
+    if time_limit < id_time(&trim_id) {
+        trim_id = time_limit.to_string();
+    }
+    let trimmed = db.xtrim_minid(stream_key, &trim_id).await?;
+    if trimmed > 0 {
+        log::debug!("Trimmed {trimmed} entries of {stream_key} before {trim_id}");
+    }
+    Ok(())
+}
+
+/// Reverts of a stream by entry ID, to tell which entries before them were removed.
+#[derive(Debug, Default)]
+pub struct Reverts(Vec<((u64, u64), u64)>);
+
+impl Reverts {
+    pub fn new(reverts: impl IntoIterator<Item = (String, u64)>) -> Self {
+        let mut reverts = reverts
+            .into_iter()
+            .map(|(id, block_height)| (id_parts(&id), block_height))
+            .collect::<Vec<_>>();
+        reverts.sort();
+        // Each one keeps the lowest block height kept by it or any later one
+        for i in (1..reverts.len()).rev() {
+            reverts[i - 1].1 = reverts[i - 1].1.min(reverts[i].1);
+        }
+        Self(reverts)
+    }
+
+    /// Highest block height that the reverts after `id` kept. Entries up to `id` from
+    /// later blocks were removed.
+    pub fn kept_after(&self, id: &str) -> Option<u64> {
+        let id = id_parts(id);
+        let later = self.0.partition_point(|(revert_id, _)| *revert_id <= id);
+        self.0.get(later).map(|(_, block_height)| *block_height)
+    }
+}
+
+/// Time and sequence of an entry ID, to compare IDs.
+pub fn id_parts(id: &str) -> (u64, u64) {
+    match id.split_once('-') {
+        Some((time, sequence)) => (
+            time.parse().unwrap_or_default(),
+            sequence.parse().unwrap_or_default(),
+        ),
+        None => (id.parse().unwrap_or_default(), 0),
+    }
+}
+
+/// The largest entry ID smaller than `id`, reading after it starts at `id`.
+pub fn previous_id(id: &str) -> anyhow::Result<String> {
+    let (millis, sequence) = id
+        .split_once('-')
+        .ok_or_else(|| anyhow::anyhow!("Invalid entry ID {id}"))?;
+    let (millis, sequence) = (millis.parse::<u64>()?, sequence.parse::<u64>()?);
+    Ok(match (millis, sequence) {
+        (0, 0) => "0-0".to_string(),
+        (millis, 0) => format!("{}-{}", millis - 1, u64::MAX),
+        (millis, sequence) => format!("{millis}-{}", sequence - 1),
+    })
+}
+
+/// ID of the last entry of the stream, or `0-0` if it's empty. Reading after `$` instead
+/// would skip entries added between reads, since `$` means the last entry at the time of
+/// each `XREAD` and the position doesn't move while a stream has nothing new.
+async fn latest_id(db: &mut redis_db::RedisDB, stream_key: &str) -> redis::RedisResult<String> {
+    let last_entry = db.xrevrange(stream_key, "+", "-", 1).await?;
+    Ok(match last_entry.into_iter().next() {
+        Some((id, _)) => id,
+        None => "0-0".to_string(),
+    })
+}
+
+/// The ID to read a stream after to start at `start_position`.
+async fn start_id(
+    db: &mut redis_db::RedisDB,
+    stream_key: &str,
+    start_position: StartPosition,
+) -> redis::RedisResult<String> {
+    let start_time = match start_position {
+        StartPosition::Latest => return latest_id(db, stream_key).await,
+        StartPosition::Earliest => return Ok("0".to_string()),
+        StartPosition::Id(id) => return Ok(id),
+        StartPosition::Timestamp(time) => time,
+        StartPosition::AfterBlock(block_height) => {
+            // Block heights grow with entry IDs, so binary search the last entry that isn't
+            // from a later block. Entries of different blocks can share a millisecond, so
+            // the search is over whole IDs.
+            let first_entry = db.xrange(stream_key, "-", "+", 1).await?;
+            let Some((first_id, _)) = first_entry.first() else {
+                return Ok("0".to_string());
+            };
+            let mut last_kept = None;
+            let mut low = id_number(first_id);
+            let mut high = u128::MAX;
+            while low < high {
+                let middle = low + (high - low) / 2;
+                let entry = db.xrange(stream_key, &number_id(middle), "+", 1).await?;
+                match entry.into_iter().next() {
+                    Some((id, data))
+                        if block_height_of(&data).unwrap_or_default() <= block_height =>
+                    {
+                        low = id_number(&id).saturating_add(1);
+                        last_kept = Some(id);
+                    }
+                    _ => high = middle,
+                }
+            }
+            return Ok(last_kept.unwrap_or_else(|| "0".to_string()));
+        }
+    };
+    Ok(match start_time.checked_sub(1) {
+        Some(time) => format!("{time}-{}", u64::MAX),
+        None => "0".to_string(),
+    })
+}
+
+fn id_time(id: &str) -> u64 {
+    id_parts(id).0
+}
+
+/// An entry ID as one number, to search between IDs.
+fn id_number(id: &str) -> u128 {
+    let (time, sequence) = id_parts(id);
+    (time as u128) << 64 | sequence as u128
+}
+
+fn number_id(number: u128) -> String {
+    format!("{}-{}", (number >> 64) as u64, number as u64)
+}
+
+fn check_order(
+    last_block_height: Option<u64>,
+    block_height: Option<u64>,
+    max_block_gap: Option<u64>,
+) -> Option<String> {
+    let (last_block_height, block_height) = (last_block_height?, block_height?);
+    if block_height < last_block_height {
+        return Some(format!(
+            "block height went back from {last_block_height} to {block_height}"
+        ));
+    }
+    match max_block_gap {
+        Some(max_block_gap) if block_height - last_block_height > max_block_gap => Some(format!(
+            "gap of {} blocks after {last_block_height}",
+            block_height - last_block_height
+        )),
+        _ => None,
+    }
+}
+
+/// Reads up to `count` entries of `stream_key` between `start` and `end`, in `XRANGE`
+/// syntax (`-`, `+`, `<ms>`, `<ms>-<seq>`, or `(<id>` to exclude it).
+pub async fn range_events(
+    connection: ConnectionManager,
+    stream_key: &str,
+    start: &str,
+    end: &str,
+    count: usize,
+) -> redis::RedisResult<Vec<(String, HashMap<String, Value>)>> {
+    let mut db = redis_db::RedisDB::new(connection).await;
+    db.xrange(stream_key, start, end, count).await
+}
+
+/// Revert messages look like `{"block_height": 123}`, which means that everything after
+/// block 123 is no longer valid.
+pub fn parse_revert(value: &Value) -> anyhow::Result<u64> {
+    #[derive(Deserialize)]
+    struct Revert {
+        block_height: u64,
+    }
+    let revert: Revert = serde_json::from_str(&String::from_redis_value(value)?)?;
+    Ok(revert.block_height)
+}
+
+/// Finds `field` in the top level of any JSON field of the entry, as a string.
+fn field_of(values: &HashMap<String, Value>, field: &str) -> Option<String> {
+    values
+        .values()
+        .filter_map(|value| String::from_redis_value(value).ok())
+        .filter_map(|value| serde_json::from_str::<serde_json::Value>(&value).ok())
+        .find_map(|value| match value.get(field)? {
+            serde_json::Value::String(value) => Some(value.clone()),
+            value => Some(value.to_string()),
+        })
+}
+
+/// Finds `block_height` in the top level of any JSON field of the entry, which is where
+/// every indexer puts it (in `context` or in the event itself).
+pub fn block_height_of(values: &HashMap<String, Value>) -> Option<u64> {
+    values
+        .values()
+        .filter_map(|value| String::from_redis_value(value).ok())
+        .filter_map(|value| serde_json::from_str::<serde_json::Value>(&value).ok())
+        .find_map(|value| value.get("block_height")?.as_u64())
+}
+
+#[async_trait::async_trait]
+pub trait EventHandler<Db: Sync = sqlx::PgPool>: Send + Sync {
+    async fn handle(&self, id: &str, values: HashMap<String, Value>, db: &Db)
+        -> anyhow::Result<()>;
+
+    /// Removes everything stored from blocks after `block_height`. Sinks that can't
+    /// remove data keep it.
+    async fn revert(&self, id: &str, block_height: u64, _db: &Db) -> anyhow::Result<()> {
+        log::warn!("Revert {id} to block {block_height} isn't supported by this sink, ignoring");
+        Ok(())
     }
 }
 
 #[async_trait::async_trait]
-pub trait EventHandler {
+impl<Db: Sync, H: EventHandler<Db> + ?Sized> EventHandler<Db> for Box<H> {
     async fn handle(
         &self,
+        id: &str,
         values: HashMap<String, Value>,
-        pg_pool: &sqlx::PgPool,
-    ) -> anyhow::Result<()>;
+        db: &Db,
+    ) -> anyhow::Result<()> {
+        (**self).handle(id, values, db).await
+    }
+
+    async fn revert(&self, id: &str, block_height: u64, db: &Db) -> anyhow::Result<()> {
+        (**self).revert(id, block_height, db).await
+    }
 }
 
 // Modified version of https://github.com/fastnear/redis-node/blob/4b9eb42f5d22162fac22fa14e90481bc016483fa/src/bin/redis_db/mod.rs
 mod redis_db {
-    use std::{collections::HashMap, time::Duration};
+    use std::{
+        collections::{HashMap, HashSet},
+        time::Duration,
+    };
 
-    use itertools::Itertools;
-    use redis::{aio::ConnectionManager, from_redis_value, Value};
+    use redis::{aio::ConnectionLike, Cmd, Pipeline, RedisFuture, Value};
 
     use self::stream::*;
 
     pub struct RedisDB {
-        pub connection: ConnectionManager,
+        connection: Connection,
     }
 
     impl RedisDB {
-        pub async fn new(connection: ConnectionManager) -> Self {
-            Self { connection }
+        pub async fn new(connection: impl ConnectionLike + Send + Sync + 'static) -> Self {
+            Self {
+                connection: Connection(Box::new(connection)),
+            }
+        }
+    }
+
+    /// Any connection, so that tests can use an in-memory one.
+    struct Connection(Box<dyn ConnectionLike + Send + Sync>);
+
+    impl ConnectionLike for Connection {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            self.0.req_packed_command(cmd)
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            cmd: &'a Pipeline,
+            offset: usize,
+            count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            self.0.req_packed_commands(cmd, offset, count)
+        }
+
+        fn get_db(&self) -> i64 {
+            self.0.get_db()
         }
     }
 
@@ -84,41 +984,148 @@ mod redis_db {
                 .await
         }
 
+        pub async fn smembers(&mut self, key: &str) -> redis::RedisResult<HashSet<String>> {
+            redis::cmd("SMEMBERS")
+                .arg(key)
+                .query_async(&mut self.connection)
+                .await
+        }
+
+        /// Replaces the set at `key` with `members`, or deletes it if there are none.
+        pub async fn replace_set(
+            &mut self,
+            key: &str,
+            members: &[String],
+        ) -> redis::RedisResult<()> {
+            redis::cmd("DEL")
+                .arg(key)
+                .query_async::<_, ()>(&mut self.connection)
+                .await?;
+            if !members.is_empty() {
+                redis::cmd("SADD")
+                    .arg(key)
+                    .arg(members)
+                    .query_async::<_, ()>(&mut self.connection)
+                    .await?;
+            }
+            Ok(())
+        }
+
+        pub async fn hincrby(
+            &mut self,
+            key: &str,
+            field: &str,
+            increment: u64,
+        ) -> redis::RedisResult<u64> {
+            redis::cmd("HINCRBY")
+                .arg(key)
+                .arg(field)
+                .arg(increment)
+                .query_async(&mut self.connection)
+                .await
+        }
+
+        pub async fn hgetall(&mut self, key: &str) -> redis::RedisResult<HashMap<String, u64>> {
+            redis::cmd("HGETALL")
+                .arg(key)
+                .query_async(&mut self.connection)
+                .await
+        }
+
         pub async fn xread(
             &mut self,
             count: usize,
+            block: Duration,
             key: &str,
             id: &str,
         ) -> redis::RedisResult<Vec<(String, HashMap<String, Value>)>> {
+            Ok(self
+                .xread_streams(count, block, &[(key, id)])
+                .await?
+                .remove(key)
+                .unwrap_or_default())
+        }
+
+        pub async fn xread_streams(
+            &mut self,
+            count: usize,
+            block: Duration,
+            keys_ids: &[(&str, &str)],
+        ) -> redis::RedisResult<HashMap<String, Vec<(String, HashMap<String, Value>)>>> {
             let streams: Vec<Stream> = redis::cmd("XREAD")
                 .arg("COUNT")
                 .arg(count)
                 .arg("BLOCK")
-                .arg(Duration::from_millis(250).as_millis() as u64)
+                .arg(block.as_millis() as u64)
                 .arg("STREAMS")
-                .arg(key)
-                .arg(id)
+                .arg(keys_ids.iter().map(|(key, _)| *key).collect::<Vec<_>>())
+                .arg(keys_ids.iter().map(|(_, id)| *id).collect::<Vec<_>>())
                 .query_async(&mut self.connection)
                 .await?;
             Ok(streams
                 .into_iter()
-                .filter(|s| s.id::<String>().unwrap() == key)
-                .flat_map(|s| s.entries.into_iter())
-                .map(|entry| {
-                    let id = entry.id().unwrap();
-                    let key_values = entry
-                        .key_values
-                        .into_iter()
-                        .tuples()
-                        .map(|(k, v)| (from_redis_value(&k).unwrap(), v))
-                        .collect();
-                    (id, key_values)
+                .map(|s| {
+                    (
+                        s.id::<String>().unwrap(),
+                        s.entries.into_iter().map(Entry::into_pair).collect(),
+                    )
                 })
                 .collect())
         }
+
+        pub async fn xrange(
+            &mut self,
+            key: &str,
+            start: &str,
+            end: &str,
+            count: usize,
+        ) -> redis::RedisResult<Vec<(String, HashMap<String, Value>)>> {
+            let entries: Vec<Entry> = redis::cmd("XRANGE")
+                .arg(key)
+                .arg(start)
+                .arg(end)
+                .arg("COUNT")
+                .arg(count)
+                .query_async(&mut self.connection)
+                .await?;
+            Ok(entries.into_iter().map(Entry::into_pair).collect())
+        }
+
+        pub async fn xrevrange(
+            &mut self,
+            key: &str,
+            end: &str,
+            start: &str,
+            count: usize,
+        ) -> redis::RedisResult<Vec<(String, HashMap<String, Value>)>> {
+            let entries: Vec<Entry> = redis::cmd("XREVRANGE")
+                .arg(key)
+                .arg(end)
+                .arg(start)
+                .arg("COUNT")
+                .arg(count)
+                .query_async(&mut self.connection)
+                .await?;
+            Ok(entries.into_iter().map(Entry::into_pair).collect())
+        }
+
+        /// Approximately trims the stream to entries from `min_id`, returns the number of
+        /// removed entries.
+        pub async fn xtrim_minid(&mut self, key: &str, min_id: &str) -> redis::RedisResult<u64> {
+            redis::cmd("XTRIM")
+                .arg(key)
+                .arg("MINID")
+                .arg("~")
+                .arg(min_id)
+                .query_async(&mut self.connection)
+                .await
+        }
     }
 
     mod stream {
+        use std::collections::HashMap;
+
+        use itertools::Itertools;
         use redis::{from_redis_value, FromRedisValue, RedisResult, Value};
 
         pub struct Stream {
@@ -156,6 +1163,856 @@ mod redis_db {
             pub fn id<RV: FromRedisValue>(&self) -> RedisResult<RV> {
                 from_redis_value(&self.id)
             }
+
+            pub fn into_pair(self) -> (String, HashMap<String, Value>) {
+                let id = self.id().unwrap();
+                let key_values = self
+                    .key_values
+                    .into_iter()
+                    .tuples()
+                    .map(|(k, v)| (from_redis_value(&k).unwrap(), v))
+                    .collect();
+                (id, key_values)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use redis::{Arg, Cmd, Pipeline, RedisFuture};
+
+    use super::*;
+
+    /// Just enough of Redis in memory for the reader. `XREAD` resolves `$` when it's called
+    /// and, when there's nothing to read, returns what was added during the block timeout,
+    /// like Redis does.
+    #[derive(Clone, Default)]
+    struct FakeRedis(Arc<Mutex<FakeState>>);
+
+    #[derive(Default)]
+    struct FakeState {
+        strings: HashMap<String, String>,
+        hashes: HashMap<String, HashMap<String, i64>>,
+        sets: HashMap<String, HashSet<String>>,
+        streams: HashMap<String, Vec<FakeEntry>>,
+        next_time: u64,
+        /// `COUNT` of the last `XREAD`.
+        read_count: usize,
+    }
+
+    /// ID parts and the only field, as `name\nvalue`.
+    type FakeEntry = ((u64, u64), String);
+
+    impl FakeRedis {
+        /// Adds an entry with `{"block_height": ...}` as its `event` field, or a revert.
+        fn add(&self, stream_key: &str, field: &str, value: serde_json::Value) -> String {
+            let mut state = self.0.lock().unwrap();
+            state.next_time += 1;
+            let id = (state.next_time, 0);
+            state
+                .streams
+                .entry(stream_key.to_string())
+                .or_default()
+                .push((id, format!("{field}\n{value}")));
+            format!("{}-{}", id.0, id.1)
+        }
+
+        fn add_event(&self, stream_key: &str, block_height: Option<u64>) -> String {
+            let event = match block_height {
+                Some(block_height) => serde_json::json!({ "block_height": block_height }),
+                None => serde_json::json!({}),
+            };
+            self.add(stream_key, "event", event)
+        }
+
+        fn get(&self, key: &str) -> Option<String> {
+            self.0.lock().unwrap().strings.get(key).cloned()
+        }
+
+        /// Entries after each position, for the streams that have some.
+        fn read(&self, after: &[(String, (u64, u64))], count: usize) -> Vec<Value> {
+            let state = self.0.lock().unwrap();
+            after
+                .iter()
+                .filter_map(|(key, after)| {
+                    let read = state
+                        .streams
+                        .get(key)?
+                        .iter()
+                        .filter(|(id, _)| id > after)
+                        .take(count)
+                        .collect::<Vec<_>>();
+                    (!read.is_empty()).then(|| Value::Bulk(vec![data(key), entries(read)]))
+                })
+                .collect()
+        }
+
+        fn xread(&self, args: &[String]) -> RedisFuture<'static, Value> {
+            let count = args[2].parse::<usize>().unwrap();
+            let block = Duration::from_millis(args[4].parse().unwrap());
+            let (keys, ids) = args[6..].split_at((args.len() - 6) / 2);
+            let after = {
+                let mut state = self.0.lock().unwrap();
+                state.read_count = count;
+                keys.iter()
+                    .zip(ids)
+                    .map(|(key, id)| {
+                        let after = match id.as_str() {
+                            "$" => state
+                                .streams
+                                .get(key)
+                                .and_then(|stream| stream.last())
+                                .map_or((0, 0), |(id, _)| *id),
+                            id => id_parts(id),
+                        };
+                        (key.clone(), after)
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let redis = self.clone();
+            Box::pin(async move {
+                let mut streams = redis.read(&after, count);
+                if streams.is_empty() {
+                    tokio::time::sleep(block).await;
+                    streams = redis.read(&after, count);
+                }
+                Ok(match streams.is_empty() {
+                    true => Value::Nil,
+                    false => Value::Bulk(streams),
+                })
+            })
+        }
+
+        fn execute(&self, args: &[String]) -> redis::RedisResult<Value> {
+            let mut state = self.0.lock().unwrap();
+            let value = match args[0].as_str() {
+                "SET" => {
+                    state.strings.insert(args[1].clone(), args[2].clone());
+                    Value::Okay
+                }
+                "GET" => match state.strings.get(&args[1]) {
+                    Some(value) => data(value),
+                    None => Value::Nil,
+                },
+                "DEL" => Value::Int(
+                    (state.strings.remove(&args[1]).is_some()
+                        || state.sets.remove(&args[1]).is_some()) as i64,
+                ),
+                "SADD" => {
+                    let set = state.sets.entry(args[1].clone()).or_default();
+                    let added = args[2..]
+                        .iter()
+                        .filter(|member| set.insert(member.to_string()));
+                    Value::Int(added.count() as i64)
+                }
+                "SMEMBERS" => Value::Bulk(
+                    state
+                        .sets
+                        .get(&args[1])
+                        .into_iter()
+                        .flatten()
+                        .map(|member| data(member))
+                        .collect(),
+                ),
+                "HINCRBY" => {
+                    let value = state
+                        .hashes
+                        .entry(args[1].clone())
+                        .or_default()
+                        .entry(args[2].clone())
+                        .or_default();
+                    *value += args[3].parse::<i64>().unwrap();
+                    Value::Int(*value)
+                }
+                "HGETALL" => Value::Bulk(
+                    state
+                        .hashes
+                        .get(&args[1])
+                        .into_iter()
+                        .flatten()
+                        .flat_map(|(field, value)| [data(field), data(&value.to_string())])
+                        .collect(),
+                ),
+                "XRANGE" | "XREVRANGE" if state.strings.contains_key(&args[1]) => {
+                    return Err((redis::ErrorKind::TypeError, "WRONGTYPE").into());
+                }
+                "XRANGE" | "XREVRANGE" => {
+                    let (start, end) = match args[0].as_str() {
+                        "XRANGE" => (&args[2], &args[3]),
+                        _ => (&args[3], &args[2]),
+                    };
+                    let start = match start.strip_prefix('(') {
+                        Some(id) => (id_parts(id).0, id_parts(id).1.saturating_add(1)),
+                        None if start == "-" => (0, 0),
+                        None => id_parts(start),
+                    };
+                    let end = match end.as_str() {
+                        "+" => (u64::MAX, u64::MAX),
+                        end if end.contains('-') => id_parts(end),
+                        end => (id_parts(end).0, u64::MAX),
+                    };
+                    let count = args[5].parse::<usize>().unwrap();
+                    let mut range = state
+                        .streams
+                        .get(&args[1])
+                        .into_iter()
+                        .flatten()
+                        .filter(|(id, _)| start <= *id && *id <= end)
+                        .collect::<Vec<_>>();
+                    if args[0] == "XREVRANGE" {
+                        range.reverse();
+                    }
+                    range.truncate(count);
+                    entries(range)
+                }
+                "XTRIM" => {
+                    let min_id = id_parts(&args[4]);
+                    let stream = state.streams.entry(args[1].clone()).or_default();
+                    let before = stream.len();
+                    stream.retain(|(id, _)| *id >= min_id);
+                    Value::Int((before - stream.len()) as i64)
+                }
+                command => panic!("Unexpected command {command}"),
+            };
+            Ok(value)
+        }
+    }
+
+    fn data(value: &str) -> Value {
+        Value::Data(value.as_bytes().to_vec())
+    }
+
+    fn entries(entries: Vec<&FakeEntry>) -> Value {
+        Value::Bulk(
+            entries
+                .into_iter()
+                .map(|((time, sequence), fields)| {
+                    let (field, value) = fields.split_once('\n').unwrap();
+                    Value::Bulk(vec![
+                        data(&format!("{time}-{sequence}")),
+                        Value::Bulk(vec![data(field), data(value)]),
+                    ])
+                })
+                .collect(),
+        )
+    }
+
+    impl ConnectionLike for FakeRedis {
+        fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, Value> {
+            let args = cmd
+                .args_iter()
+                .map(|arg| match arg {
+                    Arg::Simple(arg) => String::from_utf8_lossy(arg).into_owned(),
+                    Arg::Cursor => unimplemented!(),
+                })
+                .collect::<Vec<_>>();
+            if args[0] == "XREAD" {
+                return self.xread(&args);
+            }
+            let result = self.execute(&args);
+            Box::pin(async move { result })
+        }
+
+        fn req_packed_commands<'a>(
+            &'a mut self,
+            _cmd: &'a Pipeline,
+            _offset: usize,
+            _count: usize,
+        ) -> RedisFuture<'a, Vec<Value>> {
+            unimplemented!()
+        }
+
+        fn get_db(&self) -> i64 {
+            0
         }
     }
+
+    impl Backpressure for () {}
+
+    /// Records what it's asked to do, and fails the entries in `failing`. Clones record to
+    /// the same list.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        calls: Arc<Mutex<Vec<String>>>,
+        failing: Vec<String>,
+        /// How long handling an entry takes.
+        delay: Duration,
+    }
+
+    impl Recorder {
+        fn calls(&self) -> Vec<String> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl EventHandler<()> for Recorder {
+        async fn handle(
+            &self,
+            id: &str,
+            _values: HashMap<String, Value>,
+            _db: &(),
+        ) -> anyhow::Result<()> {
+            tokio::time::sleep(self.delay).await;
+            if self.failing.iter().any(|failing| failing == id) {
+                anyhow::bail!("Failing {id}");
+            }
+            self.calls.lock().unwrap().push(id.to_string());
+            Ok(())
+        }
+
+        async fn revert(&self, id: &str, block_height: u64, _db: &()) -> anyhow::Result<()> {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{id} revert {block_height}"));
+            Ok(())
+        }
+    }
+
+    fn options(finality_blocks: u64) -> StreamOptions {
+        StreamOptions {
+            finality_blocks,
+            block_timeout: Duration::from_millis(10),
+            ..Default::default()
+        }
+    }
+
+    async fn read_all(
+        db: &mut redis_db::RedisDB,
+        reader: &StreamReader<'_>,
+    ) -> Vec<(String, HashMap<String, Value>)> {
+        db.xread(100, Duration::ZERO, reader.stream_key, &reader.read_id)
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn previous_id_precedes_id() {
+        assert_eq!(previous_id("5-3").unwrap(), "5-2");
+        assert_eq!(previous_id("5-0").unwrap(), format!("4-{}", u64::MAX));
+        assert!(previous_id("5").is_err());
+    }
+
+    #[tokio::test]
+    async fn handles_entries_once_final() {
+        let redis = FakeRedis::default();
+        let ids = (1..=4)
+            .map(|block_height| redis.add_event("s", Some(block_height)))
+            .collect::<Vec<_>>();
+        let options = StreamOptions {
+            start_positions: HashMap::from([("s".to_string(), StartPosition::Earliest)]),
+            ..options(2)
+        };
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let handler = Recorder::default();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+        reader.save(&mut db).await;
+
+        assert_eq!(handler.calls(), ids[..2]);
+        assert_eq!(redis.get("c_last_id_s").as_ref(), Some(&ids[1]));
+        assert_eq!(reader.pending.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn holds_entries_without_block_height_with_the_one_before() {
+        let redis = FakeRedis::default();
+        let ids = [
+            redis.add_event("s", Some(1)),
+            redis.add_event("s", None),
+            redis.add_event("s", Some(2)),
+            redis.add_event("s", Some(3)),
+        ];
+        let options = StreamOptions {
+            start_positions: HashMap::from([("s".to_string(), StartPosition::Earliest)]),
+            ..options(1)
+        };
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let handler = Recorder::default();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+
+        assert_eq!(handler.calls(), ids[..3]);
+    }
+
+    #[tokio::test]
+    async fn saves_position_before_pending_entries() {
+        let redis = FakeRedis::default();
+        redis.add_event("s", Some(1));
+        let options = options(5);
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let ids = [redis.add_event("s", Some(2)), redis.add_event("s", Some(3))];
+        let handler = Recorder::default();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+        reader.save(&mut db).await;
+
+        assert!(handler.calls().is_empty());
+        let saved = redis.get("c_last_id_s").unwrap();
+        let unread = db.xrange("s", &format!("({saved}"), "+", 10).await.unwrap();
+        assert_eq!(
+            unread.into_iter().map(|(id, _)| id).collect::<Vec<_>>(),
+            ids
+        );
+    }
+
+    #[tokio::test]
+    async fn reverts_pending_and_stored_entries() {
+        let redis = FakeRedis::default();
+        let ids = [
+            redis.add_event("s", Some(1)),
+            redis.add_event("s", Some(2)),
+            redis.add_event("s", Some(3)),
+            redis.add("s", "revert", serde_json::json!({ "block_height": 1 })),
+            redis.add_event("s", Some(2)),
+            redis.add_event("s", Some(4)),
+        ];
+        let options = StreamOptions {
+            start_positions: HashMap::from([("s".to_string(), StartPosition::Earliest)]),
+            ..options(1)
+        };
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let handler = Recorder::default();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+
+        // Block 3 wasn't final yet, so it's dropped without being handled
+        assert_eq!(
+            handler.calls(),
+            [
+                ids[0].clone(),
+                ids[1].clone(),
+                format!("{} revert 1", ids[3]),
+                ids[4].clone(),
+            ]
+        );
+        assert_eq!(reader.last_id, ids[4]);
+    }
+
+    #[test]
+    fn checks_order() {
+        assert_eq!(check_order(None, Some(5), Some(1)), None);
+        assert_eq!(check_order(Some(5), None, Some(1)), None);
+        assert_eq!(check_order(Some(5), Some(5), Some(0)), None);
+        assert_eq!(check_order(Some(5), Some(100), None), None);
+        assert_eq!(
+            check_order(Some(5), Some(4), None).as_deref(),
+            Some("block height went back from 5 to 4")
+        );
+        assert_eq!(check_order(Some(5), Some(7), Some(2)), None);
+        assert_eq!(
+            check_order(Some(5), Some(8), Some(2)).as_deref(),
+            Some("gap of 3 blocks after 5")
+        );
+    }
+
+    #[tokio::test]
+    async fn checks_order_across_restarts() {
+        let redis = FakeRedis::default();
+        redis.add_event("s", Some(10));
+        let options = StreamOptions {
+            start_positions: HashMap::from([("s".to_string(), StartPosition::Earliest)]),
+            max_block_gaps: HashMap::from([("s".to_string(), 5)]),
+            ..options(0)
+        };
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let handler = Recorder::default();
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+        reader.save(&mut db).await;
+
+        redis.add_event("s", Some(20));
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+        assert_eq!(reader.anomalies, 1);
+    }
+
+    #[tokio::test]
+    async fn uses_block_gap_of_stream() {
+        let redis = FakeRedis::default();
+        for stream_key in ["quiet", "busy"] {
+            redis.add_event(stream_key, Some(10));
+            redis.add_event(stream_key, Some(20));
+        }
+        let options = StreamOptions {
+            start_positions: HashMap::from([
+                ("quiet".to_string(), StartPosition::Earliest),
+                ("busy".to_string(), StartPosition::Earliest),
+            ]),
+            max_block_gaps: HashMap::from([("quiet".to_string(), 100), ("busy".to_string(), 5)]),
+            ..options(0)
+        };
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let handler = Recorder::default();
+        for (stream_key, anomalies) in [("quiet", 0), ("busy", 1)] {
+            let mut reader = StreamReader::new(&mut db, "c", stream_key, &options)
+                .await
+                .unwrap();
+            let entries = read_all(&mut db, &reader).await;
+            assert_eq!(
+                reader.process(&mut db, entries, &handler, &()).await,
+                Processed::Running
+            );
+            assert_eq!(reader.anomalies, anomalies, "{stream_key}");
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stays_paused_after_restart() {
+        let redis = FakeRedis::default();
+        let ids = [
+            redis.add_event("s", Some(10)),
+            redis.add_event("s", Some(50)),
+            redis.add_event("s", Some(51)),
+        ];
+        // Paused at the gap before the restart
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        db.set("c_last_id_s", &ids[0]).await.unwrap();
+        db.set("c_last_block_s", "10").await.unwrap();
+        db.set(
+            "c_paused_s",
+            &format!("{}: gap of 40 blocks after 10", ids[1]),
+        )
+        .await
+        .unwrap();
+        let options = StreamOptions {
+            max_block_gaps: HashMap::from([("s".to_string(), 5)]),
+            pause_on_anomaly: true,
+            ..options(0)
+        };
+
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let resumed = Arc::new(Mutex::new(false));
+        tokio::join!(reader.wait_while_paused(&mut db), {
+            let (redis, resumed) = (redis.clone(), resumed.clone());
+            async move {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                *resumed.lock().unwrap() = true;
+                redis.0.lock().unwrap().strings.remove("c_paused_s");
+            }
+        });
+        assert!(*resumed.lock().unwrap());
+
+        // The operator let the gap through, so it doesn't pause again
+        let handler = Recorder::default();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+        assert_eq!(reader.anomalies, 0);
+        assert_eq!(handler.calls(), ids[1..]);
+    }
+
+    #[tokio::test]
+    async fn stops_stream_without_start_position() {
+        let redis = FakeRedis::default();
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        db.set("s", "not a stream").await.unwrap();
+        let options = StreamOptions {
+            start_positions: HashMap::from([("s".to_string(), StartPosition::AfterBlock(5))]),
+            ..options(0)
+        };
+        assert!(StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn starts_after_latest_entry() {
+        let redis = FakeRedis::default();
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let options = options(0);
+        let reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        assert_eq!(reader.read_id, "0-0");
+
+        let id = redis.add_event("s", Some(1));
+        db.set("c_last_id_s", "$").await.unwrap();
+        let reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        assert_eq!(reader.read_id, id);
+    }
+
+    #[tokio::test]
+    async fn starts_after_block_within_millisecond() {
+        let redis = FakeRedis::default();
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        // Blocks 5 and 6 share a millisecond
+        let blocks = [
+            ((1, 0), 4),
+            ((2, 0), 5),
+            ((2, 1), 5),
+            ((2, 2), 6),
+            ((3, 0), 7),
+        ];
+        redis.0.lock().unwrap().streams.insert(
+            "s".to_string(),
+            blocks
+                .iter()
+                .map(|(id, block_height)| {
+                    let event = serde_json::json!({ "block_height": block_height });
+                    (*id, format!("event\n{event}"))
+                })
+                .collect(),
+        );
+        for (block_height, id) in [(3, "0"), (4, "1-0"), (5, "2-1"), (6, "2-2"), (9, "3-0")] {
+            let start_position = StartPosition::AfterBlock(block_height);
+            assert_eq!(start_id(&mut db, "s", start_position).await.unwrap(), id);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn multiplexed_streams_read_every_entry() {
+        let redis = FakeRedis::default();
+        // Added before the streams start, so not read
+        redis.add_event("busy", Some(0));
+        // The busy stream is behind, so there are entries to read every time and quiet ones
+        // are added between reads
+        let handler = Recorder {
+            delay: Duration::from_millis(5),
+            ..Default::default()
+        };
+        let handlers: Vec<(&str, Box<dyn EventHandler<()>>)> = vec![
+            ("busy", Box::new(handler.clone())),
+            ("quiet", Box::new(handler.clone())),
+        ];
+        let options = StreamOptions {
+            multiplex: true,
+            read_count: 3,
+            ..options(0)
+        };
+
+        let mut added = tokio::select! {
+            _ = stream_events_multiplexed("c", handlers, redis.clone(), (), &options) => {
+                unreachable!()
+            }
+            added = async {
+                let mut added = Vec::new();
+                for block_height in 1..=100 {
+                    tokio::time::sleep(Duration::from_millis(3)).await;
+                    added.push(redis.add_event("busy", Some(block_height)));
+                    if block_height % 10 == 0 {
+                        added.push(redis.add_event("quiet", Some(block_height)));
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                added
+            } => added,
+        };
+
+        let mut handled = handler.calls();
+        handled.sort_by_key(|id| id_parts(id));
+        added.sort_by_key(|id| id_parts(id));
+        assert_eq!(handled, added);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn multiplexed_stream_pauses_alone() {
+        let redis = FakeRedis::default();
+        let handler = Recorder::default();
+        let handlers: Vec<(&str, Box<dyn EventHandler<()>>)> = vec![
+            ("gap", Box::new(handler.clone())),
+            ("steady", Box::new(handler.clone())),
+        ];
+        let options = StreamOptions {
+            multiplex: true,
+            max_block_gaps: HashMap::from([("gap".to_string(), 5)]),
+            pause_on_anomaly: true,
+            ..options(0)
+        };
+
+        let (gap, steady) = tokio::select! {
+            _ = stream_events_multiplexed("c", handlers, redis.clone(), (), &options) => {
+                unreachable!()
+            }
+            added = async {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                let gap = [redis.add_event("gap", Some(1)), redis.add_event("gap", Some(50))];
+                let mut steady = Vec::new();
+                for block_height in 1..=10 {
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                    steady.push(redis.add_event("steady", Some(block_height)));
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                assert_eq!(
+                    redis.get("c_paused_gap").unwrap(),
+                    format!("{}: gap of 49 blocks after 1", gap[1])
+                );
+                let mut handled = vec![gap[0].clone()];
+                handled.extend(steady.iter().cloned());
+                assert_eq!(handler.calls(), handled);
+
+                // Resumed at the entry it paused at, which isn't checked again
+                redis.0.lock().unwrap().strings.remove("c_paused_gap");
+                tokio::time::sleep(Duration::from_secs(10)).await;
+                (gap, steady)
+            } => added,
+        };
+        let mut handled = vec![gap[0].clone()];
+        handled.extend(steady);
+        handled.push(gap[1].clone());
+        assert_eq!(handler.calls(), handled);
+        assert_eq!(redis.get("c_last_id_gap").as_ref(), Some(&gap[1]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn multiplexed_read_count_shrinks_when_idle() {
+        let redis = FakeRedis::default();
+        let handler = Recorder::default();
+        let handlers: Vec<(&str, Box<dyn EventHandler<()>>)> = vec![
+            ("a", Box::new(handler.clone())),
+            ("b", Box::new(handler.clone())),
+        ];
+        let options = StreamOptions {
+            multiplex: true,
+            read_count: 64,
+            ..options(0)
+        };
+        tokio::select! {
+            _ = stream_events_multiplexed("c", handlers, redis.clone(), (), &options) => {
+                unreachable!()
+            }
+            _ = tokio::time::sleep(Duration::from_secs(1)) => (),
+        }
+        assert_eq!(redis.0.lock().unwrap().read_count, 1);
+    }
+
+    #[tokio::test]
+    async fn skips_entries_handled_after_a_failed_worker() {
+        let redis = FakeRedis::default();
+        let options = StreamOptions {
+            start_positions: HashMap::from([("s".to_string(), StartPosition::Earliest)]),
+            workers: 2,
+            partition_keys: HashMap::from([("s".to_string(), "account".to_string())]),
+            ..options(0)
+        };
+        // Two accounts handled by different workers
+        let shard = |account: &str| {
+            let mut hasher = DefaultHasher::new();
+            Some(account.to_string()).hash(&mut hasher);
+            hasher.finish() % 2
+        };
+        let (x, y) = (
+            "x",
+            ["y", "z", "w", "v"]
+                .into_iter()
+                .find(|y| shard(y) != shard("x"))
+                .unwrap(),
+        );
+        let ids = [x, y, x, y, x].map(|account| {
+            redis.add(
+                "s",
+                "event",
+                serde_json::json!({ "block_height": 1, "account": account }),
+            )
+        });
+
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let handler = Recorder {
+            failing: vec![ids[2].clone()],
+            ..Default::default()
+        };
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Stopped
+        );
+        reader.save(&mut db).await;
+        let mut handled = handler.calls();
+        handled.sort_by_key(|id| id_parts(id));
+        assert_eq!(handled, [ids[0].clone(), ids[1].clone(), ids[3].clone()]);
+        assert_eq!(redis.get("c_last_id_s").as_ref(), Some(&ids[1]));
+
+        let handler = Recorder::default();
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Running
+        );
+        reader.save(&mut db).await;
+        let mut handled = handler.calls();
+        handled.sort_by_key(|id| id_parts(id));
+        assert_eq!(handled, [ids[2].clone(), ids[4].clone()]);
+        assert!(!redis.0.lock().unwrap().sets.contains_key("c_handled_s"));
+    }
+
+    #[tokio::test]
+    async fn counts_schema_versions_of_handled_entries() {
+        let redis = FakeRedis::default();
+        let options = StreamOptions {
+            start_positions: HashMap::from([("s".to_string(), StartPosition::Earliest)]),
+            ..options(0)
+        };
+        let ids = [1, 2, 3].map(|block_height| redis.add_event("s", Some(block_height)));
+
+        let mut db = redis_db::RedisDB::new(redis.clone()).await;
+        let handler = Recorder {
+            failing: vec![ids[1].clone()],
+            ..Default::default()
+        };
+        let mut reader = StreamReader::new(&mut db, "c", "s", &options)
+            .await
+            .unwrap();
+        let entries = read_all(&mut db, &reader).await;
+        assert_eq!(
+            reader.process(&mut db, entries, &handler, &()).await,
+            Processed::Stopped
+        );
+        reader.save(&mut db).await;
+        assert_eq!(
+            redis.0.lock().unwrap().hashes["c_schema_versions_s"],
+            HashMap::from([("unversioned".to_string(), 1)])
+        );
+    }
 }
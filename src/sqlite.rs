@@ -0,0 +1,1926 @@
+//! SQLite sink for local development and tests. Tables mirror the TimescaleDB ones,
+//! except that NUMERIC amounts are stored as TEXT and arrays as JSON.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use events_api_redis_to_db::{
+    events::{
+        decode_entry, timestamp, AccountCreateEvent, AccountDeleteEvent, ContractDeployEvent,
+        Decoded, FtBurnEvent, FtMintEvent, FtTransferEvent, LogNep297Event, NearTransferEvent,
+        NftApproveEvent, NftBurnEvent, NftListEvent, NftMintEvent, NftRevokeEvent, NftSaleEvent,
+        NftTransferEvent, NftUnlistEvent, PotlockDonationEvent, PotlockPotConfigEvent,
+        PotlockPotDonationEvent, PotlockPotPayoutEvent, PotlockPotProjectDonationEvent,
+        PotlockProjectStatusEvent, SocialCommentEvent, SocialFollowEvent, SocialLikeEvent,
+        SocialPostEvent, TokenPriceEvent, TradeBalanceChangeSwapEvent, TradeLiquidityAddEvent,
+        TradeLiquidityRemoveEvent, TradePoolChangeEvent, TradeRawPoolSwapEvent, TransactionEvent,
+    },
+    redis_reader::EventHandler,
+};
+use redis::Value;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
+
+use crate::{
+    AccountCreateHandler, AccountDeleteHandler, ContractDeployHandler, FtBurnHandler,
+    FtMintHandler, FtTransferHandler, Listing, LogNep297Handler, NearTransferHandler,
+    NftApproveHandler, NftBurnHandler, NftListHandler, NftMintHandler, NftRevokeHandler,
+    NftSaleHandler, NftTransferHandler, NftUnlistHandler, PotlockDonationHandler,
+    PotlockPotConfigHandler, PotlockPotDonationHandler, PotlockPotPayoutHandler,
+    PotlockPotProjectDonationHandler, PotlockProjectStatusHandler, SocialCommentHandler,
+    SocialFollowHandler, SocialLikeHandler, SocialPostHandler, TokenPriceHandler,
+    TradeBalanceChangeSwapHandler, TradeLiquidityAddHandler, TradeLiquidityRemoveHandler,
+    TradePoolChangeHandler, TradeRawPoolSwapHandler, TransactionHandler,
+};
+
+/// Opens (creating if necessary) the database and applies `migrations_sqlite`.
+pub async fn connect(database_url: &str) -> anyhow::Result<SqlitePool> {
+    let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await?;
+    sqlx::migrate!("./migrations_sqlite").run(&pool).await?;
+    Ok(pool)
+}
+
+pub async fn max_block_height(
+    sqlite_pool: &SqlitePool,
+    table: &str,
+) -> anyhow::Result<Option<u64>> {
+    let max_block_height: Option<i64> =
+        sqlx::query_scalar(&format!("SELECT MAX(block_height) FROM {table}"))
+            .fetch_one(sqlite_pool)
+            .await?;
+    Ok(max_block_height.map(|block_height| block_height as u64))
+}
+
+/// Removes the rows of `table` from blocks after `block_height`.
+async fn revert_table(
+    sqlite_pool: &SqlitePool,
+    table: &str,
+    block_height: u64,
+) -> anyhow::Result<()> {
+    sqlx::query(&format!("DELETE FROM {table} WHERE block_height > $1"))
+        .bind(block_height as i64)
+        .execute(sqlite_pool)
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftMintHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftMintEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO nft_mint (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_ids, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.owner_id)
+            .bind(serde_json::to_string(&event.token_ids)?)
+            .bind(event.memo)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nft mint event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_mint", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftTransferHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftTransferEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO nft_transfer (timestamp, transaction_id, receipt_id, block_height, contract_id, old_owner_id, new_owner_id, token_ids, memo, token_prices_near, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.old_owner_id)
+            .bind(event.new_owner_id)
+            .bind(serde_json::to_string(&event.token_ids)?)
+            .bind(event.memo)
+            .bind(serde_json::to_string(&event.token_prices_near.iter().map(|price| price.unwrap_or_default().to_string()).collect::<Vec<_>>())?)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nft transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_transfer", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftBurnHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftBurnEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO nft_burn (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_ids, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.owner_id)
+            .bind(serde_json::to_string(&event.token_ids)?)
+            .bind(event.memo)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nft burn event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_burn", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for PotlockDonationHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockDonationEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO potlock_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, donor_id, total_amount, ft_id, message, donated_at, project_id, protocol_fee, referrer_id, referrer_fee, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.donation_id as i64)
+            .bind(event.donor_id)
+            .bind(event.total_amount.to_string())
+            .bind(event.ft_id)
+            .bind(event.message)
+            .bind(event.donated_at)
+            .bind(event.project_id)
+            .bind(event.protocol_fee.to_string())
+            .bind(event.referrer_id)
+            .bind(event.referrer_fee.map(|fee| fee.to_string()))
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock donation event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "potlock_donation", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for PotlockPotProjectDonationHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotProjectDonationEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO potlock_pot_project_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, pot_id, donor_id, total_amount, net_amount, message, donated_at, project_id, referrer_id, referrer_fee, protocol_fee, chef_id, chef_fee, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.donation_id as i64)
+            .bind(event.pot_id)
+            .bind(event.donor_id)
+            .bind(event.total_amount.to_string())
+            .bind(event.net_amount.to_string())
+            .bind(event.message)
+            .bind(event.donated_at)
+            .bind(event.project_id)
+            .bind(event.referrer_id)
+            .bind(event.referrer_fee.map(|fee| fee.to_string()))
+            .bind(event.protocol_fee.to_string())
+            .bind(event.chef_id)
+            .bind(event.chef_fee.map(|fee| fee.to_string()))
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock pot project donation event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "potlock_pot_project_donation", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for PotlockPotDonationHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotDonationEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO potlock_pot_donation (timestamp, transaction_id, receipt_id, block_height, donation_id, pot_id, donor_id, total_amount, net_amount, message, donated_at, referrer_id, referrer_fee, protocol_fee, chef_id, chef_fee, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.donation_id as i64)
+            .bind(event.pot_id)
+            .bind(event.donor_id)
+            .bind(event.total_amount.to_string())
+            .bind(event.net_amount.to_string())
+            .bind(event.message)
+            .bind(event.donated_at)
+            .bind(event.referrer_id)
+            .bind(event.referrer_fee.map(|fee| fee.to_string()))
+            .bind(event.protocol_fee.to_string())
+            .bind(event.chef_id)
+            .bind(event.chef_fee.map(|fee| fee.to_string()))
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock pot donation event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "potlock_pot_donation", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for TradeRawPoolSwapHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeRawPoolSwapEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO trade_pool (timestamp, trader, transaction_id, receipt_id, block_height, pool, token_in, token_out, amount_in, amount_out, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.trader)
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.0.pool)
+            .bind(event.0.token_in)
+            .bind(event.0.token_out)
+            .bind(event.0.amount_in.to_string())
+            .bind(event.0.amount_out.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse raw pool swap event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "trade_pool", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for TradeBalanceChangeSwapHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeBalanceChangeSwapEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO trade_swap (timestamp, trader, transaction_id, receipt_id, block_height, balance_changes, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.trader)
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(serde_json::Value::Object(event.balance_changes.into_iter().map(|(k, v)| (k, serde_json::Value::String(v.to_string()))).collect()).to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse balance change swap event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "trade_swap", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for TradePoolChangeHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TradePoolChangeEvent>(&values) {
+            sqlx::query(
+                r#"
+                INSERT INTO trade_pool_change (timestamp, receipt_id, block_height, pool_id, pool, extra)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+            )
+            .bind(timestamp(event.block_timestamp_nanosec))
+            .bind(event.receipt_id)
+            .bind(event.block_height as i64)
+            .bind(event.pool_id)
+            .bind(event.pool.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse pool change event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "trade_pool_change", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for FtTransferHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtTransferEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO ft_transfer (timestamp, transaction_id, receipt_id, block_height, contract_id, old_owner_id, new_owner_id, amount, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.old_owner_id)
+            .bind(event.new_owner_id)
+            .bind(event.amount.to_string())
+            .bind(event.memo)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse ft transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "ft_transfer", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for FtMintHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtMintEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO ft_mint (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, amount, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.owner_id)
+            .bind(event.amount.to_string())
+            .bind(event.memo)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse ft mint event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "ft_mint", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for FtBurnHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<FtBurnEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO ft_burn (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, amount, memo, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.owner_id)
+            .bind(event.amount.to_string())
+            .bind(event.memo)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse ft burn event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "ft_burn", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NearTransferHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<NearTransferEvent>(&values) {
+            sqlx::query(
+                r#"
+                INSERT INTO near_transfer (timestamp, transaction_id, receipt_id, block_height, sender_id, receiver_id, amount, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+            )
+            .bind(timestamp(event.block_timestamp_nanosec))
+            .bind(event.transaction_id)
+            .bind(event.receipt_id)
+            .bind(event.block_height as i64)
+            .bind(event.sender_id)
+            .bind(event.receiver_id)
+            .bind(event.amount.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse near transfer event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "near_transfer", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for SocialPostHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialPostEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO social_post (timestamp, transaction_id, receipt_id, block_height, account_id, path, text, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.account_id)
+            .bind(event.path)
+            .bind(event.text)
+            .bind(event.value.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social post event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "social_post", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for SocialCommentHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialCommentEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO social_comment (timestamp, transaction_id, receipt_id, block_height, account_id, path, text, item_author_id, item_block_height, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.account_id)
+            .bind(event.path)
+            .bind(event.text)
+            .bind(event.item_author_id)
+            .bind(event.item_block_height as i64)
+            .bind(event.value.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social comment event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "social_comment", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for SocialLikeHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialLikeEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO social_like (timestamp, transaction_id, receipt_id, block_height, account_id, path, item_author_id, item_block_height, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.account_id)
+            .bind(event.path)
+            .bind(event.item_author_id)
+            .bind(event.item_block_height as i64)
+            .bind(event.value.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social like event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "social_like", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for SocialFollowHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<SocialFollowEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO social_follow (timestamp, transaction_id, receipt_id, block_height, account_id, path, target_id, follow, value, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.account_id)
+            .bind(event.path)
+            .bind(event.target_id)
+            .bind(event.follow)
+            .bind(event.value.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse social follow event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "social_follow", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for LogNep297Handler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<LogNep297Event>(&values)
+        {
+            if event.is_promoted() {
+                return Ok(());
+            }
+            sqlx::query(
+                r#"
+                INSERT INTO log_nep297 (timestamp, transaction_id, receipt_id, block_height, contract_id, standard, version, event, data, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.standard)
+            .bind(event.version)
+            .bind(event.event)
+            .bind(event.data.map(|value| value.to_string()))
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nep297 log event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "log_nep297", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for PotlockPotConfigHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotConfigEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO potlock_pot_config (timestamp, transaction_id, receipt_id, block_height, pot_id, created, owner_id, admins, chef_id, name, description, application_start, application_end, public_round_start, public_round_end, config, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.pot_id)
+            .bind(event.created)
+            .bind(event.owner_id)
+            .bind(serde_json::to_string(&event.admins)?)
+            .bind(event.chef_id)
+            .bind(event.name)
+            .bind(event.description)
+            .bind(event.application_start)
+            .bind(event.application_end)
+            .bind(event.public_round_start)
+            .bind(event.public_round_end)
+            .bind(event.config.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock pot config event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "potlock_pot_config", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for PotlockProjectStatusHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockProjectStatusEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO potlock_project_status (timestamp, transaction_id, receipt_id, block_height, project_id, pot_id, status, reviewer_id, review_notes, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.project_id)
+            .bind(event.pot_id)
+            .bind(event.status)
+            .bind(event.reviewer_id)
+            .bind(event.review_notes)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock project status event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "potlock_project_status", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for PotlockPotPayoutHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<PotlockPotPayoutEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO potlock_pot_payout (timestamp, transaction_id, receipt_id, block_height, payout_id, pot_id, project_id, amount, paid_at, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.payout_id)
+            .bind(event.pot_id)
+            .bind(event.project_id)
+            .bind(event.amount.to_string())
+            .bind(event.paid_at)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse potlock pot payout event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "potlock_pot_payout", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftApproveHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftApproveEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO nft_approve (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_id, account_id, approval_id, msg, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.owner_id)
+            .bind(event.token_id)
+            .bind(event.account_id)
+            .bind(event.approval_id as i64)
+            .bind(event.msg)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nft approve event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_approve", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftRevokeHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftRevokeEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO nft_revoke (timestamp, transaction_id, receipt_id, block_height, contract_id, owner_id, token_id, account_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.owner_id)
+            .bind(event.token_id)
+            .bind(event.account_id)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse nft revoke event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_revoke", block_height).await?;
+        Ok(())
+    }
+}
+
+/// Same as the Postgres `set_listing`. Prices are text, so they're compared by length
+/// first.
+async fn set_listing(tx: &mut sqlx::SqliteConnection, listing: &Listing<'_>) -> anyhow::Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO nft_listing (marketplace_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (marketplace_id, nft_contract_id, token_id) DO UPDATE SET
+            seller_id = excluded.seller_id,
+            price = excluded.price,
+            ft_id = excluded.ft_id,
+            timestamp = excluded.timestamp,
+            block_height = excluded.block_height,
+            active = excluded.active
+        WHERE (
+            nft_listing.block_height,
+            CASE WHEN nft_listing.active THEN 0 WHEN nft_listing.price IS NULL THEN 1 ELSE 2 END,
+            length(COALESCE(nft_listing.price, '')),
+            COALESCE(nft_listing.price, '')
+        ) <= (
+            excluded.block_height,
+            CASE WHEN excluded.active THEN 0 WHEN excluded.price IS NULL THEN 1 ELSE 2 END,
+            length(COALESCE(excluded.price, '')),
+            COALESCE(excluded.price, '')
+        )
+        "#,
+    )
+    .bind(listing.marketplace_id)
+    .bind(listing.nft_contract_id)
+    .bind(listing.token_id)
+    .bind(listing.seller_id)
+    .bind(listing.price.map(|price| price.to_string()))
+    .bind(listing.ft_id)
+    .bind(listing.timestamp)
+    .bind(listing.block_height as i64)
+    .bind(listing.active)
+    .execute(tx)
+    .await?;
+    Ok(())
+}
+
+/// Same as the Postgres `rebuild_listings`.
+async fn rebuild_listings(sqlite_pool: &SqlitePool, block_height: u64) -> anyhow::Result<()> {
+    let mut tx = sqlite_pool.begin().await?;
+    let listings: Vec<(String, String, String)> = sqlx::query_as(
+        "DELETE FROM nft_listing WHERE block_height > $1 RETURNING marketplace_id, nft_contract_id, token_id",
+    )
+    .bind(block_height as i64)
+    .fetch_all(&mut *tx)
+    .await?;
+    for (marketplace_id, nft_contract_id, token_id) in listings {
+        sqlx::query(
+            r#"
+            INSERT INTO nft_listing (marketplace_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active)
+            SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, active FROM (
+                SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, TRUE AS active, 0 AS rank
+                FROM nft_list WHERE contract_id = $1 AND nft_contract_id = $2 AND token_id = $3
+                UNION ALL
+                SELECT contract_id, nft_contract_id, token_id, seller_id, NULL, NULL, timestamp, block_height, FALSE, 1
+                FROM nft_unlist WHERE contract_id = $1 AND nft_contract_id = $2 AND token_id = $3
+                UNION ALL
+                SELECT contract_id, nft_contract_id, token_id, seller_id, price, ft_id, timestamp, block_height, FALSE, 2
+                FROM nft_sale WHERE contract_id = $1 AND nft_contract_id = $2 AND token_id = $3
+            )
+            ORDER BY block_height DESC, rank DESC, length(COALESCE(price, '')) DESC, COALESCE(price, '') DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(marketplace_id)
+        .bind(nft_contract_id)
+        .bind(token_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftListHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftListEvent>(&values)
+        {
+            let mut tx = sqlite_pool.begin().await?;
+            set_listing(
+                &mut tx,
+                &Listing {
+                    marketplace_id: &context.contract_id,
+                    nft_contract_id: &event.nft_contract_id,
+                    token_id: &event.token_id,
+                    seller_id: &event.seller_id,
+                    price: Some(event.price),
+                    ft_id: Some(&event.ft_id),
+                    timestamp: timestamp(context.block_timestamp_nanosec),
+                    block_height: context.block_height,
+                    active: true,
+                },
+            )
+            .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO nft_list (timestamp, transaction_id, receipt_id, block_height, contract_id, nft_contract_id, token_id, seller_id, price, ft_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.nft_contract_id)
+            .bind(event.token_id)
+            .bind(event.seller_id)
+            .bind(event.price.to_string())
+            .bind(event.ft_id)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        } else {
+            log::error!("Failed to parse nft list event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_list", block_height).await?;
+        rebuild_listings(sqlite_pool, block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftUnlistHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftUnlistEvent>(&values)
+        {
+            let mut tx = sqlite_pool.begin().await?;
+            set_listing(
+                &mut tx,
+                &Listing {
+                    marketplace_id: &context.contract_id,
+                    nft_contract_id: &event.nft_contract_id,
+                    token_id: &event.token_id,
+                    seller_id: &event.seller_id,
+                    price: None,
+                    ft_id: None,
+                    timestamp: timestamp(context.block_timestamp_nanosec),
+                    block_height: context.block_height,
+                    active: false,
+                },
+            )
+            .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO nft_unlist (timestamp, transaction_id, receipt_id, block_height, contract_id, nft_contract_id, token_id, seller_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.nft_contract_id)
+            .bind(event.token_id)
+            .bind(event.seller_id)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        } else {
+            log::error!("Failed to parse nft unlist event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_unlist", block_height).await?;
+        rebuild_listings(sqlite_pool, block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for NftSaleHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<NftSaleEvent>(&values)
+        {
+            let mut tx = sqlite_pool.begin().await?;
+            set_listing(
+                &mut tx,
+                &Listing {
+                    marketplace_id: &context.contract_id,
+                    nft_contract_id: &event.nft_contract_id,
+                    token_id: &event.token_id,
+                    seller_id: &event.seller_id,
+                    price: Some(event.price),
+                    ft_id: Some(&event.ft_id),
+                    timestamp: timestamp(context.block_timestamp_nanosec),
+                    block_height: context.block_height,
+                    active: false,
+                },
+            )
+            .await?;
+            sqlx::query(
+                r#"
+                INSERT INTO nft_sale (timestamp, transaction_id, receipt_id, block_height, contract_id, nft_contract_id, token_id, seller_id, buyer_id, price, ft_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(context.contract_id)
+            .bind(event.nft_contract_id)
+            .bind(event.token_id)
+            .bind(event.seller_id)
+            .bind(event.buyer_id)
+            .bind(event.price.to_string())
+            .bind(event.ft_id)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(&mut *tx)
+            .await?;
+            tx.commit().await?;
+        } else {
+            log::error!("Failed to parse nft sale event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "nft_sale", block_height).await?;
+        rebuild_listings(sqlite_pool, block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for TradeLiquidityAddHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeLiquidityAddEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO trade_liquidity_add (timestamp, trader, transaction_id, receipt_id, block_height, pool, tokens, amounts, shares, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.trader)
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.pool)
+            .bind(serde_json::to_string(&event.tokens)?)
+            .bind(serde_json::to_string(&event.amounts.iter().map(|amount| amount.to_string()).collect::<Vec<_>>())?)
+            .bind(event.shares.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse liquidity add event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "trade_liquidity_add", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for TradeLiquidityRemoveHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<TradeLiquidityRemoveEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO trade_liquidity_remove (timestamp, trader, transaction_id, receipt_id, block_height, pool, tokens, amounts, shares, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.trader)
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.pool)
+            .bind(serde_json::to_string(&event.tokens)?)
+            .bind(serde_json::to_string(&event.amounts.iter().map(|amount| amount.to_string()).collect::<Vec<_>>())?)
+            .bind(event.shares.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse liquidity remove event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "trade_liquidity_remove", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for AccountCreateHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<AccountCreateEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO account_create (timestamp, transaction_id, receipt_id, block_height, account_id, creator_id, initial_balance, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.account_id)
+            .bind(event.creator_id)
+            .bind(event.initial_balance.to_string())
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse account create event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "account_create", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for AccountDeleteHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<AccountDeleteEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO account_delete (timestamp, transaction_id, receipt_id, block_height, account_id, beneficiary_id, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.account_id)
+            .bind(event.beneficiary_id)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse account delete event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "account_delete", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for ContractDeployHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded {
+            context,
+            event,
+            extra,
+        }) = decode_entry::<ContractDeployEvent>(&values)
+        {
+            sqlx::query(
+                r#"
+                INSERT INTO contract_deploy (timestamp, transaction_id, receipt_id, block_height, account_id, code_hash, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+            )
+            .bind(timestamp(context.block_timestamp_nanosec))
+            .bind(context.transaction_id)
+            .bind(context.receipt_id)
+            .bind(context.block_height as i64)
+            .bind(event.account_id)
+            .bind(event.code_hash)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse contract deploy event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "contract_deploy", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for TransactionHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TransactionEvent>(&values) {
+            sqlx::query(
+                r#"
+                INSERT INTO transactions (timestamp, transaction_id, block_height, block_hash, signer_id, receiver_id, gas_burnt, tokens_burnt, status, extra)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT (transaction_id, timestamp) DO NOTHING
+                "#,
+            )
+            .bind(timestamp(event.block_timestamp_nanosec))
+            .bind(event.transaction_id)
+            .bind(event.block_height as i64)
+            .bind(event.block_hash)
+            .bind(event.signer_id)
+            .bind(event.receiver_id)
+            .bind(event.gas_burnt as i64)
+            .bind(event.tokens_burnt.to_string())
+            .bind(event.status)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse transaction event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "transactions", block_height).await?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl EventHandler<SqlitePool> for TokenPriceHandler {
+    async fn handle(
+        &self,
+        _id: &str,
+        values: HashMap<String, Value>,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        if let Ok(Decoded { event, extra, .. }) = decode_entry::<TokenPriceEvent>(&values) {
+            sqlx::query(
+                r#"
+                INSERT INTO token_price (timestamp, block_height, token_id, price_usd, decimals, extra)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                "#,
+            )
+            .bind(timestamp(event.block_timestamp_nanosec))
+            .bind(event.block_height as i64)
+            .bind(event.token_id)
+            .bind(event.price_usd)
+            .bind(event.decimals as i64)
+            .bind(extra.map(|extra| extra.to_string()))
+            .execute(sqlite_pool)
+            .await?;
+        } else {
+            log::error!("Failed to parse token price event");
+        }
+        Ok(())
+    }
+
+    async fn revert(
+        &self,
+        _id: &str,
+        block_height: u64,
+        sqlite_pool: &SqlitePool,
+    ) -> anyhow::Result<()> {
+        revert_table(sqlite_pool, "token_price", block_height).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(fields: &[(&str, serde_json::Value)]) -> HashMap<String, Value> {
+        fields
+            .iter()
+            .map(|(field, value)| {
+                (
+                    field.to_string(),
+                    Value::Data(value.to_string().into_bytes()),
+                )
+            })
+            .collect()
+    }
+
+    fn context(block_height: u64) -> serde_json::Value {
+        serde_json::json!({
+            "transaction_id": "tx",
+            "receipt_id": format!("receipt{block_height}"),
+            "block_height": block_height,
+            "block_timestamp_nanosec": "1700000000000000000",
+            "contract_id": "token.near",
+        })
+    }
+
+    #[tokio::test]
+    async fn stores_amounts_as_text() {
+        let sqlite_pool = connect("sqlite::memory:").await.unwrap();
+        // More than fits in an i64 or an f64
+        let amount = "340282366920938463463374607431768211455";
+        let values = entry(&[
+            ("context", context(1)),
+            (
+                "transfer",
+                serde_json::json!({
+                    "old_owner_id": "alice.near",
+                    "new_owner_id": "bob.near",
+                    "amount": amount,
+                }),
+            ),
+        ]);
+        FtTransferHandler
+            .handle("1-0", values, &sqlite_pool)
+            .await
+            .unwrap();
+
+        let stored: String = sqlx::query_scalar("SELECT amount FROM ft_transfer")
+            .fetch_one(&sqlite_pool)
+            .await
+            .unwrap();
+        assert_eq!(stored, amount);
+    }
+
+    #[tokio::test]
+    async fn stores_arrays_and_extra_as_json() {
+        let sqlite_pool = connect("sqlite::memory:").await.unwrap();
+        let values = entry(&[
+            ("context", context(1)),
+            (
+                "mint",
+                serde_json::json!({
+                    "owner_id": "alice.near",
+                    "token_ids": ["1", "2"],
+                    "rarity": "rare",
+                }),
+            ),
+        ]);
+        NftMintHandler
+            .handle("1-0", values, &sqlite_pool)
+            .await
+            .unwrap();
+
+        let (token_ids, extra): (String, Option<String>) =
+            sqlx::query_as("SELECT token_ids, extra FROM nft_mint")
+                .fetch_one(&sqlite_pool)
+                .await
+                .unwrap();
+        assert_eq!(
+            serde_json::from_str::<Vec<String>>(&token_ids).unwrap(),
+            ["1", "2"]
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&extra.unwrap()).unwrap(),
+            serde_json::json!({ "mint": { "rarity": "rare" } })
+        );
+    }
+
+    #[tokio::test]
+    async fn reverts_rows_after_block() {
+        let sqlite_pool = connect("sqlite::memory:").await.unwrap();
+        for block_height in 1..=3 {
+            let values = entry(&[
+                ("context", context(block_height)),
+                (
+                    "mint",
+                    serde_json::json!({
+                        "owner_id": "alice.near",
+                        "token_ids": [block_height.to_string()],
+                    }),
+                ),
+            ]);
+            NftMintHandler
+                .handle(&format!("{block_height}-0"), values, &sqlite_pool)
+                .await
+                .unwrap();
+        }
+        NftMintHandler.revert("4-0", 1, &sqlite_pool).await.unwrap();
+
+        let block_heights: Vec<i64> =
+            sqlx::query_scalar("SELECT block_height FROM nft_mint ORDER BY block_height")
+                .fetch_all(&sqlite_pool)
+                .await
+                .unwrap();
+        assert_eq!(block_heights, [1]);
+        assert_eq!(
+            max_block_height(&sqlite_pool, "nft_mint").await.unwrap(),
+            Some(1)
+        );
+    }
+}
@@ -0,0 +1,590 @@
+//! Pushes decoded events to HTTP subscribers. Subscribers are read from a JSON file:
+//!
+//! ```json
+//! [{
+//!     "name": "my-project",
+//!     "url": "https://example.com/hook",
+//!     "secret": "...",
+//!     "events": [{ "stream": "potlock_donation", "fields": { "project_id": "my-project.near" } }]
+//! }]
+//! ```
+//!
+//! An event is sent to a subscriber if any of its filters names the event's stream and
+//! every field in the filter equals the field of the event or its context (or, for arrays,
+//! is contained in it). The body is `{"stream", "id", "context", "event"}` and is signed
+//! with HMAC-SHA256 of the subscriber's secret in the `X-Signature-256` header.
+//!
+//! Deliveries are stored in `webhook_delivery` before sending, so pending ones are
+//! resumed after a restart, and are retried with exponential backoff.
+
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+
+use events_api_redis_to_db::{
+    events::{
+        decode_entry, AccountCreateEvent, AccountDeleteEvent, ContractDeployEvent, Decoded,
+        FtBurnEvent, FtMintEvent, FtTransferEvent, LogNep297Event, NearTransferEvent,
+        NftApproveEvent, NftBurnEvent, NftListEvent, NftMintEvent, NftRevokeEvent, NftSaleEvent,
+        NftTransferEvent, NftUnlistEvent, PotlockDonationEvent, PotlockPotConfigEvent,
+        PotlockPotDonationEvent, PotlockPotPayoutEvent, PotlockPotProjectDonationEvent,
+        PotlockProjectStatusEvent, SocialCommentEvent, SocialFollowEvent, SocialLikeEvent,
+        SocialPostEvent, StreamEvent, TokenPriceEvent, TradeBalanceChangeSwapEvent,
+        TradeLiquidityAddEvent, TradeLiquidityRemoveEvent, TradePoolChangeEvent,
+        TradeRawPoolSwapEvent, TransactionEvent,
+    },
+    redis_reader::{Backpressure, EventHandler},
+};
+use hmac::{Hmac, Mac};
+use redis::Value;
+use serde::Deserialize;
+use sha2::Sha256;
+
+#[derive(Debug, Deserialize)]
+pub struct Subscriber {
+    pub name: String,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<SubscriberFilter>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubscriberFilter {
+    pub stream: String,
+    #[serde(default)]
+    pub fields: HashMap<String, serde_json::Value>,
+}
+
+impl SubscriberFilter {
+    fn matches(&self, stream: &str, payload: &serde_json::Value) -> bool {
+        self.stream == stream
+            && self.fields.iter().all(|(field, expected)| {
+                match payload["event"]
+                    .get(field)
+                    .or_else(|| payload["context"].get(field))
+                {
+                    Some(serde_json::Value::Array(values)) => values.contains(expected),
+                    Some(value) => value == expected,
+                    None => false,
+                }
+            })
+    }
+}
+
+#[derive(Clone)]
+pub struct WebhookSink {
+    subscribers: Arc<Vec<Subscriber>>,
+    pg_pool: sqlx::PgPool,
+    client: reqwest::Client,
+    max_attempts: i32,
+    /// The delay before retrying is this times 2 to the power of the attempts so far.
+    retry_base: Duration,
+}
+
+impl WebhookSink {
+    pub fn new(subscribers_path: &Path, pg_pool: sqlx::PgPool) -> anyhow::Result<Self> {
+        let subscribers: Vec<Subscriber> =
+            serde_json::from_str(&std::fs::read_to_string(subscribers_path)?)?;
+        log::info!("Loaded {} webhook subscribers", subscribers.len());
+        Ok(Self {
+            subscribers: Arc::new(subscribers),
+            pg_pool,
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()?,
+            max_attempts: std::env::var("WEBHOOK_MAX_ATTEMPTS")
+                .map(|attempts| attempts.parse())
+                .unwrap_or(Ok(8))?,
+            retry_base: Duration::from_secs(1),
+        })
+    }
+
+    /// Restarts deliveries that were still pending when the process stopped.
+    pub async fn resume_pending(&self) -> anyhow::Result<()> {
+        let pending = sqlx::query!(
+            r#"
+            SELECT id, subscriber, payload, attempts
+            FROM webhook_delivery
+            WHERE status = 'pending'
+            ORDER BY id
+            "#
+        )
+        .fetch_all(&self.pg_pool)
+        .await?;
+        log::info!("Resuming {} pending webhook deliveries", pending.len());
+        for delivery in pending {
+            let Some(index) = self
+                .subscribers
+                .iter()
+                .position(|subscriber| subscriber.name == delivery.subscriber)
+            else {
+                log::warn!(
+                    "Subscriber {} of delivery {} no longer exists",
+                    delivery.subscriber,
+                    delivery.id
+                );
+                continue;
+            };
+            tokio::spawn(self.clone().deliver(
+                delivery.id,
+                index,
+                delivery.payload.to_string(),
+                delivery.attempts,
+            ));
+        }
+        Ok(())
+    }
+
+    async fn dispatch(
+        &self,
+        stream: &str,
+        id: &str,
+        context: serde_json::Value,
+        event: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        let payload = serde_json::json!({
+            "stream": stream,
+            "id": id,
+            "context": context,
+            "event": event,
+        });
+        for (index, subscriber) in self.subscribers.iter().enumerate() {
+            if !subscriber
+                .events
+                .iter()
+                .any(|filter| filter.matches(stream, &payload))
+            {
+                continue;
+            }
+            let delivery_id = sqlx::query_scalar!(
+                r#"
+                INSERT INTO webhook_delivery (subscriber, stream, entry_id, payload)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (subscriber, stream, entry_id) DO NOTHING
+                RETURNING id
+                "#,
+                subscriber.name,
+                stream,
+                id,
+                payload,
+            )
+            .fetch_optional(&self.pg_pool)
+            .await?;
+            // Already queued before a restart
+            let Some(delivery_id) = delivery_id else {
+                continue;
+            };
+            tokio::spawn(
+                self.clone()
+                    .deliver(delivery_id, index, payload.to_string(), 0),
+            );
+        }
+        Ok(())
+    }
+
+    async fn deliver(self, delivery_id: i64, subscriber_index: usize, body: String, attempts: i32) {
+        let subscriber = &self.subscribers[subscriber_index];
+        let signature = sign(&subscriber.secret, &body);
+
+        for attempt in attempts + 1..=self.max_attempts {
+            let result = self
+                .client
+                .post(&subscriber.url)
+                .header("Content-Type", "application/json")
+                .header("X-Signature-256", &signature)
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            let last_error = result.err().map(|err| err.to_string());
+            let status = match last_error {
+                None => "delivered",
+                Some(_) if attempt == self.max_attempts => "failed",
+                Some(_) => "pending",
+            };
+            if let Err(err) = sqlx::query!(
+                r#"
+                UPDATE webhook_delivery
+                SET status = $2, attempts = $3, last_error = $4, updated_at = now()
+                WHERE id = $1
+                "#,
+                delivery_id,
+                status,
+                attempt,
+                last_error,
+            )
+            .execute(&self.pg_pool)
+            .await
+            {
+                log::error!("Failed to update webhook delivery {delivery_id}: {err:?}");
+            }
+            match status {
+                "delivered" => return,
+                "failed" => {
+                    log::error!(
+                        "Giving up on webhook delivery {delivery_id} to {} after {attempt} attempts: {}",
+                        subscriber.name,
+                        last_error.unwrap_or_default()
+                    );
+                    return;
+                }
+                _ => {
+                    let backoff = self.retry_base * (1 << attempt.min(10));
+                    log::warn!(
+                        "Webhook delivery {delivery_id} to {} failed, retrying in {backoff:?}: {}",
+                        subscriber.name,
+                        last_error.unwrap_or_default()
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+            }
+        }
+    }
+}
+
+/// Value of the `X-Signature-256` header for `body`.
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Deliveries are recorded in Postgres before they're sent.
+impl Backpressure for WebhookSink {
+    fn is_saturated(&self) -> bool {
+        self.pg_pool.is_saturated()
+    }
+}
+
+/// Decodes an entry of `stream` and dispatches it to the matching subscribers.
+async fn dispatch_entry<E: StreamEvent>(
+    webhook_sink: &WebhookSink,
+    stream: &str,
+    id: &str,
+    values: &HashMap<String, Value>,
+) -> anyhow::Result<()> {
+    let Ok(Decoded { context, event, .. }) = decode_entry::<E>(values) else {
+        log::error!("Failed to parse {stream} entry {id}");
+        return Ok(());
+    };
+    if event.is_skipped() {
+        return Ok(());
+    }
+    // Amounts are serialized as decimal strings, so this only fails on a malformed entry,
+    // which shouldn't stop the stream
+    let (context, event) = match (serde_json::to_value(context), serde_json::to_value(event)) {
+        (Ok(context), Ok(event)) => (context, event),
+        (Err(err), _) | (_, Err(err)) => {
+            log::error!("Failed to serialize {stream} entry {id}: {err:?}");
+            return Ok(());
+        }
+    };
+    webhook_sink.dispatch(stream, id, context, event).await
+}
+
+macro_rules! impl_webhook_handlers {
+    ($($stream_key:literal => $handler:ident($event:ident), $partition_key:literal;)*) => {$(
+        #[async_trait::async_trait]
+        impl EventHandler<WebhookSink> for crate::$handler {
+            async fn handle(
+                &self,
+                id: &str,
+                values: HashMap<String, Value>,
+                webhook_sink: &WebhookSink,
+            ) -> anyhow::Result<()> {
+                dispatch_entry::<$event>(webhook_sink, $stream_key, id, &values).await
+            }
+        }
+    )*};
+}
+
+streams!(impl_webhook_handlers);
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    /// Signature header and body of a request received by the stand-in subscriber.
+    type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Local HTTP subscriber that answers with `statuses` in turn, and 200 once they run
+    /// out.
+    async fn stand_in(statuses: Vec<u16>) -> (String, Received) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = Received::default();
+        let requests = received.clone();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let (headers, body) = read_request(&mut socket).await;
+                let signature = headers
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("x-signature-256"))
+                    .map(|(_, value)| value.trim().to_string())
+                    .unwrap_or_default();
+                requests.lock().unwrap().push((signature, body));
+                let status = statuses.next().unwrap_or(200);
+                socket
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    async fn read_request(socket: &mut TcpStream) -> (String, String) {
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request).to_string();
+            if let Some((headers, body)) = text.split_once("\r\n\r\n") {
+                let length = headers
+                    .lines()
+                    .filter_map(|line| line.split_once(':'))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .map(|(_, value)| value.trim().parse().unwrap())
+                    .unwrap_or(0);
+                if body.len() >= length || read == 0 {
+                    return (headers.to_string(), body.to_string());
+                }
+            }
+        }
+    }
+
+    fn sink(pg_pool: sqlx::PgPool, url: String, stream: &str, max_attempts: i32) -> WebhookSink {
+        WebhookSink {
+            subscribers: Arc::new(vec![Subscriber {
+                name: "test".to_string(),
+                url,
+                secret: "secret".to_string(),
+                events: vec![SubscriberFilter {
+                    stream: stream.to_string(),
+                    fields: HashMap::new(),
+                }],
+            }]),
+            pg_pool,
+            client: reqwest::Client::new(),
+            max_attempts,
+            retry_base: Duration::from_millis(10),
+        }
+    }
+
+    /// Waits until no delivery is pending, and returns the status and attempts of each.
+    async fn settled(pg_pool: &sqlx::PgPool) -> Vec<(String, i32)> {
+        for _ in 0..500 {
+            let deliveries: Vec<(String, i32)> =
+                sqlx::query_as("SELECT status, attempts FROM webhook_delivery ORDER BY id")
+                    .fetch_all(pg_pool)
+                    .await
+                    .unwrap();
+            if deliveries.iter().all(|(status, _)| status != "pending") {
+                return deliveries;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Deliveries are still pending");
+    }
+
+    fn near_transfer(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "sender_id": "alice.near",
+            "receiver_id": "bob.near",
+            "amount": "1000",
+            "transaction_id": id,
+            "receipt_id": id,
+            "block_height": 100,
+            "block_timestamp_nanosec": "1700000000000000000",
+        })
+    }
+
+    #[test]
+    fn signs_with_hmac_sha256() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[sqlx::test]
+    async fn retries_failed_deliveries(pg_pool: sqlx::PgPool) {
+        let (url, received) = stand_in(vec![500, 503]).await;
+        let sink = sink(pg_pool.clone(), url, "near_transfer", 8);
+        sink.dispatch(
+            "near_transfer",
+            "1-0",
+            serde_json::Value::Null,
+            near_transfer("a"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(settled(&pg_pool).await, [("delivered".to_string(), 3)]);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 3);
+        for (signature, body) in received.iter() {
+            assert_eq!(signature, &sign("secret", body));
+            let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(payload["stream"], "near_transfer");
+            assert_eq!(payload["id"], "1-0");
+            assert_eq!(payload["event"]["receipt_id"], "a");
+        }
+    }
+
+    #[sqlx::test]
+    async fn gives_up_after_max_attempts(pg_pool: sqlx::PgPool) {
+        let (url, received) = stand_in(vec![500; 10]).await;
+        let sink = sink(pg_pool.clone(), url, "near_transfer", 2);
+        sink.dispatch(
+            "near_transfer",
+            "1-0",
+            serde_json::Value::Null,
+            near_transfer("a"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(settled(&pg_pool).await, [("failed".to_string(), 2)]);
+        assert_eq!(received.lock().unwrap().len(), 2);
+    }
+
+    #[sqlx::test]
+    async fn queues_each_entry_once(pg_pool: sqlx::PgPool) {
+        let (url, received) = stand_in(Vec::new()).await;
+        let sink = sink(pg_pool.clone(), url, "near_transfer", 8);
+        for _ in 0..2 {
+            sink.dispatch(
+                "near_transfer",
+                "1-0",
+                serde_json::Value::Null,
+                near_transfer("a"),
+            )
+            .await
+            .unwrap();
+        }
+
+        assert_eq!(settled(&pg_pool).await, [("delivered".to_string(), 1)]);
+        assert_eq!(received.lock().unwrap().len(), 1);
+    }
+
+    #[sqlx::test]
+    async fn resumes_pending_deliveries(pg_pool: sqlx::PgPool) {
+        // Left pending by a process that stopped after the first attempt
+        sqlx::query(
+            r#"
+            INSERT INTO webhook_delivery (subscriber, stream, entry_id, payload, attempts)
+            VALUES ('test', 'near_transfer', '1-0', $1, 1), ('removed', 'near_transfer', '1-0', $1, 1)
+            "#,
+        )
+        .bind(serde_json::json!({ "stream": "near_transfer", "id": "1-0" }))
+        .execute(&pg_pool)
+        .await
+        .unwrap();
+        let (url, received) = stand_in(Vec::new()).await;
+        let sink = sink(pg_pool.clone(), url, "near_transfer", 8);
+        sink.resume_pending().await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while received.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let statuses: Vec<(String, String, i32)> = sqlx::query_as(
+            "SELECT subscriber, status, attempts FROM webhook_delivery ORDER BY subscriber",
+        )
+        .fetch_all(&pg_pool)
+        .await
+        .unwrap();
+        let (signature, body) = received.lock().unwrap()[0].clone();
+        assert_eq!(signature, sign("secret", &body));
+        assert!(statuses.contains(&("removed".to_string(), "pending".to_string(), 1)));
+        for _ in 0..500 {
+            let (status, attempts): (String, i32) = sqlx::query_as(
+                "SELECT status, attempts FROM webhook_delivery WHERE subscriber = 'test'",
+            )
+            .fetch_one(&pg_pool)
+            .await
+            .unwrap();
+            if status == "delivered" {
+                assert_eq!(attempts, 2);
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("Delivery wasn't resumed");
+    }
+
+    #[sqlx::test]
+    async fn sends_large_amounts_as_strings(pg_pool: sqlx::PgPool) {
+        let (url, received) = stand_in(Vec::new()).await;
+        let sink = sink(pg_pool.clone(), url, "potlock_donation", 8);
+        let values = HashMap::from([
+            (
+                "context".to_string(),
+                Value::Data(
+                    serde_json::json!({
+                        "transaction_id": "tx",
+                        "receipt_id": "receipt",
+                        "block_height": 100,
+                        "block_timestamp_nanosec": "1700000000000000000",
+                    })
+                    .to_string()
+                    .into_bytes(),
+                ),
+            ),
+            (
+                "donation".to_string(),
+                Value::Data(
+                    serde_json::json!({
+                        "donation_id": 1,
+                        "donor_id": "alice.near",
+                        "total_amount": "100000000000000000000000000",
+                        "ft_id": "near",
+                        "message": null,
+                        "donated_at": 1700000000000u64,
+                        "project_id": "project.near",
+                        "protocol_fee": "1",
+                        "referrer_id": "referrer.near",
+                        "referrer_fee": "20000000000000000000000000",
+                    })
+                    .to_string()
+                    .into_bytes(),
+                ),
+            ),
+        ]);
+        EventHandler::handle(&crate::PotlockDonationHandler, "1-0", values, &sink)
+            .await
+            .unwrap();
+
+        assert_eq!(settled(&pg_pool).await, [("delivered".to_string(), 1)]);
+        let payload: serde_json::Value =
+            serde_json::from_str(&received.lock().unwrap()[0].1).unwrap();
+        assert_eq!(
+            payload["event"]["referrer_fee"],
+            "20000000000000000000000000"
+        );
+        assert_eq!(
+            payload["event"]["total_amount"],
+            "100000000000000000000000000"
+        );
+    }
+}